@@ -0,0 +1,73 @@
+// The game is a binary without a library, so the benchmarks bring in the modules they need themselves
+#![allow(dead_code)]
+#[path = "../src/bot.rs"] mod bot;
+#[path = "../src/clear.rs"] mod clear;
+#[path = "../src/daily.rs"] mod daily;
+#[path = "../src/external.rs"] mod external;
+#[path = "../src/game.rs"] mod game;
+#[path = "../src/locale.rs"] mod locale;
+#[path = "../src/mode.rs"] mod mode;
+#[path = "../src/pieces.rs"] mod pieces;
+#[path = "../src/replay.rs"] mod replay;
+#[path = "../src/rules.rs"] mod rules;
+#[path = "../src/save.rs"] mod save;
+
+use std::hint::black_box;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use fltk::enums::Color;
+use bot::{Bot, Difficulty};
+use game::{Cell, Tetris};
+use rules::{ClearGravity, RuleSet};
+
+type Board = Tetris<10, 20>;
+
+// A board some way into a game, the same every run
+fn midgame() -> Board {
+    let mut tetris = Board::with_seed(RuleSet::default(), 1);
+    tetris.start();
+    let bot = Bot::new(Difficulty::Hard);
+    for _ in 0..30 {
+        bot.place_now(&mut tetris);
+    }
+    while tetris.poll_event().is_some() {}
+    tetris
+}
+
+fn can_place_at(c: &mut Criterion) {
+    let tetris = midgame();
+    let (shape, loc) = (tetris.falling.shape, tetris.falling.loc);
+    c.bench_function("can_place_at", |b| b.iter(|| black_box(&tetris).can_place_at(black_box(&shape), black_box(&loc))));
+}
+
+// the falling piece from its spawn all the way down to the stack
+fn step(c: &mut Criterion) {
+    let tetris = midgame();
+    c.bench_function("step", |b| b.iter_batched(|| tetris.clone(), |mut tetris| {
+        for _ in 0..20 {
+            tetris.step();
+        }
+        tetris
+    }, BatchSize::SmallInput));
+}
+
+fn line_clear(c: &mut Criterion) {
+    // four full rows at the bottom under a stack with a gap in every row
+    let mut field = [[Some(Cell::block(Color::Red)); 10]; 20];
+    for (y, row) in field.iter_mut().enumerate().take(16) {
+        row[y % 10] = None;
+    }
+    for gravity in [ClearGravity::Naive, ClearGravity::Sticky, ClearGravity::Cascade] {
+        c.bench_function(&format!("line_clear_{gravity:?}"), |b| b.iter_batched(|| field, |mut field| {
+            clear::resolve(&mut field, gravity)
+        }, BatchSize::SmallInput));
+    }
+}
+
+fn bot_search(c: &mut Criterion) {
+    let tetris = midgame();
+    let bot = Bot::new(Difficulty::Hard);
+    c.bench_function("bot_search", |b| b.iter(|| bot.best_placement(black_box(&tetris)).is_some()));
+}
+
+criterion_group!(engine, can_place_at, step, line_clear, bot_search);
+criterion_main!(engine);
@@ -0,0 +1,106 @@
+use std::process::Command;
+use std::sync::{LazyLock, Mutex, RwLock};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use crate::audio::{Audio, Sound};
+use crate::config::Accessibility;
+use crate::game::{GameEvent, GameState, Tetris};
+use crate::locale::{tr, tr_with};
+
+// Changed from the settings, so it's kept here and written to the config whenever that's saved
+pub static ACCESSIBILITY: RwLock<Accessibility> = RwLock::new(Accessibility::Off);
+
+// Speech takes a while, so it gets a thread of its own that only ever says the latest thing it was
+// handed, rather than falling further and further behind the game
+static SPEECH: LazyLock<Mutex<Sender<String>>> = LazyLock::new(|| {
+    let (sender, receiver) = mpsc::channel::<String>();
+    thread::spawn(move || {
+        while let Ok(text) = receiver.recv() {
+            let text = receiver.try_iter().last().unwrap_or(text);
+            // no speech on this system just means nothing gets said
+            let _ = speech_command(&text).status();
+        }
+    });
+    Mutex::new(sender)
+});
+
+#[cfg(target_os = "macos")]
+fn speech_command(text: &str) -> Command {
+    let mut command = Command::new("say");
+    command.arg(text);
+    command
+}
+
+// speech-dispatcher, which Orca speaks through too
+#[cfg(all(unix, not(target_os = "macos")))]
+fn speech_command(text: &str) -> Command {
+    let mut command = Command::new("spd-say");
+    command.args(["--wait", text]);
+    command
+}
+
+#[cfg(windows)]
+fn speech_command(text: &str) -> Command {
+    let mut command = Command::new("powershell");
+    let text = text.replace('\'', "''");
+    command.args(["-NoProfile", "-Command", &format!("Add-Type -AssemblyName System.Speech; (New-Object System.Speech.Synthesis.SpeechSynthesizer).Speak('{text}')")]);
+    command
+}
+
+fn speak(text: String) {
+    let _ = SPEECH.lock().unwrap().send(text);
+}
+
+// Announces a game as its copies come in: every new piece, the events that matter from the engine's
+// queue, and the stack getting into danger. Everything from one copy is said in one go, after its state
+#[derive(Default)]
+pub struct Announcer {
+    piece: Option<usize>,
+    danger: bool,
+    said: Vec<String>,
+}
+
+impl Announcer {
+    pub fn receive_event(&mut self, event: &GameEvent, audio: &Audio) {
+        let text = match event {
+            GameEvent::LinesCleared(lines) => tr_with("access.lines", &[("lines", lines)]),
+            GameEvent::LevelUp(level) => tr_with("access.level", &[("level", level)]),
+            GameEvent::GameOver => tr("hud.game_over").to_string(),
+            _ => return,
+        };
+        // the cues for these are the sounds they already have
+        self.announce(text, None, audio);
+    }
+
+    pub fn receive_state<const W: usize, const H: usize>(&mut self, tetris: &Tetris<W, H>, audio: &Audio) {
+        if matches!(tetris.state, GameState::RUNNING) && self.piece != Some(tetris.pieces) {
+            self.piece = Some(tetris.pieces);
+            let name = tetris.falling.shape.name().to_char();
+            // pentominoes and custom pieces have no letter to say
+            let text = if name == '*' {tr("access.piece").to_string()} else {name.to_string()};
+            self.announce(text, Some(Sound::Piece(name)), audio);
+        }
+        if self.danger != tetris.in_danger() {
+            self.danger = tetris.in_danger();
+            if self.danger {
+                self.announce(tr("access.danger").to_string(), Some(Sound::Danger), audio);
+            }
+        }
+        if !self.said.is_empty() {
+            speak(self.said.join(", "));
+            self.said.clear();
+        }
+    }
+
+    fn announce(&mut self, text: String, cue: Option<Sound>, audio: &Audio) {
+        match *ACCESSIBILITY.read().unwrap() {
+            Accessibility::Off => {},
+            Accessibility::Speech => self.said.push(text),
+            Accessibility::Cues => {
+                if let Some(cue) = cue {
+                    audio.play(cue);
+                }
+            },
+        }
+    }
+}
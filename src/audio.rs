@@ -0,0 +1,410 @@
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use crate::game::GameEvent;
+
+// every pack is a directory in here, with files named after the sounds, like `line_clear.ogg`
+const SOUND_PACKS_DIR: &str = "sounds";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Sound {
+    Move,
+    Rotate,
+    Lock,
+    LineClear,
+    Tetris,
+    LevelUp,
+    GameOver,
+    // stingers, played on top of the line clear
+    TSpin,
+    BackToBack,
+    PerfectClear,
+    Combo,
+    // the accessibility cues: one for every piece as it comes in, by its letter, and one for the
+    // stack getting too high
+    Piece(char),
+    Danger,
+}
+
+// combos shorter than this are not worth a stinger
+const LONG_COMBO: usize = 3;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Track {
+    Menu,
+    Game,
+    // the game track sped up, for when the stack gets close to the top
+    Danger,
+}
+
+// every part goes from 0 to 1, music and effects are scaled by the master volume
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Volume {
+    pub master: f32,
+    pub music: f32,
+    pub effects: f32,
+}
+
+// only the backend reads these, and there is no backend without the `sound` feature
+#[cfg_attr(not(feature = "sound"), allow(dead_code))]
+enum Command {
+    Play(Sound),
+    Music(Option<Track>),
+    PauseMusic(bool),
+    SetVolume(Volume),
+    SetMuted(bool),
+    SoundPack(Option<String>),
+}
+
+impl Sound {
+    pub fn for_event(event: &GameEvent) -> Option<Sound> {
+        match event {
+            GameEvent::Moved => Some(Sound::Move),
+            GameEvent::Rotated => Some(Sound::Rotate),
+            GameEvent::Locked => Some(Sound::Lock),
+            GameEvent::LinesCleared(4..) => Some(Sound::Tetris),
+            GameEvent::LinesCleared(_) => Some(Sound::LineClear),
+            GameEvent::LevelUp(_) => Some(Sound::LevelUp),
+            GameEvent::GameOver => Some(Sound::GameOver),
+            GameEvent::TSpin(_) => Some(Sound::TSpin),
+            GameEvent::BackToBack => Some(Sound::BackToBack),
+            GameEvent::PerfectClear => Some(Sound::PerfectClear),
+            GameEvent::Combo(combo) if *combo >= LONG_COMBO => Some(Sound::Combo),
+            GameEvent::Combo(_) => None,
+            GameEvent::Chain(_) => Some(Sound::Combo),
+            GameEvent::ItemGained | GameEvent::ItemUsed => None,
+        }
+    }
+}
+
+// Sounds are handed over to a thread that owns the output device, so playing one never blocks the UI,
+// and any thread can play them.
+// Without the `sound` feature, or without an output device, everything here is silent.
+// Music additionally needs the `music` feature.
+pub struct Audio {
+    sender: Option<Sender<Command>>,
+    muted: AtomicBool,
+}
+
+impl Audio {
+    pub fn new(volume: Volume) -> Self {
+        let audio = Self { sender: backend::start(), muted: AtomicBool::new(false) };
+        audio.set_volume(volume);
+        audio
+    }
+
+    pub fn play(&self, sound: Sound) {
+        self.send(Command::Play(sound));
+    }
+
+    // Switching to the track that is already on keeps it playing from where it is
+    pub fn play_music(&self, track: Option<Track>) {
+        self.send(Command::Music(track));
+    }
+
+    pub fn pause_music(&self, paused: bool) {
+        self.send(Command::PauseMusic(paused));
+    }
+
+    pub fn set_volume(&self, volume: Volume) {
+        self.send(Command::SetVolume(volume));
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted.load(Ordering::Relaxed)
+    }
+
+    pub fn toggle_mute(&self) {
+        let muted = !self.muted.fetch_xor(true, Ordering::Relaxed);
+        self.send(Command::SetMuted(muted));
+    }
+
+    // None goes back to the built-in sounds, and so does every sound missing from the pack
+    pub fn set_sound_pack(&self, pack: Option<&str>) {
+        self.send(Command::SoundPack(pack.map(str::to_string)));
+    }
+
+    fn send(&self, command: Command) {
+        if let Some(sender) = self.sender.as_ref() {
+            let _ = sender.send(command);
+        }
+    }
+
+    pub fn play_event(&self, event: &GameEvent) {
+        if let Some(sound) = Sound::for_event(event) {
+            self.play(sound);
+        }
+    }
+}
+
+pub fn sound_packs() -> Vec<String> {
+    let Ok(entries) = fs::read_dir(SOUND_PACKS_DIR) else {
+        return vec![];
+    };
+
+    let mut packs: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    packs.sort();
+    packs
+}
+
+#[cfg(feature = "sound")]
+mod backend {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::io::Cursor;
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::sync::mpsc::{self, Sender};
+    use std::thread;
+    use std::time::Duration;
+    use rodio::{Decoder, OutputStream, Sink, Source};
+    use rodio::source::SineWave;
+    use super::{Command, Sound, Volume, SOUND_PACKS_DIR};
+
+    const SOUNDS: [Sound; 19] = [
+        Sound::Move,
+        Sound::Rotate,
+        Sound::Lock,
+        Sound::LineClear,
+        Sound::Tetris,
+        Sound::LevelUp,
+        Sound::GameOver,
+        Sound::TSpin,
+        Sound::BackToBack,
+        Sound::PerfectClear,
+        Sound::Combo,
+        Sound::Piece('I'),
+        Sound::Piece('O'),
+        Sound::Piece('T'),
+        Sound::Piece('S'),
+        Sound::Piece('Z'),
+        Sound::Piece('J'),
+        Sound::Piece('L'),
+        Sound::Danger,
+    ];
+
+    fn file_name(sound: Sound) -> &'static str {
+        match sound {
+            Sound::Move => "move",
+            Sound::Rotate => "rotate",
+            Sound::Lock => "lock",
+            Sound::LineClear => "line_clear",
+            Sound::Tetris => "tetris",
+            Sound::LevelUp => "level_up",
+            Sound::GameOver => "game_over",
+            Sound::TSpin => "t_spin",
+            Sound::BackToBack => "back_to_back",
+            Sound::PerfectClear => "perfect_clear",
+            Sound::Combo => "combo",
+            Sound::Piece('I') => "piece_i",
+            Sound::Piece('O') => "piece_o",
+            Sound::Piece('T') => "piece_t",
+            Sound::Piece('S') => "piece_s",
+            Sound::Piece('Z') => "piece_z",
+            Sound::Piece('J') => "piece_j",
+            Sound::Piece('L') => "piece_l",
+            Sound::Piece(_) => "piece",
+            Sound::Danger => "danger",
+        }
+    }
+
+    // Files are read once, up front, and decoded again from memory every time they play
+    fn load_pack(name: &str) -> HashMap<Sound, Arc<[u8]>> {
+        let dir = Path::new(SOUND_PACKS_DIR).join(name);
+        let mut pack = HashMap::new();
+
+        for sound in SOUNDS {
+            let file = ["ogg", "wav"].iter()
+                .find_map(|extension| fs::read(dir.join(format!("{name}.{extension}", name = file_name(sound)))).ok());
+            if let Some(bytes) = file {
+                pack.insert(sound, bytes.into());
+            }
+        }
+        pack
+    }
+
+    // (frequency in Hz, length in millis) of every tone, played one after another
+    fn tones(sound: Sound) -> &'static [(f32, u64)] {
+        match sound {
+            Sound::Move => &[(880.0, 15)],
+            Sound::Rotate => &[(1320.0, 20)],
+            Sound::Lock => &[(220.0, 40)],
+            Sound::LineClear => &[(660.0, 60), (880.0, 80)],
+            Sound::Tetris => &[(523.0, 70), (659.0, 70), (784.0, 70), (1047.0, 160)],
+            Sound::LevelUp => &[(784.0, 90), (1047.0, 90), (1568.0, 140)],
+            Sound::GameOver => &[(392.0, 200), (330.0, 200), (262.0, 400)],
+            Sound::TSpin => &[(1175.0, 50), (1397.0, 50), (1175.0, 50), (1397.0, 90)],
+            Sound::BackToBack => &[(1568.0, 60), (2093.0, 120)],
+            Sound::PerfectClear => &[(1047.0, 80), (1319.0, 80), (1568.0, 80), (2093.0, 80), (2637.0, 240)],
+            Sound::Combo => &[(988.0, 40), (1319.0, 80)],
+            // a note of its own for every piece, told apart by pitch and then by rhythm
+            Sound::Piece('I') => &[(523.0, 160)],
+            Sound::Piece('O') => &[(587.0, 60), (587.0, 60)],
+            Sound::Piece('T') => &[(659.0, 40), (784.0, 80)],
+            Sound::Piece('S') => &[(698.0, 50), (880.0, 50)],
+            Sound::Piece('Z') => &[(880.0, 50), (698.0, 50)],
+            Sound::Piece('J') => &[(392.0, 60), (330.0, 90)],
+            Sound::Piece('L') => &[(330.0, 60), (392.0, 90)],
+            Sound::Piece(_) => &[(440.0, 100)],
+            Sound::Danger => &[(1760.0, 80), (1245.0, 80), (1760.0, 80), (1245.0, 80)],
+        }
+    }
+
+    pub fn start() -> Option<Sender<Command>> {
+        let (sender, receiver) = mpsc::channel::<Command>();
+
+        thread::spawn(move || {
+            // the stream has to stay on this thread and alive for as long as anything plays
+            let Ok((_stream, handle)) = OutputStream::try_default() else {
+                return;
+            };
+
+            let mut volume = Volume { master: 1.0, music: 1.0, effects: 1.0 };
+            let mut muted = false;
+            let level = |volume: &Volume, muted: bool, part: f32| if muted {0.0} else {volume.master * part};
+            let mut pack: HashMap<Sound, Arc<[u8]>> = HashMap::new();
+            #[cfg(feature = "music")]
+            let mut music = music::Player::default();
+
+            for command in receiver {
+                match command {
+                    Command::Play(sound) => {
+                        let Ok(sink) = Sink::try_new(&handle) else {
+                            continue;
+                        };
+                        sink.set_volume(level(&volume, muted, volume.effects));
+
+                        let file = pack.get(&sound).and_then(|bytes| Decoder::new(Cursor::new(bytes.clone())).ok());
+                        if let Some(file) = file {
+                            sink.append(file);
+                        } else {
+                            for (frequency, millis) in tones(sound) {
+                                sink.append(SineWave::new(*frequency).take_duration(Duration::from_millis(*millis)).amplify(0.15));
+                            }
+                        }
+                        // every sound gets a sink of its own, rodio mixes them together
+                        sink.detach();
+                    },
+                    Command::SoundPack(name) => pack = name.map(|name| load_pack(&name)).unwrap_or_default(),
+                    Command::SetVolume(changed) => {
+                        volume = changed;
+                        #[cfg(feature = "music")]
+                        music.set_volume(level(&volume, muted, volume.music));
+                    },
+                    Command::SetMuted(changed) => {
+                        muted = changed;
+                        #[cfg(feature = "music")]
+                        music.set_volume(level(&volume, muted, volume.music));
+                    },
+                    #[cfg(feature = "music")]
+                    Command::Music(track) => music.switch(&handle, track),
+                    #[cfg(feature = "music")]
+                    Command::PauseMusic(paused) => music.pause(paused),
+                    #[cfg(not(feature = "music"))]
+                    Command::Music(_) | Command::PauseMusic(_) => {},
+                }
+            }
+        });
+
+        Some(sender)
+    }
+
+    #[cfg(feature = "music")]
+    mod music {
+        use std::time::Duration;
+        use rodio::{OutputStreamHandle, Sink, Source};
+        use rodio::source::SineWave;
+        use super::super::Track;
+
+        // millis per beat
+        fn beat(track: Track) -> u64 {
+            match track {
+                Track::Danger => 100,
+                _ => 150,
+            }
+        }
+
+        // (frequency in Hz, length in beats), a frequency of 0 is a rest
+        fn melody(track: Track) -> &'static [(f32, u64)] {
+            match track {
+                Track::Menu => &[
+                    (392.0, 4), (330.0, 4), (349.0, 4), (294.0, 4),
+                    (330.0, 4), (262.0, 4), (294.0, 6), (0.0, 2),
+                ],
+                // Korobeiniki
+                Track::Game | Track::Danger => &[
+                    (659.0, 2), (494.0, 1), (523.0, 1), (587.0, 2), (523.0, 1), (494.0, 1),
+                    (440.0, 2), (440.0, 1), (523.0, 1), (659.0, 2), (587.0, 1), (523.0, 1),
+                    (494.0, 3), (523.0, 1), (587.0, 2), (659.0, 2),
+                    (523.0, 2), (440.0, 2), (440.0, 2), (0.0, 2),
+                    (587.0, 3), (698.0, 1), (880.0, 2), (784.0, 1), (698.0, 1),
+                    (659.0, 3), (523.0, 1), (659.0, 2), (587.0, 1), (523.0, 1),
+                    (494.0, 2), (494.0, 1), (523.0, 1), (587.0, 2), (659.0, 2),
+                    (523.0, 2), (440.0, 2), (440.0, 2), (0.0, 2),
+                ],
+            }
+        }
+
+        pub struct Player {
+            playing: Option<(Track, Sink)>,
+            volume: f32,
+        }
+
+        impl Default for Player {
+            fn default() -> Self {
+                Self { playing: None, volume: 1.0 }
+            }
+        }
+
+        impl Player {
+            pub fn switch(&mut self, handle: &OutputStreamHandle, track: Option<Track>) {
+                if self.playing.as_ref().map(|(playing, _)| *playing) == track {
+                    return;
+                }
+                self.playing = None;
+
+                let Some(track) = track else {
+                    return;
+                };
+                let Ok(sink) = Sink::try_new(handle) else {
+                    return;
+                };
+                sink.set_volume(self.volume);
+
+                let notes = melody(track).iter().map(|(frequency, beats)| {
+                    let volume = if *frequency > 0.0 {0.06} else {0.0};
+                    SineWave::new(frequency.max(1.0)).take_duration(Duration::from_millis(beats * beat(track))).amplify(volume)
+                });
+                sink.append(rodio::source::from_iter(notes.collect::<Vec<_>>()).repeat_infinite());
+                self.playing = Some((track, sink));
+            }
+
+            pub fn set_volume(&mut self, volume: f32) {
+                self.volume = volume;
+                if let Some((_, sink)) = self.playing.as_ref() {
+                    sink.set_volume(volume);
+                }
+            }
+
+            pub fn pause(&mut self, paused: bool) {
+                if let Some((_, sink)) = self.playing.as_ref() {
+                    if paused {sink.pause()} else {sink.play()}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "sound"))]
+mod backend {
+    use std::sync::mpsc::Sender;
+    use super::Command;
+
+    pub fn start() -> Option<Sender<Command>> {
+        None
+    }
+}
@@ -0,0 +1,86 @@
+use std::sync::Mutex;
+
+// Playing only takes the keyboard, which some systems don't count as being there, so a running game
+// keeps the screen on and the computer from going to sleep
+static STATE: Mutex<State> = Mutex::new(State { inhibitor: None, attempted: false });
+
+struct State {
+    // None while nothing's holding it awake
+    inhibitor: Option<Inhibitor>,
+    // set from when a game asks for it until it's done, so one that couldn't get it isn't tried every frame
+    attempted: bool,
+}
+
+// Safe to call every frame, it only does anything when it changes
+pub fn keep_awake(awake: bool) {
+    let mut state = STATE.lock().unwrap();
+    if awake == state.attempted {
+        return;
+    }
+    state.attempted = awake;
+    state.inhibitor = if awake {Inhibitor::start()} else {None};
+}
+
+// A tool that holds the system awake for as long as it runs, told to also stop with the game in case
+// the game goes down without releasing it
+#[cfg(not(windows))]
+struct Inhibitor(std::process::Child);
+
+#[cfg(not(windows))]
+impl Inhibitor {
+    fn start() -> Option<Inhibitor> {
+        use std::process::Command;
+        let pid = std::process::id().to_string();
+        #[cfg(target_os = "macos")]
+        let command = Command::new("caffeinate").args(["-d", "-i", "-w", &pid]).spawn();
+        #[cfg(not(target_os = "macos"))]
+        let command = Command::new("systemd-inhibit")
+            .args(["--what=idle:sleep", "--who=Tetris", "--why=A game is running", "tail", &format!("--pid={pid}"), "-f", "/dev/null"])
+            .spawn();
+        command.map(Inhibitor).map_err(|err| eprintln!("can't keep the computer awake: {err}")).ok()
+    }
+}
+
+#[cfg(not(windows))]
+impl Drop for Inhibitor {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+// Windows has a flag for it instead, which stays set until it's cleared
+#[cfg(windows)]
+struct Inhibitor;
+
+#[cfg(windows)]
+const ES_CONTINUOUS: u32 = 0x8000_0000;
+#[cfg(windows)]
+const ES_SYSTEM_REQUIRED: u32 = 0x0000_0001;
+#[cfg(windows)]
+const ES_DISPLAY_REQUIRED: u32 = 0x0000_0002;
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+extern "system" {
+    fn SetThreadExecutionState(flags: u32) -> u32;
+}
+
+#[cfg(windows)]
+impl Inhibitor {
+    fn start() -> Option<Inhibitor> {
+        // 0 is what it returns when it failed
+        if unsafe { SetThreadExecutionState(ES_CONTINUOUS | ES_SYSTEM_REQUIRED | ES_DISPLAY_REQUIRED) } == 0 {
+            eprintln!("can't keep the computer awake");
+            return None;
+        }
+        Some(Inhibitor)
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Inhibitor {
+    fn drop(&mut self) {
+        unsafe { SetThreadExecutionState(ES_CONTINUOUS) };
+    }
+}
@@ -0,0 +1,34 @@
+use std::time::Instant;
+use crate::bot::{Bot, Difficulty};
+use crate::game::{GameState, Tetris};
+
+// Good placements can keep a game going for a very long time, so every game gets cut off here
+const MAX_PIECES: usize = 10_000;
+
+pub fn run<const W: usize, const H: usize>(games: usize) {
+    let bot = Bot::new(Difficulty::Hard);
+    let (mut lines, mut score, mut pieces) = (0, 0, 0);
+    let started = Instant::now();
+
+    for game in 1..=games {
+        let mut tetris = Tetris::<W, H>::new();
+        tetris.start();
+
+        while !matches!(tetris.state, GameState::LOST) && tetris.pieces < MAX_PIECES {
+            bot.place_now(&mut tetris);
+            while tetris.poll_event().is_some() {}
+        }
+
+        println!("game {game}: {lines} lines, {score} score, {pieces} pieces", lines = tetris.lines, score = tetris.score, pieces = tetris.pieces);
+        lines += tetris.lines;
+        score += tetris.score;
+        pieces += tetris.pieces;
+    }
+
+    let games = games.max(1) as f64;
+    let seconds = started.elapsed().as_secs_f64();
+    println!("average lines: {:.1}", lines as f64 / games);
+    println!("average score: {:.1}", score as f64 / games);
+    println!("average pieces: {:.1}", pieces as f64 / games);
+    println!("pieces per second: {:.0}", pieces as f64 / seconds.max(f64::EPSILON));
+}
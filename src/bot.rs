@@ -0,0 +1,280 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+use crate::external::ExternalBot;
+use crate::game::{GameState, Input, Move, Point, Shape, ShapeIter, Tetris};
+use crate::locale::tr;
+
+#[derive(Clone, Copy, Debug)]
+enum Action {
+    Left,
+    Right,
+    Rotate,
+    Drop,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+    Insane,
+}
+
+impl Difficulty {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => tr("difficulty.easy"),
+            Difficulty::Normal => tr("difficulty.normal"),
+            Difficulty::Hard => tr("difficulty.hard"),
+            Difficulty::Insane => tr("difficulty.insane"),
+        }
+    }
+
+    pub fn next(&self) -> Difficulty {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Insane,
+            Difficulty::Insane => Difficulty::Easy,
+        }
+    }
+
+    pub fn weights(&self) -> Weights {
+        match self {
+            Difficulty::Easy => Weights { height: -0.2, lines: 0.2, holes: -0.05, bumpiness: -0.02 },
+            Difficulty::Normal => Weights { height: -0.3, lines: 0.3, holes: -0.15, bumpiness: -0.05 },
+            Difficulty::Hard => Weights { height: -0.45, lines: 0.6, holes: -0.3, bumpiness: -0.15 },
+            Difficulty::Insane => Weights::default(),
+        }
+    }
+
+    // (pause before the first move of a piece, pause between moves) in millis
+    pub fn delays(&self) -> (u128, u128) {
+        match self {
+            Difficulty::Easy => (600, 250),
+            Difficulty::Normal => (300, 150),
+            Difficulty::Hard => (150, 80),
+            Difficulty::Insane => (0, 20),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Weights {
+    pub height: f64,
+    pub lines: f64,
+    pub holes: f64,
+    pub bumpiness: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            height: -0.510066,
+            lines: 0.760666,
+            holes: -0.35663,
+            bumpiness: -0.184483,
+        }
+    }
+}
+
+pub struct Placement {
+    pub shape: Shape,
+    pub loc: Point,
+    pub inputs: Vec<Input>,
+    score: f64,
+}
+
+#[derive(Clone)]
+pub struct Bot {
+    weights: Weights,
+    reaction_delay: u128,
+    action_delay: u128,
+    plan: VecDeque<Action>,
+    planned_piece: Option<usize>,
+    // nothing is done with a new piece until then, as if the bot was still looking at it
+    reacting_until: SystemTime,
+    // None before the first move of a piece
+    last_action: Option<SystemTime>,
+    // a program on the bot socket that picks the placements instead, from the ones it's offered
+    external: Option<ExternalBot>,
+    offered: Vec<Move>,
+}
+
+impl Bot {
+    pub fn new(difficulty: Difficulty) -> Self {
+        let (reaction_delay, action_delay) = difficulty.delays();
+        Self::with_weights(difficulty.weights(), reaction_delay, action_delay)
+    }
+
+    pub fn with_weights(weights: Weights, reaction_delay: u128, action_delay: u128) -> Self {
+        Self {
+            weights,
+            reaction_delay,
+            action_delay,
+            plan: VecDeque::new(),
+            planned_piece: None,
+            reacting_until: SystemTime::now(),
+            last_action: None,
+            external: None,
+            offered: vec![],
+        }
+    }
+
+    // Plays the moves `external` picks, as quickly as the bots of `difficulty` do
+    pub fn external(difficulty: Difficulty, external: ExternalBot) -> Self {
+        Self { external: Some(external), ..Self::new(difficulty) }
+    }
+
+    pub fn drive<const W: usize, const H: usize>(&mut self, tetris: &mut Tetris<W, H>) {
+
+        if !matches!(tetris.state, GameState::RUNNING) {
+            return;
+        }
+        let now = SystemTime::now();
+
+        if self.planned_piece != Some(tetris.pieces) {
+            self.plan = match self.external.as_ref() {
+                Some(external) => {
+                    self.offered = Self::droppable_moves(tetris);
+                    external.offer(tetris, &self.offered);
+                    VecDeque::new()
+                },
+                None => self.find_plan(tetris),
+            };
+            self.planned_piece = Some(tetris.pieces);
+            self.reacting_until = now + Duration::from_millis(self.reaction_delay as u64);
+            self.last_action = None;
+        }
+        // the piece just falls until the answer comes in
+        let answer = self.external.as_ref().and_then(|external| external.take_answer(tetris.pieces));
+        if let Some(placement) = answer.and_then(|chosen| self.offered.get(chosen)) {
+            self.plan = Self::plan_of(&placement.inputs);
+        }
+
+        if now < self.reacting_until {
+            return;
+        }
+        let waited = self.last_action.map(|last| now.duration_since(last).unwrap_or_default().as_millis());
+        if waited.is_some_and(|waited| waited < self.action_delay) {
+            return;
+        }
+        self.last_action = Some(now);
+
+        // items get used as soon as the bot gets to them, which takes a move like any other
+        if tetris.item.is_some() {
+            tetris.receive_use_item();
+        } else if let Some(action) = self.plan.pop_front() {
+            Self::perform(tetris, action);
+        }
+    }
+
+    // Places the falling piece right away, for running games without a clock
+    pub fn place_now<const W: usize, const H: usize>(&self, tetris: &mut Tetris<W, H>) {
+        for action in self.find_plan(tetris) {
+            Self::perform(tetris, action);
+        }
+    }
+
+    fn perform<const W: usize, const H: usize>(tetris: &mut Tetris<W, H>, action: Action) {
+        match action {
+            Action::Left => tetris.receive_left(),
+            Action::Right => tetris.receive_right(),
+            Action::Rotate => tetris.receive_rotate(),
+            Action::Drop => tetris.receive_hard_drop(),
+        }
+    }
+
+    // The bot only ever hard drops, so it gets to the places a piece lands on from the height it's falling at
+    // now, and never under overhangs
+    fn droppable_moves<const W: usize, const H: usize>(tetris: &Tetris<W, H>) -> Vec<Move> {
+        let height = tetris.falling.loc.y;
+        tetris.legal_moves().into_iter()
+            .filter(|candidate| {
+                let above = Point::new(candidate.loc.x, height);
+                tetris.can_place_at(&candidate.shape, &above) && tetris.landing_position(&candidate.shape, &above).y == candidate.loc.y
+            })
+            .collect()
+    }
+
+    fn find_plan<const W: usize, const H: usize>(&self, tetris: &Tetris<W, H>) -> VecDeque<Action> {
+        match self.best_of(tetris, Self::droppable_moves(tetris)) {
+            Some(placement) => Self::plan_of(&placement.inputs),
+            None => VecDeque::new(),
+        }
+    }
+
+    fn plan_of(inputs: &[Input]) -> VecDeque<Action> {
+        let mut plan: VecDeque<Action> = inputs.iter()
+            .filter_map(|input| match input {
+                Input::Left => Some(Action::Left),
+                Input::Right => Some(Action::Right),
+                Input::Rotate => Some(Action::Rotate),
+                Input::Down => None,
+            })
+            .collect();
+        plan.push_back(Action::Drop);
+        plan
+    }
+
+    // Tucks included, since a player can make them even though the bot can't
+    pub fn best_placement<const W: usize, const H: usize>(&self, tetris: &Tetris<W, H>) -> Option<Placement> {
+        self.best_of(tetris, tetris.legal_moves())
+    }
+
+    fn best_of<const W: usize, const H: usize>(&self, tetris: &Tetris<W, H>, moves: Vec<Move>) -> Option<Placement> {
+        let mut best: Option<Placement> = None;
+        for Move { shape, loc, inputs } in moves {
+            let score = self.evaluate(tetris, &shape, &loc);
+            if best.as_ref().is_some_and(|best| best.score >= score) {
+                continue;
+            }
+            best = Some(Placement { shape, loc, inputs, score });
+        }
+        best
+    }
+
+    fn evaluate<const W: usize, const H: usize>(&self, tetris: &Tetris<W, H>, shape: &Shape, loc: &Point) -> f64 {
+        // locking over the top loses the game
+        if ShapeIter::new(shape, loc).any(|p| p.is_above_field()) {
+            return f64::NEG_INFINITY;
+        }
+        let mut field = tetris.field.map(|row| row.map(|cell| cell.is_some()));
+        ShapeIter::new(shape, loc).for_each_mut(|p| field[p.y][p.x] = true);
+
+        let rows: Vec<[bool; W]> = field.iter()
+            .filter(|row| !row.iter().all(|cell| *cell))
+            .copied()
+            .collect();
+        let lines = H - rows.len();
+
+        let mut heights = [0usize; W];
+        let mut holes = 0;
+        for (x, height) in heights.iter_mut().enumerate() {
+            if let Some(top) = rows.iter().position(|row| row[x]) {
+                *height = rows.len() - top;
+                holes += rows[top..].iter().filter(|row| !row[x]).count();
+            }
+        }
+
+        let height: usize = heights.iter().sum();
+        let bumpiness: usize = heights.windows(2).map(|pair| pair[0].abs_diff(pair[1])).sum();
+
+        self.weights.height * height as f64
+            + self.weights.lines * lines as f64
+            + self.weights.holes * holes as f64
+            + self.weights.bumpiness * bumpiness as f64
+    }
+}
+
+// What a player would press for a tap of a placement, for tests that play the bot's moves through a game
+#[cfg(test)]
+pub fn taps(input: Input) -> &'static [crate::replay::Recorded] {
+    use crate::replay::Recorded;
+    match input {
+        Input::Left => &[Recorded::LeftPress, Recorded::LeftRelease],
+        Input::Right => &[Recorded::RightPress, Recorded::RightRelease],
+        Input::Rotate => &[Recorded::Rotate],
+        Input::Down => &[Recorded::Tick],
+    }
+}
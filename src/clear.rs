@@ -0,0 +1,220 @@
+use crate::game::Cell;
+use crate::rules::ClearGravity;
+
+// Clears every full row and lets the rest of the field fall the way the gravity says, over and over
+// for as long as falling blocks fill more rows. Gives how many rows went in every round, the first
+// one being the rows that were full to begin with, and nothing when no row was
+pub fn resolve<const W: usize, const H: usize>(field: &mut [[Option<Cell>; W]; H], gravity: ClearGravity) -> Vec<usize> {
+    let mut rounds = vec![];
+    loop {
+        let full = (0..H).filter(|y| field[*y].iter().all(|cell| cell.is_some())).count();
+        if full == 0 {
+            return rounds;
+        }
+        rounds.push(full);
+
+        match gravity {
+            ClearGravity::Naive => shift_down(field),
+            ClearGravity::Sticky | ClearGravity::Cascade => {
+                for row in field.iter_mut().filter(|row| row.iter().all(|cell| cell.is_some())) {
+                    row.fill(None);
+                }
+                drop_groups(field, gravity == ClearGravity::Cascade);
+            },
+        }
+    }
+}
+
+// Everything above a cleared row comes down by as many rows as were cleared below it
+fn shift_down<const W: usize, const H: usize>(field: &mut [[Option<Cell>; W]; H]) {
+    let mut moving = 0;
+    for y in (0..H).rev() {
+        field.swap(y, y + moving);
+        if field[y + moving].iter().all(|cell| cell.is_some()) {
+            field[y + moving].fill(None);
+            moving += 1;
+        }
+    }
+}
+
+// Blocks that touch fall together, as far down as they go. With `by_color` only blocks of the same
+// color hold on to each other, so every piece that's still whole falls on its own
+fn drop_groups<const W: usize, const H: usize>(field: &mut [[Option<Cell>; W]; H], by_color: bool) {
+    loop {
+        let mut moved = false;
+        let mut groups = groups(field, by_color);
+        // the lowest ones first, so the ones above can land on them
+        groups.sort_by_key(|group| std::cmp::Reverse(group.iter().map(|(_, y)| *y).max()));
+
+        for group in groups {
+            let cells: Vec<Option<Cell>> = group.iter().map(|(x, y)| field[*y][*x]).collect();
+            for (x, y) in group.iter() {
+                field[*y][*x] = None;
+            }
+            let fits = |drop: usize| group.iter().all(|(x, y)| y + drop < H && field[y + drop][*x].is_none());
+            let mut drop = 0;
+            while fits(drop + 1) {
+                drop += 1;
+            }
+            for ((x, y), cell) in group.iter().zip(cells) {
+                field[y + drop][*x] = cell;
+            }
+            moved |= drop > 0;
+        }
+        if !moved {
+            return;
+        }
+    }
+}
+
+fn groups<const W: usize, const H: usize>(field: &[[Option<Cell>; W]; H], by_color: bool) -> Vec<Vec<(usize, usize)>> {
+    let mut seen = [[false; W]; H];
+    let mut groups = vec![];
+    for y in 0..H {
+        for x in 0..W {
+            if seen[y][x] || field[y][x].is_none() {
+                continue;
+            }
+            seen[y][x] = true;
+            let mut group = vec![];
+            let mut queue = vec![(x, y)];
+            while let Some((x, y)) = queue.pop() {
+                group.push((x, y));
+                let neighbours = [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)];
+                for (nx, ny) in neighbours {
+                    if nx >= W || ny >= H || seen[ny][nx] {
+                        continue;
+                    }
+                    let joins = field[ny][nx].is_some() && (!by_color || field[ny][nx].map(|cell| cell.color) == field[y][x].map(|cell| cell.color));
+                    if joins {
+                        seen[ny][nx] = true;
+                        queue.push((nx, ny));
+                    }
+                }
+            }
+            groups.push(group);
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use fltk::enums::Color;
+    use super::*;
+
+    // Every letter a color of its own, `.` an empty cell
+    fn field(rows: [&str; 6]) -> [[Option<Cell>; 4]; 6] {
+        rows.map(|row| {
+            let mut cells = [None; 4];
+            for (x, letter) in row.chars().enumerate().filter(|(_, letter)| *letter != '.') {
+                cells[x] = Some(Cell { color: Color::from_rgb(letter as u8, 0, 0), piece: None, garbage: false, locked_at: Duration::ZERO });
+            }
+            cells
+        })
+    }
+
+    fn rows(field: &[[Option<Cell>; 4]; 6]) -> Vec<String> {
+        field.iter()
+            .map(|row| row.iter().map(|cell| cell.map_or('.', |cell| (cell.color.bits() >> 24) as u8 as char)).collect())
+            .collect()
+    }
+
+    fn resolved(gravity: ClearGravity, before: [&str; 6]) -> (Vec<usize>, Vec<String>) {
+        let mut field = field(before);
+        let rounds = resolve(&mut field, gravity);
+        (rounds, rows(&field))
+    }
+
+    #[test]
+    fn naive_gravity_shifts_everything_and_leaves_blocks_floating() {
+        let before = [
+            "....",
+            ".a..",
+            "....",
+            "bbbb",
+            "a...",
+            "bbbb",
+        ];
+        let after = [
+            "....",
+            "....",
+            "....",
+            ".a..",
+            "....",
+            "a...",
+        ];
+        assert_eq!(resolved(ClearGravity::Naive, before), (vec![2], after.map(String::from).to_vec()));
+    }
+
+    #[test]
+    fn sticky_gravity_drops_whatever_touches_together() {
+        let before = [
+            "....",
+            "aa..",
+            "b...",
+            "bbbb",
+            ".x..",
+            ".x..",
+        ];
+        let after = [
+            "....",
+            "....",
+            "....",
+            "aa..",
+            "bx..",
+            ".x..",
+        ];
+        assert_eq!(resolved(ClearGravity::Sticky, before), (vec![1], after.map(String::from).to_vec()));
+    }
+
+    #[test]
+    fn cascade_gravity_drops_every_color_on_its_own() {
+        let before = [
+            "....",
+            "aa..",
+            "b...",
+            "bbbb",
+            ".x..",
+            ".x..",
+        ];
+        let after = [
+            "....",
+            "....",
+            "....",
+            "aa..",
+            ".x..",
+            "bx..",
+        ];
+        assert_eq!(resolved(ClearGravity::Cascade, before), (vec![1], after.map(String::from).to_vec()));
+    }
+
+    #[test]
+    fn blocks_falling_into_gaps_clear_again_as_a_chain() {
+        let before = [
+            "....",
+            "..c.",
+            "..c.",
+            "bbbb",
+            "aa.a",
+            "aa.a",
+        ];
+        for gravity in [ClearGravity::Sticky, ClearGravity::Cascade] {
+            assert_eq!(resolved(gravity, before), (vec![1, 2], ["...."; 6].map(String::from).to_vec()));
+        }
+    }
+
+    #[test]
+    fn nothing_full_is_no_rounds() {
+        let before = [
+            "....",
+            "....",
+            "....",
+            "....",
+            "aa.a",
+            "aa.a",
+        ];
+        assert_eq!(resolved(ClearGravity::Sticky, before), (vec![], before.map(String::from).to_vec()));
+    }
+}
@@ -0,0 +1,257 @@
+use std::fs;
+use fltk::enums::Color;
+use crate::audio::Volume;
+use crate::game::Handling;
+use crate::keys::{key_name, parse_key, Action, KeyBindings};
+use crate::save::save_or_report;
+
+const CONFIG_FILE: &str = "config.toml";
+pub const MIN_CELL_SIZE: i32 = 24;
+pub const MAX_CELL_SIZE: i32 = 56;
+pub const MIN_GAME_SPEED: f64 = 0.5;
+pub const MAX_DAS: u128 = 500;
+pub const MAX_ARR: u128 = 100;
+pub const MAX_SOFT_DROP_FACTOR: u32 = 40;
+const MIN_RATE: u32 = 10;
+const MAX_RATE: u32 = 1000;
+
+// A solid background behind the game, for cutting it out of a stream with a chroma key filter
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ChromaKey {
+    Off,
+    Green,
+    Magenta,
+}
+
+impl ChromaKey {
+    pub const ALL: [ChromaKey; 3] = [ChromaKey::Off, ChromaKey::Green, ChromaKey::Magenta];
+
+    pub fn config_key(&self) -> &'static str {
+        match self {
+            ChromaKey::Off => "off",
+            ChromaKey::Green => "green",
+            ChromaKey::Magenta => "magenta",
+        }
+    }
+
+    // None keeps the usual background
+    pub fn color(&self) -> Option<Color> {
+        match self {
+            ChromaKey::Off => None,
+            ChromaKey::Green => Some(Color::from_rgb(0, 255, 0)),
+            ChromaKey::Magenta => Some(Color::from_rgb(255, 0, 255)),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Theme {
+    Classic,
+    // black and white with a bright color for every kind of piece, for low vision
+    HighContrast,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 2] = [Theme::Classic, Theme::HighContrast];
+
+    pub fn config_key(&self) -> &'static str {
+        match self {
+            Theme::Classic => "classic",
+            Theme::HighContrast => "high_contrast",
+        }
+    }
+}
+
+// How the game tells what's happening on the field to players who can't see it
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Accessibility {
+    Off,
+    // read out by the system's speech, which screen readers speak through as well
+    Speech,
+    // a sound for every piece and for danger, on top of the usual ones
+    Cues,
+}
+
+impl Accessibility {
+    pub const ALL: [Accessibility; 3] = [Accessibility::Off, Accessibility::Speech, Accessibility::Cues];
+
+    pub fn config_key(&self) -> &'static str {
+        match self {
+            Accessibility::Off => "off",
+            Accessibility::Speech => "speech",
+            Accessibility::Cues => "cues",
+        }
+    }
+}
+
+// Player preferences, kept next to the game as flat `key = value` lines
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub volume: Volume,
+    // None plays the built-in sounds
+    pub sound_pack: Option<String>,
+    // None keeps results on this computer only
+    pub leaderboard_url: Option<String>,
+    // every finished game gets a record added to it, as CSV for a `.csv` file and JSON lines otherwise
+    pub export_file: Option<String>,
+    // the Discord application the game shows up as, only used with the `discord` feature
+    pub discord_app_id: Option<String>,
+    // where bot programs can connect to play as versus opponents, None keeps the bot socket closed
+    pub bot_port: Option<u16>,
+    // the Twitch channel whose chat plays in the chat plays mode, which is only there when it's set
+    pub twitch_channel: Option<String>,
+    // how big a cell of the field is on screen, everything else scales along with it
+    pub cell_size: i32,
+    // where the window was last left, None lets it open at the default spot.
+    // Its size isn't kept, that follows from cell_size
+    pub window_position: Option<(i32, i32)>,
+    pub fullscreen: bool,
+    // just the field and the next piece, for small screens and stream overlays
+    pub compact_layout: bool,
+    // the field on the right and the panels on the left
+    pub left_handed: bool,
+    // a 16:9 window with the field in the middle, for capturing in a stream
+    pub stream_layout: bool,
+    pub chroma_key: ChromaKey,
+    pub theme: Theme,
+    pub accessibility: Accessibility,
+    // rows the falling piece would clear are lit up on the field
+    pub clear_preview: bool,
+    // pieces jump from row to row and cleared rows go at once, with nothing sliding or wiping across
+    pub reduce_motion: bool,
+    // everything in a game takes 1/game_speed as long, down to half the pace. Slowed games don't
+    // make it onto the leaderboards
+    pub game_speed: f64,
+    // a running game pauses itself when the window loses focus or gets minimized
+    pub auto_pause: bool,
+    pub handling: Handling,
+    // code of one of the LOCALES
+    pub language: String,
+    // how often a game moves along and how often it gets drawn, both per second. The window as a
+    // whole keeps to the drawing rate
+    pub tick_rate: u32,
+    pub render_fps: u32,
+    pub keys: KeyBindings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            volume: Volume { master: 0.8, music: 0.6, effects: 1.0 },
+            sound_pack: None,
+            leaderboard_url: None,
+            export_file: None,
+            discord_app_id: None,
+            bot_port: None,
+            twitch_channel: None,
+            cell_size: 40,
+            window_position: None,
+            fullscreen: false,
+            compact_layout: false,
+            left_handed: false,
+            stream_layout: false,
+            chroma_key: ChromaKey::Off,
+            theme: Theme::Classic,
+            accessibility: Accessibility::Off,
+            clear_preview: false,
+            reduce_motion: false,
+            game_speed: 1.0,
+            auto_pause: true,
+            handling: Handling::default(),
+            language: "en".to_string(),
+            tick_rate: 60,
+            render_fps: 60,
+            keys: KeyBindings::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Self {
+        let mut config = Self::default();
+
+        for line in fs::read_to_string(CONFIG_FILE).unwrap_or_default().lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+
+            // keys from other versions of the game and broken values are left at their defaults
+            let volume = || value.parse::<f32>().ok().map(|volume| volume.clamp(0.0, 1.0));
+            match key.trim() {
+                "master_volume" => config.volume.master = volume().unwrap_or(config.volume.master),
+                "music_volume" => config.volume.music = volume().unwrap_or(config.volume.music),
+                "effects_volume" => config.volume.effects = volume().unwrap_or(config.volume.effects),
+                "sound_pack" => config.sound_pack = Some(value.trim_matches('"').to_string()).filter(|pack| !pack.is_empty()),
+                "leaderboard_url" => config.leaderboard_url = Some(value.trim_matches('"').to_string()).filter(|url| !url.is_empty()),
+                "export_file" => config.export_file = Some(value.trim_matches('"').to_string()).filter(|file| !file.is_empty()),
+                "discord_app_id" => config.discord_app_id = Some(value.trim_matches('"').to_string()).filter(|id| !id.is_empty()),
+                "bot_port" => config.bot_port = value.parse().ok(),
+                "twitch_channel" => config.twitch_channel = Some(value.trim_matches('"').to_string()).filter(|channel| !channel.is_empty()),
+                "cell_size" => config.cell_size = value.parse().map_or(config.cell_size, |size: i32| size.clamp(MIN_CELL_SIZE, MAX_CELL_SIZE)),
+                "window_position" => config.window_position = value.split_once(',').and_then(|(x, y)| Some((x.trim().parse().ok()?, y.trim().parse().ok()?))),
+                "fullscreen" => config.fullscreen = value.parse().unwrap_or(config.fullscreen),
+                "compact_layout" => config.compact_layout = value.parse().unwrap_or(config.compact_layout),
+                "left_handed" => config.left_handed = value.parse().unwrap_or(config.left_handed),
+                "stream_layout" => config.stream_layout = value.parse().unwrap_or(config.stream_layout),
+                "chroma_key" => config.chroma_key = ChromaKey::ALL.into_iter().find(|chroma_key| chroma_key.config_key() == value).unwrap_or(config.chroma_key),
+                "theme" => config.theme = Theme::ALL.into_iter().find(|theme| theme.config_key() == value).unwrap_or(config.theme),
+                "accessibility" => config.accessibility = Accessibility::ALL.into_iter().find(|accessibility| accessibility.config_key() == value).unwrap_or(config.accessibility),
+                "clear_preview" => config.clear_preview = value.parse().unwrap_or(config.clear_preview),
+                "reduce_motion" => config.reduce_motion = value.parse().unwrap_or(config.reduce_motion),
+                "game_speed" => config.game_speed = value.parse().map_or(config.game_speed, |speed: f64| speed.clamp(MIN_GAME_SPEED, 1.0)),
+                "language" => config.language = value.trim_matches('"').to_string(),
+                "auto_pause" => config.auto_pause = value.parse().unwrap_or(config.auto_pause),
+                "das" => config.handling.das = value.parse().map_or(config.handling.das, |das: u128| das.min(MAX_DAS)),
+                "arr" => config.handling.arr = value.parse().map_or(config.handling.arr, |arr: u128| arr.min(MAX_ARR)),
+                "tap_only" => config.handling.tap_only = value.parse().unwrap_or(config.handling.tap_only),
+                "soft_drop_factor" => config.handling.soft_drop_factor = value.parse().map_or(config.handling.soft_drop_factor, |factor: u32| factor.clamp(1, MAX_SOFT_DROP_FACTOR)),
+                "tick_rate" => config.tick_rate = value.parse().map_or(config.tick_rate, |rate: u32| rate.clamp(MIN_RATE, MAX_RATE)),
+                "render_fps" => config.render_fps = value.parse().map_or(config.render_fps, |fps: u32| fps.clamp(MIN_RATE, MAX_RATE)),
+                key => {
+                    let action = Action::ALL.iter().find(|action| action.config_key() == key);
+                    if let (Some(action), Some(bound)) = (action, parse_key(value)) {
+                        config.keys.set(*action, bound);
+                    }
+                },
+            }
+        }
+        config
+    }
+
+    pub fn save(&self) {
+        let mut lines = vec![
+            format!("master_volume = {}", self.volume.master),
+            format!("music_volume = {}", self.volume.music),
+            format!("effects_volume = {}", self.volume.effects),
+            format!("sound_pack = \"{}\"", self.sound_pack.as_deref().unwrap_or_default()),
+            format!("leaderboard_url = \"{}\"", self.leaderboard_url.as_deref().unwrap_or_default()),
+            format!("export_file = \"{}\"", self.export_file.as_deref().unwrap_or_default()),
+            format!("discord_app_id = \"{}\"", self.discord_app_id.as_deref().unwrap_or_default()),
+            self.bot_port.map_or("bot_port =".to_string(), |port| format!("bot_port = {port}")),
+            format!("twitch_channel = \"{}\"", self.twitch_channel.as_deref().unwrap_or_default()),
+            format!("cell_size = {}", self.cell_size),
+            self.window_position.map_or("window_position =".to_string(), |(x, y)| format!("window_position = {x},{y}")),
+            format!("fullscreen = {}", self.fullscreen),
+            format!("compact_layout = {}", self.compact_layout),
+            format!("left_handed = {}", self.left_handed),
+            format!("stream_layout = {}", self.stream_layout),
+            format!("chroma_key = {}", self.chroma_key.config_key()),
+            format!("theme = {}", self.theme.config_key()),
+            format!("accessibility = {}", self.accessibility.config_key()),
+            format!("clear_preview = {}", self.clear_preview),
+            format!("reduce_motion = {}", self.reduce_motion),
+            format!("game_speed = {}", self.game_speed),
+            format!("language = \"{}\"", self.language),
+            format!("auto_pause = {}", self.auto_pause),
+            format!("das = {}", self.handling.das),
+            format!("arr = {}", self.handling.arr),
+            format!("soft_drop_factor = {}", self.handling.soft_drop_factor),
+            format!("tap_only = {}", self.handling.tap_only),
+            format!("tick_rate = {}", self.tick_rate),
+            format!("render_fps = {}", self.render_fps),
+        ];
+        lines.extend(Action::ALL.iter().map(|action| format!("{} = {}", action.config_key(), key_name(self.keys.key(*action)))));
+        save_or_report(CONFIG_FILE, lines.join("\n") + "\n");
+    }
+}
@@ -0,0 +1,73 @@
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use crate::game::{Tetris, Tetromino};
+use crate::rules::RuleSet;
+use crate::save::save_or_report;
+
+const BEST_FILE: &str = "daily_best.txt";
+// more pieces than anyone gets through in one game
+const QUEUE_LENGTH: usize = 5000;
+
+// Days since 1970-01-01 in UTC, so the challenge changes at the same moment everywhere
+pub fn today() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() / (24 * 60 * 60)
+}
+
+// Like 2024-03-09
+pub fn date(day: u64) -> String {
+    // the days-to-civil conversion from Howard Hinnant's date algorithms, with eras starting on March 1st
+    let days = day as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day_of_month = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {month_index + 3} else {month_index - 9};
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year}-{month:02}-{day_of_month:02}")
+}
+
+// The same 7-bag sequence for everyone playing on that day
+pub fn to_tetris<const W: usize, const H: usize>(day: u64, seed: u64) -> Tetris<W, H> {
+    let mut rng = StdRng::seed_from_u64(day);
+    let mut queue = vec![];
+    while queue.len() < QUEUE_LENGTH {
+        let mut bag = [Tetromino::O, Tetromino::J, Tetromino::L, Tetromino::I, Tetromino::S, Tetromino::Z, Tetromino::T];
+        bag.shuffle(&mut rng);
+        queue.extend(bag);
+    }
+    Tetris::with_seed_and_queue(RuleSet::default(), seed, queue)
+}
+
+// The best score of the day, kept next to the game as `day score` and dropped once the day is over
+pub struct DailyBest {
+    day: u64,
+    pub score: Option<usize>,
+}
+
+impl DailyBest {
+    pub fn load(day: u64) -> Self {
+        let score = fs::read_to_string(BEST_FILE).ok()
+            .and_then(|data| {
+                let (saved_day, score) = data.trim().split_once(' ')?;
+                Some((saved_day.parse::<u64>().ok()?, score.parse().ok()?))
+            })
+            .filter(|(saved_day, _)| *saved_day == day)
+            .map(|(_, score)| score);
+        Self { day, score }
+    }
+
+    // Returns whether the score beat the day's best
+    pub fn record(&mut self, score: usize) -> bool {
+        if self.score.is_some_and(|best| best >= score) {
+            return false;
+        }
+        self.score = Some(score);
+        save_or_report(BEST_FILE, format!("{day} {score}\n", day = self.day));
+        true
+    }
+}
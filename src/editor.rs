@@ -0,0 +1,39 @@
+use fltk::enums::Color;
+use crate::game::{Cell, Tetris, Tetromino};
+use crate::rules::RuleSet;
+
+const PAINT_COLOR: Color = Color::from_rgb(110, 110, 110);
+
+pub struct BoardSetup<const W: usize, const H: usize> {
+    pub field: [[Option<Cell>; W]; H],
+    pub pieces: Vec<Tetromino>,
+}
+
+impl<const W: usize, const H: usize> BoardSetup<W, H> {
+    pub fn new() -> Self {
+        Self {
+            field: [[None; W]; H],
+            pieces: vec![],
+        }
+    }
+
+    // Rows the spawned piece can reach stay empty, otherwise the game would be lost before it starts
+    pub fn can_paint(&self, x: usize, y: usize) -> bool {
+        x < W && y < H && y > Tetris::<W, H>::spawn_point(&RuleSet::default()).y + 1
+    }
+
+    pub fn paint(&mut self, x: usize, y: usize, filled: bool) {
+        if self.can_paint(x, y) {
+            self.field[y][x] = if filled {Some(Cell::block(PAINT_COLOR))} else {None};
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.field = [[None; W]; H];
+        self.pieces.clear();
+    }
+
+    pub fn to_tetris(&self, rules: RuleSet) -> Tetris<W, H> {
+        Tetris::with_setup(self.field, self.pieces.clone(), rules)
+    }
+}
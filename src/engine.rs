@@ -0,0 +1,134 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::game::{GameEvent, Tetris};
+use crate::replay::Recorded;
+use crate::versus::Match;
+
+// What an engine runs: ticked at the engine's rate, changed by the inputs sent to it, and drawn
+// from copies of it
+pub trait Simulation: Clone + Send + 'static {
+    fn receive_tick(&mut self);
+
+    fn take_changed(&mut self) -> bool;
+
+    // events for the window to poll, each one handed over with a single copy
+    fn take_events(&mut self) -> Vec<GameEvent> {
+        vec![]
+    }
+}
+
+impl<const W: usize, const H: usize> Simulation for Tetris<W, H> {
+    fn receive_tick(&mut self) {
+        self.receive(Recorded::Tick);
+    }
+
+    fn take_changed(&mut self) -> bool {
+        Tetris::take_changed(self)
+    }
+
+    fn take_events(&mut self) -> Vec<GameEvent> {
+        std::iter::from_fn(|| self.poll_event()).collect()
+    }
+}
+
+// the match polls its players' events itself, to send garbage
+impl<const W: usize, const H: usize> Simulation for Match<W, H> {
+    fn receive_tick(&mut self) {
+        Match::receive_tick(self);
+    }
+
+    fn take_changed(&mut self) -> bool {
+        // every player's flag gets taken, not just the first one that's set
+        let mut changed = false;
+        for player in self.players.iter_mut() {
+            changed |= player.tetris.take_changed();
+        }
+        changed
+    }
+}
+
+type Input<T> = Box<dyn FnOnce(&mut T) + Send>;
+
+// A game running on a thread of its own, so however long the bots or anything else in it take, the
+// window keeps taking keys. Inputs go in through `send`, and every time the game changes a copy of it
+// comes back to draw. The thread stops once the engine is dropped
+pub struct Engine<T: Simulation> {
+    inputs: Sender<Input<T>>,
+    frames: Receiver<(T, Vec<GameEvent>)>,
+    ticking: Arc<AtomicBool>,
+    latest: T,
+    events: VecDeque<GameEvent>,
+}
+
+impl<T: Simulation> Engine<T> {
+    pub fn spawn(game: T, tick_rate: u32) -> Self {
+        let (inputs, received) = mpsc::channel::<Input<T>>();
+        let (sent, frames) = mpsc::channel();
+        let ticking = Arc::new(AtomicBool::new(true));
+        let latest = game.clone();
+
+        let is_ticking = ticking.clone();
+        let tick = Duration::from_secs_f64(1.0 / tick_rate as f64);
+        thread::spawn(move || {
+            let mut game = game;
+            let mut next_tick = Instant::now() + tick;
+            loop {
+                match received.recv_timeout(next_tick.saturating_duration_since(Instant::now())) {
+                    Ok(input) => input(&mut game),
+                    Err(RecvTimeoutError::Timeout) => {
+                        if is_ticking.load(Ordering::Relaxed) {
+                            game.receive_tick();
+                        }
+                        // ticks that fell too far behind are dropped rather than run back to back
+                        next_tick = (next_tick + tick).max(Instant::now());
+                    },
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+                if game.take_changed() {
+                    let events = game.take_events();
+                    if sent.send((game.clone(), events)).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Self { inputs, frames, ticking, latest, events: VecDeque::new() }
+    }
+
+    // Runs on the engine's thread, after whatever was sent before it
+    pub fn send(&self, input: impl FnOnce(&mut T) + Send + 'static) {
+        let _ = self.inputs.send(Box::new(input));
+    }
+
+    // Whether ticks move the game along; inputs still do while they don't
+    pub fn set_ticking(&self, ticking: bool) {
+        self.ticking.store(ticking, Ordering::Relaxed);
+    }
+
+    // Takes in the copies that came back since the last call, showing each one to `each` in the order
+    // they were made. False when there were none
+    pub fn receive_frames(&mut self, mut each: impl FnMut(&T)) -> bool {
+        let mut received = false;
+        for (game, events) in self.frames.try_iter() {
+            each(&game);
+            self.latest = game;
+            self.events.extend(events);
+            received = true;
+        }
+        received
+    }
+
+    // The game as of the latest copy
+    pub fn game(&self) -> &T {
+        &self.latest
+    }
+
+    pub fn poll_event(&mut self) -> Option<GameEvent> {
+        self.events.pop_front()
+    }
+}
@@ -0,0 +1,42 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::OnceLock;
+use crate::game::{ScoreBreakdown, Tetris};
+use crate::mode::Mode;
+use crate::stats::Performance;
+
+// Where results go, from `--export` or else `export_file` in config.toml. Unset keeps them to the game
+pub static EXPORT_FILE: OnceLock<String> = OnceLock::new();
+
+const CSV_HEADER: &str = "mode,score,lines,time_ms,pps,drops,singles,doubles,triples,tetrises,t_spins,combos,back_to_back,chains";
+
+// Adds a record of the game as it ended to the export file. A `.csv` file gets a row, under a header
+// when the file is new, anything else a line of JSON, so either one can be read while it grows
+pub fn record<const W: usize, const H: usize>(tetris: &Tetris<W, H>, mode: Mode) {
+    let Some(file) = EXPORT_FILE.get() else {
+        return;
+    };
+    let ScoreBreakdown { drops, singles, doubles, triples, tetrises, t_spins, combos, back_to_back, chains } = tetris.breakdown;
+    let counts = [drops, singles, doubles, triples, tetrises, t_spins, combos, back_to_back, chains];
+    let (time, pps) = (tetris.elapsed().as_millis(), Performance::of(tetris).pieces_per_second);
+
+    let line = if Path::new(file).extension().is_some_and(|extension| extension.eq_ignore_ascii_case("csv")) {
+        let header = if fs::metadata(file).is_err() {format!("{CSV_HEADER}\n")} else {String::new()};
+        let counts: Vec<String> = counts.iter().map(usize::to_string).collect();
+        format!("{header}{mode},{score},{lines},{time},{pps:.3},{counts}\n", mode = mode.key(), score = tetris.score, lines = tetris.lines, counts = counts.join(","))
+    } else {
+        let names = CSV_HEADER.split(',').skip(5);
+        let breakdown: Vec<String> = names.zip(counts).map(|(name, count)| format!("\"{name}\":{count}")).collect();
+        format!(
+            "{{\"mode\":\"{mode}\",\"score\":{score},\"lines\":{lines},\"time_ms\":{time},\"pps\":{pps:.3},\"breakdown\":{{{breakdown}}}}}\n",
+            mode = mode.key(), score = tetris.score, lines = tetris.lines, breakdown = breakdown.join(","),
+        )
+    };
+
+    let written = OpenOptions::new().create(true).append(true).open(file).and_then(|mut out| out.write_all(line.as_bytes()));
+    // failing to export a result shouldn't get in the way of the game, it only gets reported
+    if let Err(err) = written {
+        eprintln!("can't export the result to {file}: {err}");
+    }
+}
@@ -0,0 +1,131 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+use crate::game::{Move, ShapeIter, Tetris};
+
+// a bot that can't take a line within this long is treated as gone
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Every bot that connected and hasn't gone away since, for versus matches to take their opponents from
+static CONNECTED: Mutex<Vec<ExternalBot>> = Mutex::new(vec![]);
+
+// A bot program on the other end of the bot socket, speaking one line of JSON per message.
+// For every piece it gets the state of the game and the spots the piece can be hard dropped to:
+//   {"piece":12,"width":10,"height":20,"board":["..........",...,"##x#######"],"current":"T","hold":null,
+//    "queue":"IOSZ","garbage":3,"moves":[[[0,18],[1,18],[2,18],[1,17]],...]}
+// with the board top to bottom, `#` for the stack and `x` for garbage, and each move the cells the piece
+// would end up in as [x, y]. It answers with the one to go for, as {"piece":12,"move":4}
+#[derive(Clone)]
+pub struct ExternalBot {
+    writer: Arc<Mutex<TcpStream>>,
+    // (piece, move) as the answers come in
+    answers: Arc<Mutex<Receiver<(usize, usize)>>>,
+    connected: Arc<AtomicBool>,
+}
+
+// Takes bots on `port` of this computer only, so nothing from outside gets to play
+pub fn listen(port: u16) {
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("can't open the bot socket on port {port}: {err}");
+            return;
+        },
+    };
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Some(bot) = ExternalBot::new(stream) {
+                CONNECTED.lock().unwrap().push(bot);
+            }
+        }
+    });
+}
+
+// Up to `count` of the bots that are connected right now. They stay connected for the next match
+pub fn available(count: usize) -> Vec<ExternalBot> {
+    let mut connected = CONNECTED.lock().unwrap();
+    connected.retain(ExternalBot::is_connected);
+    connected.iter().take(count).cloned().collect()
+}
+
+impl ExternalBot {
+    fn new(stream: TcpStream) -> Option<ExternalBot> {
+        stream.set_write_timeout(Some(WRITE_TIMEOUT)).ok()?;
+        let reader = BufReader::new(stream.try_clone().ok()?);
+        let (sender, answers) = mpsc::channel();
+        let connected = Arc::new(AtomicBool::new(true));
+
+        let still_connected = connected.clone();
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                match (number(&line, "piece"), number(&line, "move")) {
+                    (Some(piece), Some(chosen)) => {
+                        let _ = sender.send((piece, chosen));
+                    },
+                    _ => eprintln!("the bot sent `{line}`, which isn't an answer"),
+                }
+            }
+            still_connected.store(false, Ordering::Relaxed);
+        });
+
+        Some(ExternalBot { writer: Arc::new(Mutex::new(stream)), answers: Arc::new(Mutex::new(answers)), connected })
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    // Sends the bot the state of the game, for it to pick one of `moves` for the falling piece
+    pub fn offer<const W: usize, const H: usize>(&self, tetris: &Tetris<W, H>, moves: &[Move]) {
+        let board: Vec<String> = tetris.field.iter()
+            .map(|row| {
+                let row: String = row.iter().map(|cell| match cell {
+                    None => '.',
+                    Some(cell) if cell.garbage => 'x',
+                    Some(_) => '#',
+                }).collect();
+                format!("\"{row}\"")
+            })
+            .collect();
+        let hold = tetris.held.as_ref().map_or("null".to_string(), |piece| format!("\"{}\"", piece.shape.name().to_char()));
+        let queue: String = tetris.previews().map(|piece| piece.shape.name().to_char()).collect();
+        let moves: Vec<String> = moves.iter()
+            .map(|candidate| {
+                // cells above the field are given with a negative y
+                let mut cells = vec![];
+                ShapeIter::new(&candidate.shape, &candidate.loc).for_each_mut(|p| cells.push(format!("[{},{}]", p.x, p.y as isize)));
+                format!("[{}]", cells.join(","))
+            })
+            .collect();
+        let line = format!(
+            "{{\"piece\":{piece},\"width\":{W},\"height\":{H},\"board\":[{board}],\"current\":\"{current}\",\"hold\":{hold},\"queue\":\"{queue}\",\"garbage\":{garbage},\"moves\":[{moves}]}}\n",
+            piece = tetris.pieces, board = board.join(","), current = tetris.falling.shape.name().to_char(),
+            garbage = tetris.pending_garbage, moves = moves.join(","),
+        );
+        if self.writer.lock().unwrap().write_all(line.as_bytes()).is_err() {
+            self.connected.store(false, Ordering::Relaxed);
+        }
+    }
+
+    // The move the bot picked for `piece`, once it has. Answers for earlier pieces came too late and
+    // are passed over
+    pub fn take_answer(&self, piece: usize) -> Option<usize> {
+        self.answers.lock().unwrap().try_iter().find(|(answered, _)| *answered == piece).map(|(_, chosen)| chosen)
+    }
+}
+
+// The whole number given for `name` in a line of JSON. The answers are that simple, so they get
+// picked out rather than parsed
+fn number(line: &str, name: &str) -> Option<usize> {
+    let (_, rest) = line.split_once(&format!("\"{name}\""))?;
+    let rest = rest.trim_start().strip_prefix(':')?.trim_start();
+    let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
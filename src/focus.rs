@@ -0,0 +1,79 @@
+use fltk::enums::{Color, Key};
+use fltk::prelude::{ValuatorExt, WidgetBase, WidgetExt};
+use fltk::valuator::HorNiceSlider;
+use fltk::widget::Widget;
+
+const FOCUS_COLOR: Color = Color::from_rgb(255, 200, 80);
+const KEY_SPACE: Key = Key::from_char(' ');
+
+pub enum Focusable {
+    Button(Widget),
+    // left and right move it instead of switching columns
+    Slider(HorNiceSlider),
+}
+
+impl Focusable {
+    fn widget(&self) -> Widget {
+        match self {
+            Focusable::Button(widget) => widget.clone(),
+            Focusable::Slider(slider) => Widget::from_dyn_widget(slider).unwrap(),
+        }
+    }
+}
+
+// The widget of a menu page the arrow keys are on, with the page laid out as columns
+pub struct MenuFocus {
+    columns: Vec<Vec<Focusable>>,
+    colors: Vec<Vec<Color>>,
+    column: usize,
+    row: usize,
+}
+
+impl MenuFocus {
+    pub fn new(columns: Vec<Vec<Focusable>>) -> Self {
+        let columns: Vec<Vec<Focusable>> = columns.into_iter().filter(|column| !column.is_empty()).collect();
+        let colors = columns.iter().map(|column| column.iter().map(|item| item.widget().color()).collect()).collect();
+        let mut focus = MenuFocus { columns, colors, column: 0, row: 0 };
+        focus.highlight(true);
+        focus
+    }
+
+    fn highlight(&mut self, on: bool) {
+        let Some(item) = self.columns.get(self.column).and_then(|column| column.get(self.row)) else {
+            return;
+        };
+        let mut widget = item.widget();
+        widget.set_color(if on {FOCUS_COLOR} else {self.colors[self.column][self.row]});
+        widget.redraw();
+    }
+
+    fn move_to(&mut self, column: usize, row: usize) {
+        self.highlight(false);
+        self.column = column;
+        self.row = row.min(self.columns[column].len() - 1);
+        self.highlight(true);
+    }
+
+    // false for keys that aren't for moving around or pressing things
+    pub fn receive_key(&mut self, key: Key) -> bool {
+        if self.columns.is_empty() {
+            return false;
+        }
+        let (column, row, rows, columns) = (self.column, self.row, self.columns[self.column].len(), self.columns.len());
+
+        match (key, &mut self.columns[column][row]) {
+            (Key::Left | Key::Right, Focusable::Slider(slider)) => {
+                let step = if key == Key::Left {-slider.step()} else {slider.step()};
+                slider.set_value((slider.value() + step).clamp(slider.minimum(), slider.maximum()));
+                slider.do_callback();
+            },
+            (Key::Enter | Key::KPEnter | KEY_SPACE, Focusable::Button(widget)) => widget.do_callback(),
+            (Key::Up, _) => self.move_to(column, (row + rows - 1) % rows),
+            (Key::Down, _) => self.move_to(column, (row + 1) % rows),
+            (Key::Left, _) => self.move_to((column + columns - 1) % columns, row),
+            (Key::Right, _) => self.move_to((column + 1) % columns, row),
+            _ => return false,
+        }
+        true
+    }
+}
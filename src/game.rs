@@ -1,56 +1,345 @@
-use std::time::SystemTime;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use fltk::enums::Color;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use crate::locale::tr;
+use crate::pieces::{MAX_CELLS, PIECE_SETS};
+use crate::clear;
+use crate::replay::{Recorded, Recording};
+use crate::rules::{Hold, PieceSet, Randomizer, Rotation, RuleSet};
 
+#[derive(Clone)]
 pub enum GameState {
     READY,
     RUNNING,
+    PAUSED,
     LOST
 }
 
 #[derive(Clone, Debug)]
-enum Tetromino {
+pub enum GameEvent {
+    Moved,
+    Rotated,
+    Locked,
+    LinesCleared(usize),
+    TSpin(usize),
+    // a tetris or a T-spin clear right after another one
+    BackToBack,
+    PerfectClear,
+    // how many clears in a row came before this one
+    Combo(usize),
+    // rows filled by blocks falling after a clear, counting up from 1 for every round of the chain
+    Chain(usize),
+    LevelUp(usize),
+    // the item is in `Tetris::item` until it gets used
+    ItemGained,
+    ItemUsed,
+    GameOver,
+}
+
+// A single tap of what a move is made of, Down being a soft drop by one row
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Input {
+    Left,
+    Right,
+    Rotate,
+    Down,
+}
+
+// Somewhere the falling piece can be locked, and the fewest taps that get it there
+#[derive(Clone, Debug)]
+pub struct Move {
+    pub shape: Shape,
+    pub loc: Point,
+    pub inputs: Vec<Input>,
+}
+
+// Where the score came from, the level multiplier already taken into every part
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ScoreBreakdown {
+    // pieces locked without clearing anything, when the rules score those
+    pub drops: usize,
+    pub singles: usize,
+    pub doubles: usize,
+    pub triples: usize,
+    pub tetrises: usize,
+    pub t_spins: usize,
+    pub combos: usize,
+    pub back_to_back: usize,
+    pub chains: usize,
+}
+
+impl ScoreBreakdown {
+    pub fn parts(&self) -> [(&'static str, usize); 9] {
+        [
+            (tr("score.drops"), self.drops),
+            (tr("score.singles"), self.singles),
+            (tr("score.doubles"), self.doubles),
+            (tr("score.triples"), self.triples),
+            (tr("score.tetrises"), self.tetrises),
+            (tr("score.t_spins"), self.t_spins),
+            (tr("score.combos"), self.combos),
+            (tr("score.back_to_back"), self.back_to_back),
+            (tr("score.chains"), self.chains),
+        ]
+    }
+}
+
+// A filled block of the field
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cell {
+    pub color: Color,
+    // the piece it was locked as, none for garbage and blocks the game was set up with
+    pub piece: Option<Tetromino>,
+    pub garbage: bool,
+    // when on the game clock it got into the field
+    pub locked_at: Duration,
+}
+
+impl Cell {
+    pub fn block(color: Color) -> Self {
+        Self { color, piece: None, garbage: false, locked_at: Duration::ZERO }
+    }
+
+    fn garbage(locked_at: Duration) -> Self {
+        Self { color: GARBAGE_COLOR, piece: None, garbage: true, locked_at }
+    }
+
+    // Both were locked as one piece, which garbage and the blocks the game was set up with never are
+    pub fn is_same_piece(&self, other: &Cell) -> bool {
+        self.piece.is_some() && self.piece == other.piece && self.locked_at == other.locked_at
+    }
+}
+
+// How the piece moves while a move key is held, which is up to the player rather than the rules
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Handling {
+    // DAS, millis a move key has to be held before the piece starts sliding
+    pub das: u128,
+    // ARR, millis between the steps of a slide, 0 slides all the way to the wall at once
+    pub arr: u128,
+    // soft drop goes this many times as fast as the rules have it
+    pub soft_drop_factor: u32,
+    // for whoever can't hold keys down: a move key tapped once moves once and tapped twice in a row
+    // slides the piece until it's tapped again, and soft drop stays on from one tap to the next
+    pub tap_only: bool,
+}
+
+impl Default for Handling {
+    fn default() -> Self {
+        Self { das: 170, arr: 50, soft_drop_factor: 1, tap_only: false }
+    }
+}
+
+// The move key being held, and when on the game clock it was pressed and last slid the piece
+#[derive(Clone, Copy, Debug)]
+struct Shifting {
+    right: bool,
+    since: Duration,
+    last: Option<Duration>,
+}
+
+// Handed out for line clears when the rules have items
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Item {
+    ClearBottomRow,
+    // halves the garbage waiting to rise
+    ShrinkGarbage,
+    SlowGravity,
+}
+
+impl Item {
+    const ALL: [Item; 3] = [Item::ClearBottomRow, Item::ShrinkGarbage, Item::SlowGravity];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Item::ClearBottomRow => tr("item.clear_bottom_row"),
+            Item::ShrinkGarbage => tr("item.shrink_garbage"),
+            Item::SlowGravity => tr("item.slow_gravity"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tetromino {
     O,
+    J,
     L,
     I,
     S,
+    Z,
     T,
+    // one of the PENTOMINOES
+    Pentomino(usize),
+    // (set, piece) from the PIECE_SETS
+    Custom(usize, usize),
+}
+
+impl Tetromino {
+    pub fn from_char(name: char) -> Option<Tetromino> {
+        match name {
+            'I' => Some(Tetromino::I),
+            'O' => Some(Tetromino::O),
+            'T' => Some(Tetromino::T),
+            'S' => Some(Tetromino::S),
+            'Z' => Some(Tetromino::Z),
+            'J' => Some(Tetromino::J),
+            'L' => Some(Tetromino::L),
+            _ => None,
+        }
+    }
+
+    // pentominoes and custom pieces have no letter of their own
+    pub fn to_char(self) -> char {
+        match self {
+            Tetromino::I => 'I',
+            Tetromino::O => 'O',
+            Tetromino::T => 'T',
+            Tetromino::S => 'S',
+            Tetromino::Z => 'Z',
+            Tetromino::J => 'J',
+            Tetromino::L => 'L',
+            Tetromino::Pentomino(_) | Tetromino::Custom(..) => '*',
+        }
+    }
 }
 
-static SHAPES: std::sync::LazyLock<[Shape; 5]> = std::sync::LazyLock::new(|| {
-    [
-        Shape::new(Tetromino::I, [
-            RelPoint::new(0, 0),
-            RelPoint::new(0, 1),
-            RelPoint::new(0, -1),
-            RelPoint::new(0, -2),
-        ]),
-        Shape::new(Tetromino::O, [
-            RelPoint::new(0, 0),
-            RelPoint::new(0, 1),
-            RelPoint::new(1, 0),
-            RelPoint::new(1, 1),
-        ]),
-        Shape::new(Tetromino::S, [
-            RelPoint::new(0, 0),
-            RelPoint::new(0, 1),
-            RelPoint::new(-1, 0),
-            RelPoint::new(-1, -1),
-        ]),
-        Shape::new(Tetromino::L, [
-            RelPoint::new(0, 1),
-            RelPoint::new(1, 1),
-            RelPoint::new(1, 0),
-            RelPoint::new(1, -1),
-        ]),
-        Shape::new(Tetromino::T, [
-            RelPoint::new(0, 0),
-            RelPoint::new(0, 1),
-            RelPoint::new(0, -1),
-            RelPoint::new(1, 0),
-        ]),
-    ]
-});
+const SHAPES: [Shape; 5] = [
+    Shape::new(Tetromino::I, &[
+        RelPoint::new(0, 0),
+        RelPoint::new(0, 1),
+        RelPoint::new(0, -1),
+        RelPoint::new(0, -2),
+    ]),
+    Shape::new(Tetromino::O, &[
+        RelPoint::new(0, 0),
+        RelPoint::new(0, 1),
+        RelPoint::new(1, 0),
+        RelPoint::new(1, 1),
+    ]),
+    Shape::new(Tetromino::S, &[
+        RelPoint::new(0, 0),
+        RelPoint::new(0, 1),
+        RelPoint::new(-1, 0),
+        RelPoint::new(-1, -1),
+    ]),
+    Shape::new(Tetromino::J, &[
+        RelPoint::new(0, 1),
+        RelPoint::new(1, 1),
+        RelPoint::new(1, 0),
+        RelPoint::new(1, -1),
+    ]),
+    Shape::new(Tetromino::T, &[
+        RelPoint::new(0, 0),
+        RelPoint::new(0, 1),
+        RelPoint::new(0, -1),
+        RelPoint::new(1, 0),
+    ]),
+];
+
+// the twelve pentominoes, mirrored ones are the same piece
+const PENTOMINOES: [Shape; 12] = [
+    // F
+    Shape::new(Tetromino::Pentomino(0), &[
+        RelPoint::new(0, -1),
+        RelPoint::new(1, -1),
+        RelPoint::new(-1, 0),
+        RelPoint::new(0, 0),
+        RelPoint::new(0, 1),
+    ]),
+    // I
+    Shape::new(Tetromino::Pentomino(1), &[
+        RelPoint::new(0, -2),
+        RelPoint::new(0, -1),
+        RelPoint::new(0, 0),
+        RelPoint::new(0, 1),
+        RelPoint::new(0, 2),
+    ]),
+    // L
+    Shape::new(Tetromino::Pentomino(2), &[
+        RelPoint::new(0, -2),
+        RelPoint::new(0, -1),
+        RelPoint::new(0, 0),
+        RelPoint::new(0, 1),
+        RelPoint::new(1, 1),
+    ]),
+    // N
+    Shape::new(Tetromino::Pentomino(3), &[
+        RelPoint::new(1, -2),
+        RelPoint::new(1, -1),
+        RelPoint::new(1, 0),
+        RelPoint::new(0, 0),
+        RelPoint::new(0, 1),
+    ]),
+    // P
+    Shape::new(Tetromino::Pentomino(4), &[
+        RelPoint::new(0, -1),
+        RelPoint::new(1, -1),
+        RelPoint::new(0, 0),
+        RelPoint::new(1, 0),
+        RelPoint::new(0, 1),
+    ]),
+    // T
+    Shape::new(Tetromino::Pentomino(5), &[
+        RelPoint::new(-1, -1),
+        RelPoint::new(0, -1),
+        RelPoint::new(1, -1),
+        RelPoint::new(0, 0),
+        RelPoint::new(0, 1),
+    ]),
+    // U
+    Shape::new(Tetromino::Pentomino(6), &[
+        RelPoint::new(-1, -1),
+        RelPoint::new(1, -1),
+        RelPoint::new(-1, 0),
+        RelPoint::new(0, 0),
+        RelPoint::new(1, 0),
+    ]),
+    // V
+    Shape::new(Tetromino::Pentomino(7), &[
+        RelPoint::new(-1, -1),
+        RelPoint::new(-1, 0),
+        RelPoint::new(-1, 1),
+        RelPoint::new(0, 1),
+        RelPoint::new(1, 1),
+    ]),
+    // W
+    Shape::new(Tetromino::Pentomino(8), &[
+        RelPoint::new(-1, -1),
+        RelPoint::new(-1, 0),
+        RelPoint::new(0, 0),
+        RelPoint::new(0, 1),
+        RelPoint::new(1, 1),
+    ]),
+    // X
+    Shape::new(Tetromino::Pentomino(9), &[
+        RelPoint::new(0, -1),
+        RelPoint::new(-1, 0),
+        RelPoint::new(0, 0),
+        RelPoint::new(1, 0),
+        RelPoint::new(0, 1),
+    ]),
+    // Y
+    Shape::new(Tetromino::Pentomino(10), &[
+        RelPoint::new(0, -1),
+        RelPoint::new(-1, 0),
+        RelPoint::new(0, 0),
+        RelPoint::new(0, 1),
+        RelPoint::new(0, 2),
+    ]),
+    // Z
+    Shape::new(Tetromino::Pentomino(11), &[
+        RelPoint::new(-1, -1),
+        RelPoint::new(0, -1),
+        RelPoint::new(0, 0),
+        RelPoint::new(0, 1),
+        RelPoint::new(1, 1),
+    ]),
+];
 
 static COLORS: [Color;4] = [
     Color::Red,
@@ -59,31 +348,73 @@ static COLORS: [Color;4] = [
     Color::Yellow,
 ];
 
-#[derive(Clone, Debug)]
+// a palette for every level, cycling after the tenth
+static LEVEL_COLORS: [[Color; 4]; 10] = [
+    [Color::from_rgb(0, 88, 248), Color::from_rgb(60, 188, 252), Color::from_rgb(0, 88, 248), Color::from_rgb(60, 188, 252)],
+    [Color::from_rgb(0, 168, 0), Color::from_rgb(184, 248, 24), Color::from_rgb(0, 168, 0), Color::from_rgb(184, 248, 24)],
+    [Color::from_rgb(216, 0, 204), Color::from_rgb(248, 120, 248), Color::from_rgb(216, 0, 204), Color::from_rgb(248, 120, 248)],
+    [Color::from_rgb(0, 88, 248), Color::from_rgb(88, 216, 84), Color::from_rgb(0, 88, 248), Color::from_rgb(88, 216, 84)],
+    [Color::from_rgb(228, 0, 88), Color::from_rgb(88, 248, 152), Color::from_rgb(228, 0, 88), Color::from_rgb(88, 248, 152)],
+    [Color::from_rgb(88, 248, 152), Color::from_rgb(104, 136, 252), Color::from_rgb(88, 248, 152), Color::from_rgb(104, 136, 252)],
+    [Color::from_rgb(248, 56, 0), Color::from_rgb(124, 124, 124), Color::from_rgb(248, 56, 0), Color::from_rgb(124, 124, 124)],
+    [Color::from_rgb(104, 68, 252), Color::from_rgb(168, 0, 32), Color::from_rgb(104, 68, 252), Color::from_rgb(168, 0, 32)],
+    [Color::from_rgb(0, 88, 248), Color::from_rgb(248, 56, 0), Color::from_rgb(0, 88, 248), Color::from_rgb(248, 56, 0)],
+    [Color::from_rgb(248, 56, 0), Color::from_rgb(252, 160, 68), Color::from_rgb(248, 56, 0), Color::from_rgb(252, 160, 68)],
+];
+
+// Offsets a rotation that doesn't fit tries, in order, for the rules without SRS
+const NUDGE_KICKS: [(i32, i32); 4] = [(0, 0), (1, 0), (-1, 0), (0, -1)];
+const NUDGE_I_KICKS: [(i32, i32); 6] = [(0, 0), (1, 0), (-1, 0), (2, 0), (-2, 0), (0, -1)];
+
+// SRS offsets to try, in order, when rotating clockwise out of each orientation. y points down.
+const SRS_KICKS: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+];
+
+const SRS_I_KICKS: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+    [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+    [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+    [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+];
+
+const GARBAGE_COLOR: Color = Color::from_rgb(110, 110, 110);
+// what `#` cells read from ASCII get, since the text doesn't say which piece left them
+const ASCII_STACK_COLOR: Color = Color::from_rgb(180, 180, 180);
+// rows below the spawn point that count as danger once the stack reaches them
+const DANGER_ROWS: usize = 4;
+const SLOW_GRAVITY_TIME: Duration = Duration::from_secs(10);
+// millis per row gravity can't be faster than while slowed down
+const SLOW_GRAVITY_DELAY: f64 = 2000.0;
+
+#[derive(Clone, Copy, Debug)]
 pub struct RelPoint {
     pub(crate) dx: i32,
     pub(crate) dy: i32,
 }
 
 impl RelPoint {
-    fn new(dx: i32, dy: i32) -> Self {
+    const fn new(dx: i32, dy: i32) -> Self {
         Self { dx, dy }
     }
-    fn rotate(&mut self) -> &Self {
+    const fn rotate(&mut self) -> &Self {
         (self.dy, self.dx) = (self.dx, -self.dy);
         self
     }
-    fn mirror(&mut self) -> &Self {
+    const fn mirror(&mut self) -> &Self {
         self.dx *= -1;
         self
     }
 
-    fn to_abs(&self, point: &Point) -> Point {
+    fn to_abs(self, point: &Point) -> Point {
         Point::new((point.x as i32 + self.dx) as usize, (point.y as i32 + self.dy) as usize)
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct Point {
     pub(crate) x: usize,
     pub(crate) y: usize,
@@ -97,78 +428,177 @@ impl Point {
     pub fn add(&self, x: i32, y: i32) -> Point {
         Point::new((self.x as i32 + x) as usize, (self.y as i32 + y) as usize)
     }
+
+    // Rows above the top of the field come out of `add` wrapped around, far past any field's height.
+    // A piece kicked up from the top row has its centre up there too, so it only ever moves by `add`
+    pub fn is_above_field(&self) -> bool {
+        (self.y as isize) < 0
+    }
 }
 
-#[derive(Clone, Debug)]
+// Kept in place rather than on the heap, so copying one around while trying moves costs nothing
+#[derive(Clone, Copy, Debug)]
 pub struct Shape {
     name: Tetromino,
-    points: [RelPoint; 4]
+    // the first `len` are the shape's
+    cells: [RelPoint; MAX_CELLS],
+    len: usize,
+    // clockwise turns away from how the shape is defined
+    orientation: usize,
 }
 
 impl Shape {
-    fn new(name: Tetromino, points: [RelPoint; 4]) -> Shape {
-        Self {name, points}
+    // const so the built-in tables are worked out while compiling
+    const fn new(name: Tetromino, points: &[RelPoint]) -> Shape {
+        let mut cells = [RelPoint::new(0, 0); MAX_CELLS];
+        let mut idx = 0;
+        while idx < points.len() {
+            cells[idx] = points[idx];
+            idx += 1;
+        }
+        Self {name, cells, len: points.len(), orientation: 0}
     }
 
-    fn rotate(&mut self) -> &Self {
+    pub(crate) fn points(&self) -> &[RelPoint] {
+        &self.cells[..self.len]
+    }
+
+    pub(crate) fn name(&self) -> Tetromino {
+        self.name
+    }
+
+    pub(crate) const fn rotate(&mut self) -> &Self {
         match self.name {
             Tetromino::O => self,
             _ => {
-                for point in self.points.iter_mut() {
-                    point.rotate();
+                let mut idx = 0;
+                while idx < self.len {
+                    self.cells[idx].rotate();
+                    idx += 1;
                 }
+                self.orientation = (self.orientation + 1) % 4;
                 self
             }
         }
     }
 
     fn mirror(&mut self) -> &Self {
-        for point in self.points.iter_mut() {
+        self.name = match self.name {
+            Tetromino::J => Tetromino::L,
+            Tetromino::L => Tetromino::J,
+            Tetromino::S => Tetromino::Z,
+            Tetromino::Z => Tetromino::S,
+            name => name,
+        };
+        for point in self.cells[..self.len].iter_mut() {
             point.mirror();
         }
         self
     }
 
-    fn random() -> Shape {
-        let idx = rand::thread_rng().gen_range(0..SHAPES.len());
-        let mut shape = SHAPES[idx].clone();
+    pub fn of(name: Tetromino) -> Shape {
+        if let Tetromino::Pentomino(idx) = name {
+            return PENTOMINOES[idx];
+        }
+        if let Tetromino::Custom(set, idx) = name {
+            let piece = &PIECE_SETS[set].pieces[idx];
+            let points: Vec<RelPoint> = piece.cells.iter().map(|(dx, dy)| RelPoint::new(*dx, *dy)).collect();
+            let mut shape = Shape::new(name, &points);
+            for _ in 0..piece.turns {
+                shape.rotate();
+            }
+            shape.orientation = 0;
+            return shape;
+        }
+
+        let base = match name {
+            Tetromino::L => Tetromino::J,
+            Tetromino::Z => Tetromino::S,
+            name => name,
+        };
+        let mut shape = *SHAPES.iter().find(|shape| shape.name == base).unwrap();
+        if shape.name != name {
+            shape.mirror();
+        }
+        shape
+    }
+
+    // Turned so the flat side faces down, the way current games bring pieces in.
+    // The turned shape counts as unrotated.
+    pub fn lying_flat(name: Tetromino) -> Shape {
+        let turns = match name {
+            Tetromino::O | Tetromino::Pentomino(_) | Tetromino::Custom(..) => 0,
+            Tetromino::I | Tetromino::S | Tetromino::Z | Tetromino::J => 1,
+            Tetromino::L | Tetromino::T => 3,
+        };
+        let mut shape = Shape::of(name);
+        for _ in 0..turns {
+            shape.rotate();
+        }
+        shape.orientation = 0;
+        shape
+    }
+
+    fn random(pieces: PieceSet, rng: &mut StdRng) -> Shape {
+        let shapes: &[Shape] = match pieces {
+            PieceSet::Tetrominoes => &SHAPES,
+            PieceSet::Pentominoes => &PENTOMINOES,
+            // custom pieces are played exactly as they were made, without mirroring them
+            PieceSet::Custom(set) => {
+                let idx = rng.gen_range(0..PIECE_SETS[set].pieces.len());
+                let mut shape = Shape::of(Tetromino::Custom(set, idx));
+                for _ in 0..rng.gen_range(0..4) {
+                    shape.rotate();
+                }
+                return shape;
+            },
+        };
+        let idx = rng.gen_range(0..shapes.len());
+        let mut shape = shapes[idx];
 
-        if rand::thread_rng().gen_bool(0.5) {
+        if rng.gen_bool(0.5) {
             shape.mirror();
         }
-        for _ in 0..rand::thread_rng().gen_range(0..4) {
+        for _ in 0..rng.gen_range(0..4) {
             shape.rotate();
         }
         shape
     }
 }
 
+#[derive(Clone)]
 pub struct PreparedShape {
     pub(crate) shape: Shape,
     pub(crate) color: Color,
 }
 
 impl PreparedShape {
-    fn random() -> Self {
-        let color_idx = rand::thread_rng().gen_range(0..COLORS.len());
-        Self {
-            shape: Shape::random(),
-            color: COLORS[color_idx]
-        }
+    fn random(pieces: PieceSet, rng: &mut StdRng) -> Self {
+        Self::colored(Shape::random(pieces, rng), rng)
+    }
+
+    // custom pieces keep the color they were made with, the rest get a random one
+    fn colored(shape: Shape, rng: &mut StdRng) -> Self {
+        let color = match shape.name {
+            Tetromino::Custom(set, idx) => PIECE_SETS[set].pieces[idx].color,
+            _ => COLORS[rng.gen_range(0..COLORS.len())],
+        };
+        Self { shape, color }
     }
 }
 
+#[derive(Clone)]
 pub struct SpawnedShape {
-    shape: Shape,
-    loc: Point,
+    pub(crate) shape: Shape,
+    pub(crate) loc: Point,
     pub(crate) color: Color,
 }
 
 impl SpawnedShape {
-    fn random<const W: usize, const H: usize>() -> SpawnedShape {
-        let PreparedShape {color, shape} = PreparedShape::random();
+    fn random(loc: Point, rng: &mut StdRng) -> SpawnedShape {
+        let PreparedShape {color, shape} = PreparedShape::random(PieceSet::Tetrominoes, rng);
         SpawnedShape {
-            loc: Tetris::<W, H>::starting_point(),
+            loc,
             shape,
             color,
         }
@@ -179,107 +609,891 @@ impl SpawnedShape {
     }
 }
 
+#[derive(Clone)]
 pub struct Tetris<const W: usize, const H: usize> {
-    pub field: [[Option<Color>; W]; H],
+    pub field: [[Option<Cell>; W]; H],
     pub next: PreparedShape,
     pub falling: SpawnedShape,
+    pub held: Option<PreparedShape>,
     pub state: GameState,
     pub score: usize,
-    since_step: SystemTime,
+    pub breakdown: ScoreBreakdown,
+    pub lines: usize,
+    pub pieces: usize,
+    pub pending_garbage: usize,
+    pub finesse_faults: usize,
+    // every key that reached the running game, for the keys per piece
+    pub keys: usize,
+    // what the generator started from
+    pub seed: u64,
+    // garbage sent to opponents, before any of it got countered
+    pub attack: usize,
+    pub item: Option<Item>,
+    clock: GameClock,
+    events: VecDeque<GameEvent>,
+    callbacks: Callbacks<W, H>,
+    queue: VecDeque<PreparedShape>,
+    // shown after `next`, when the rules show more than one piece ahead
+    preview: VecDeque<PreparedShape>,
+    can_hold: bool,
+    lock_resets: usize,
+    // floor kicks the falling piece has had so far
+    floor_kicks: usize,
+    rules: RuleSet,
+    bag: Vec<Tetromino>,
+    // the timers below are all read off the game clock
+    landed_at: Option<Duration>,
+    // while set, the last piece has locked and the next one waits for the entry delay
+    entering_since: Option<Duration>,
+    // rotation and hold pressed during the entry delay, applied as the piece comes in. The rotation
+    // is whether it was clockwise
+    initial_rotation: Option<bool>,
+    initial_hold: bool,
+    // full rows that stay on the field until the line clear delay is over
+    clearing: Vec<usize>,
+    spawned_as: SpawnedShape,
+    inputs: usize,
+    rotated_last: bool,
+    combo: usize,
+    back_to_back: bool,
+    since_step: Duration,
     is_sped_up: bool,
+    handling: Handling,
+    shifting: Option<Shifting>,
+    // the move key tapped last and when, for telling a double tap with tap only
+    last_tap: Option<(bool, Duration)>,
+    slowed_until: Option<Duration>,
+    // rows pushed up so far by the rules' garbage interval
+    rows_risen: usize,
+    rng: StdRng,
+    recording: Option<Recording>,
+    // the search behind the finesse faults is only worth it for a game that shows them
+    tracks_finesse: bool,
+    // set by anything that changes how the game looks, until take_changed
+    changed: bool,
+}
+
+// Time the game has spent running. It stands still whenever the game isn't RUNNING,
+// so nothing timed with it has to account for pauses
+#[derive(Debug, Default)]
+struct GameClock {
+    elapsed: Duration,
+    // when it last started running, None while it stands still
+    running_since: Option<SystemTime>,
+    // A recorded or replayed game stays at the moment its latest input came in, so everything the input
+    // does sees the same time both times
+    held: Option<Duration>,
+}
+
+// a copy is only ever looked at, so it goes by the actual time
+impl Clone for GameClock {
+    fn clone(&self) -> Self {
+        Self { held: None, ..*self }
+    }
+}
+
+impl GameClock {
+    fn now(&self) -> Duration {
+        if let Some(held) = self.held {
+            return held;
+        }
+        self.elapsed + self.running_since.map_or(Duration::ZERO, |since| SystemTime::now().duration_since(since).unwrap_or_default())
+    }
+
+    fn run(&mut self) {
+        self.running_since.get_or_insert_with(SystemTime::now);
+    }
+
+    fn stop(&mut self) {
+        self.elapsed = self.now();
+        self.running_since = None;
+    }
+}
+
+// Called the moment something happens, for whoever can't wait for their turn at polling the events
+#[derive(Clone, Default)]
+struct Callbacks<const W: usize, const H: usize> {
+    lines_cleared: Vec<Arc<dyn Fn(usize) + Send + Sync>>,
+    game_over: Vec<GameOverCallback<W, H>>,
+}
+
+type GameOverCallback<const W: usize, const H: usize> = Arc<dyn Fn(&Tetris<W, H>) + Send + Sync>;
+
+// Everything a game is at one moment, to go back to later. Its clock is stopped, so a restored
+// game picks up where it was instead of catching up on the time in between
+#[derive(Clone)]
+pub struct Snapshot<const W: usize, const H: usize> {
+    tetris: Tetris<W, H>,
+}
+
+impl<const W: usize, const H: usize> Snapshot<W, H> {
+    pub fn pieces(&self) -> usize {
+        self.tetris.pieces
+    }
 }
 
 impl<const W: usize, const H:usize> Tetris<W, H> {
 
-    pub fn starting_point() -> Point {
-        Point::new(W / 2, 5)
+    pub fn spawn_point(rules: &RuleSet) -> Point {
+        let column = rules.spawn_column.unwrap_or(W / 2).min(W - 1);
+        let column = if rules.mirror {W - 1 - column} else {column};
+        Point::new(column, rules.spawn_row.min(H - 1))
     }
 
     pub fn new() -> Tetris<W, H> {
-        Tetris {
+        Self::with_rules(RuleSet::default())
+    }
+
+    pub fn with_rules(rules: RuleSet) -> Tetris<W, H> {
+        Self::with_seed(rules, rand::random())
+    }
+
+    // Every piece, color, garbage hole and item comes out of the one generator, so the same seed and
+    // the same inputs play the same game
+    pub fn with_seed(rules: RuleSet, seed: u64) -> Tetris<W, H> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let falling = SpawnedShape::random(Self::spawn_point(&rules), &mut rng);
+        let mut tetris = Tetris {
             field: [[None; W]; H],
-            next: PreparedShape::random(),
-            falling: SpawnedShape::random::<W, H>(),
+            next: PreparedShape::random(PieceSet::Tetrominoes, &mut rng),
+            spawned_as: falling.clone(),
+            falling,
+            held: None,
             state: GameState::READY,
-            since_step: SystemTime::now(),
+            since_step: Duration::ZERO,
             is_sped_up: false,
+            handling: Handling::default(),
+            shifting: None,
+            last_tap: None,
             score: 0,
+            breakdown: ScoreBreakdown::default(),
+            lines: 0,
+            pieces: 0,
+            pending_garbage: 0,
+            finesse_faults: 0,
+            keys: 0,
+            seed,
+            attack: 0,
+            item: None,
+            clock: GameClock::default(),
+            events: VecDeque::new(),
+            callbacks: Callbacks::default(),
+            queue: VecDeque::new(),
+            preview: VecDeque::new(),
+            can_hold: true,
+            lock_resets: 0,
+            floor_kicks: 0,
+            rules,
+            bag: vec![],
+            landed_at: None,
+            entering_since: None,
+            initial_rotation: None,
+            initial_hold: false,
+            clearing: vec![],
+            inputs: 0,
+            rotated_last: false,
+            combo: 0,
+            back_to_back: false,
+            slowed_until: None,
+            rows_risen: 0,
+            rng,
+            recording: None,
+            tracks_finesse: false,
+            changed: true,
+        };
+
+        let first = tetris.take_upcoming();
+        tetris.spawn(first);
+        tetris.next = tetris.take_upcoming();
+        for _ in 1..tetris.rules.previews {
+            let piece = tetris.take_upcoming();
+            tetris.preview.push_back(piece);
+        }
+        tetris.repaint(&COLORS);
+        tetris
+    }
+
+    // Plays the given pieces in order before falling back to random ones
+    pub fn with_queue(pieces: Vec<Tetromino>) -> Tetris<W, H> {
+        Self::with_rules_and_queue(RuleSet::default(), pieces)
+    }
+
+    pub fn with_rules_and_queue(rules: RuleSet, pieces: Vec<Tetromino>) -> Tetris<W, H> {
+        Self::with_seed_and_queue(rules, rand::random(), pieces)
+    }
+
+    // The given pieces get their colors from the seed like every other one
+    pub fn with_seed_and_queue(rules: RuleSet, seed: u64, pieces: Vec<Tetromino>) -> Tetris<W, H> {
+        let mut tetris = Self::with_seed(rules, seed);
+        let queue = pieces.into_iter().map(|name| PreparedShape::colored(Shape::of(name), &mut tetris.rng)).collect();
+        tetris.queue = queue;
+
+        let first = tetris.take_upcoming();
+        tetris.spawn(first);
+        tetris.next = tetris.take_upcoming();
+        tetris.preview.clear();
+        for _ in 1..tetris.rules.previews {
+            let piece = tetris.take_upcoming();
+            tetris.preview.push_back(piece);
+        }
+        tetris
+    }
+
+    // Starts from an already filled field, e.g. one painted in the board editor
+    pub fn with_setup(field: [[Option<Cell>; W]; H], pieces: Vec<Tetromino>, rules: RuleSet) -> Tetris<W, H> {
+        let mut tetris = Self::with_rules_and_queue(rules, pieces);
+        tetris.field = field;
+        tetris
+    }
+
+    pub fn set_handling(&mut self, handling: Handling) {
+        self.handling = handling;
+    }
+
+    pub fn slow_down(&mut self, speed: f64) {
+        self.rules.slow_down(speed);
+    }
+
+    // From here on every input that goes through `receive` is kept, for a replay of the game
+    pub fn record(&mut self) {
+        self.recording = Some(Recording::default());
+    }
+
+    // From here on every lock counts whether the piece took more inputs than it had to
+    pub fn track_finesse(&mut self) {
+        self.tracks_finesse = true;
+    }
+
+    pub fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+
+    pub fn recording(&self) -> Option<&Recording> {
+        self.recording.as_ref()
+    }
+
+    pub fn receive(&mut self, input: Recorded) {
+        if let Some(recording) = self.recording.as_mut() {
+            self.clock.held = None;
+            let now = self.clock.now();
+            self.clock.held = Some(now);
+            recording.push(now, input);
         }
+        input.apply(self);
+    }
+
+    // An input of a replay, at the time on the game clock it came in at
+    pub fn replay(&mut self, time: Duration, input: Recorded) {
+        self.clock.held = Some(time);
+        input.apply(self);
     }
 
     pub fn start(&mut self) {
+        self.changed = true;
         self.state = GameState::RUNNING;
+        self.clock.run();
+    }
+
+    // Whether the game changed since the last time this was asked, so a frame of it drawn since then
+    // still shows it as it is
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    // Time spent running, pauses left out
+    pub fn elapsed(&self) -> Duration {
+        self.clock.now()
+    }
+
+    pub fn receive_pause(&mut self) {
+        self.changed = true;
+        match self.state {
+            GameState::RUNNING => {
+                self.state = GameState::PAUSED;
+                self.clock.stop();
+            },
+            GameState::PAUSED => {
+                self.state = GameState::RUNNING;
+                self.clock.run();
+            },
+            _ => {},
+        }
+    }
+
+    // Milliseconds the falling piece takes to fall a row
+    fn fall_delay(&self, now: Duration) -> f64 {
+        if self.is_sped_up {
+            self.rules.soft_drop_delay as f64 / self.handling.soft_drop_factor as f64
+        } else if self.slowed_until.is_some_and(|until| now < until) {
+            self.rules.gravity_delay(self.level()).max(SLOW_GRAVITY_DELAY)
+        } else {
+            self.rules.gravity_delay(self.level())
+        }
+    }
+
+    // How far the falling piece is on its way to the row below, from 0 right after a step to 1 when the
+    // next one is due, and 0 when it has nowhere to fall. Drawing it that much lower moves it smoothly
+    // however far apart the ticks are
+    pub fn fall_progress(&self) -> f64 {
+        if !matches!(self.state, GameState::RUNNING) || self.is_entering() || self.landed_at.is_some()
+            || !self.can_place_at(&self.falling.shape, &self.falling.loc.add(0, 1)) {
+            return 0.0;
+        }
+        let now = self.clock.now();
+        let delay = self.fall_delay(now);
+        if delay <= 0.0 {
+            return 0.0;
+        }
+        (now.saturating_sub(self.since_step).as_secs_f64() * 1000.0 / delay).min(1.0)
     }
 
     pub fn receive_tick(&mut self) {
-        let now = SystemTime::now();
-        let delay = if self.is_sped_up {100} else {1000};
-        if now.duration_since(self.since_step).unwrap().as_millis() > delay {
+        let now = self.clock.now();
+        // the clock moves on, and with it the falling piece and everything timed
+        self.changed |= matches!(self.state, GameState::RUNNING);
+
+        if self.rules.garbage_interval > 0 && matches!(self.state, GameState::RUNNING) {
+            let due = (now.as_millis() / self.rules.garbage_interval) as usize;
+            while self.rows_risen < due && matches!(self.state, GameState::RUNNING) {
+                self.rows_risen += 1;
+                self.insert_garbage_row();
+            }
+        }
+
+        if let Some(entering_since) = self.entering_since {
+            let delay = self.rules.entry_delay + if self.clearing.is_empty() {0} else {self.rules.line_clear_delay};
+            if matches!(self.state, GameState::RUNNING) && now.saturating_sub(entering_since).as_millis() >= delay {
+                self.enter();
+            }
+            return;
+        }
+        self.auto_shift(now);
+
+        // once the piece rests on the stack it gets checked every tick, so the lock delay is kept exactly
+        if self.landed_at.is_some() {
             self.step();
             self.since_step = now;
+            return;
+        }
+
+        let delay = self.fall_delay(now);
+        let elapsed = now.saturating_sub(self.since_step).as_secs_f64() * 1000.0;
+        if elapsed <= delay {
+            return;
+        }
+
+        // gravity faster than the ticks drops several rows at once, up to the whole field
+        let rows = if delay > 0.0 {((elapsed / delay) as usize).clamp(1, H)} else {H};
+        let pieces = self.pieces;
+        for _ in 0..rows {
+            self.step();
+            if self.landed_at.is_some() || self.pieces != pieces {
+                break;
+            }
         }
+        self.since_step = now;
     }
 
     pub fn receive_left(&mut self) {
+        self.changed = true;
 
-        if !matches!(self.state, GameState::RUNNING) {
+        if !matches!(self.state, GameState::RUNNING) || self.is_entering() {
             return;
         }
+        self.keys += 1;
+        self.inputs += 1;
+        self.shift(-1);
+    }
 
-        let future_loc = self.falling.loc.add(-1, 0);
-        if !self.can_place_at(&self.falling.shape, &future_loc) {
+    pub fn receive_right(&mut self) {
+        self.changed = true;
+
+        if !matches!(self.state, GameState::RUNNING) || self.is_entering() {
             return;
         }
-        self.falling.loc = future_loc;
+        self.keys += 1;
+        self.inputs += 1;
+        self.shift(1);
     }
 
-    pub fn receive_right(&mut self) {
+    // A move key going down moves the piece once, and holding on to it slides the piece as the
+    // handling has it. The keyboard repeating the key while it's held is left out, so only the
+    // handling decides how fast that goes
+    pub fn receive_left_press(&mut self) {
+        self.press_shift(false);
+    }
+
+    pub fn receive_right_press(&mut self) {
+        self.press_shift(true);
+    }
+
+    pub fn receive_left_release(&mut self) {
+        self.release_shift(false);
+    }
+
+    pub fn receive_right_release(&mut self) {
+        self.release_shift(true);
+    }
+
+    pub fn receive_down_press(&mut self) {
+        self.changed = true;
 
         if !matches!(self.state, GameState::RUNNING) {
             return;
         }
+        self.keys += 1;
 
-        let future_loc = self.falling.loc.add(1, 0);
-        if !self.can_place_at(&self.falling.shape, &future_loc) {
+        // with tap only, the next tap turns it back off
+        self.is_sped_up = !(self.handling.tap_only && self.is_sped_up);
+    }
+
+    pub fn receive_down_release(&mut self) {
+        self.changed = true;
+        if !self.handling.tap_only {
+            self.is_sped_up = false;
+        }
+    }
+
+    pub fn receive_hard_drop(&mut self) {
+        self.changed = true;
+
+        if !matches!(self.state, GameState::RUNNING) || !self.rules.hard_drop || self.is_entering() {
             return;
         }
-        self.falling.loc = future_loc;
+        self.keys += 1;
+
+        let landing = self.landing_position(&self.falling.shape, &self.falling.loc);
+        if landing.y != self.falling.loc.y {
+            self.rotated_last = false;
+        }
+        self.falling.loc = landing;
+        self.ground_falling_shape();
+        self.since_step = self.clock.now();
     }
 
-    pub fn receive_down_press(&mut self) {
+    // Like a hard drop, except the piece is left to lock on its own
+    pub fn receive_sonic_drop(&mut self) {
+        self.changed = true;
 
-        if !matches!(self.state, GameState::RUNNING) {
+        if !matches!(self.state, GameState::RUNNING) || !self.rules.sonic_drop || self.is_entering() {
+            return;
+        }
+        self.keys += 1;
+
+        let landing = self.landing_position(&self.falling.shape, &self.falling.loc);
+        if landing.y == self.falling.loc.y {
             return;
         }
+        self.falling.loc = landing;
+        self.rotated_last = false;
+        self.since_step = self.clock.now();
+        self.events.push_back(GameEvent::Moved);
+    }
 
-        self.is_sped_up = true;
+    pub fn receive_garbage(&mut self, lines: usize) {
+        self.changed = true;
+        self.pending_garbage += lines;
     }
 
-    pub fn receive_down_release(&mut self) {
-        self.is_sped_up = false;
+    // Cancels pending garbage with an outgoing attack, returns what is left to send
+    pub fn counter_garbage(&mut self, attack: usize) -> usize {
+        self.attack += attack;
+        let countered = attack.min(self.pending_garbage);
+        self.pending_garbage -= countered;
+        attack - countered
+    }
+
+    pub fn receive_use_item(&mut self) {
+        self.changed = true;
+
+        if !matches!(self.state, GameState::RUNNING) || self.is_entering() {
+            return;
+        }
+        self.keys += 1;
+        let Some(item) = self.item.take() else {
+            return;
+        };
+
+        match item {
+            Item::ClearBottomRow => {
+                self.field.rotate_right(1);
+                self.field[0].fill(None);
+                // the piece goes down with the stack if a cell from above dropped into it
+                let lower = self.falling.loc.add(0, 1);
+                if !self.can_place_at(&self.falling.shape, &self.falling.loc) && self.can_place_at(&self.falling.shape, &lower) {
+                    self.falling.loc = lower;
+                }
+            },
+            Item::ShrinkGarbage => self.pending_garbage /= 2,
+            Item::SlowGravity => self.slowed_until = Some(self.clock.now() + SLOW_GRAVITY_TIME),
+        }
+        self.events.push_back(GameEvent::ItemUsed);
+    }
+
+    // Pushes the whole field up by one and puts the row in at the bottom.
+    // The game is lost when that pushes blocks out of the top or leaves no room for the falling piece,
+    // which doesn't get pushed off the top row
+    pub fn insert_row(&mut self, row: [Option<Cell>; W]) {
+        self.changed = true;
+        let pushed_out = self.field[0].iter().any(|cell| cell.is_some());
+        self.field.rotate_left(1);
+        self.field[H - 1] = row;
+        for y in self.clearing.iter_mut() {
+            *y = y.saturating_sub(1);
+        }
+
+        if pushed_out {
+            self.loose();
+            return;
+        }
+        if self.is_entering() || self.can_place_at(&self.falling.shape, &self.falling.loc) {
+            return;
+        }
+        let above = self.falling.loc.add(0, -1);
+        if !above.is_above_field() && self.can_place_at(&self.falling.shape, &above) {
+            self.falling.loc = above;
+        } else {
+            self.loose();
+        }
+    }
+
+    // A garbage row with one hole in a random column
+    pub fn insert_garbage_row(&mut self) {
+        let mut row = [Some(Cell::garbage(self.clock.now())); W];
+        row[self.rng.gen_range(0..W)] = None;
+        self.insert_row(row);
+    }
+
+    // Rows that still have garbage in them
+    pub fn garbage_rows(&self) -> usize {
+        self.field.iter().filter(|row| row.iter().flatten().any(|cell| cell.garbage)).count()
+    }
+
+    pub fn level(&self) -> usize {
+        self.lines / self.rules.lines_per_level
+    }
+
+    // The stack reaches within a few rows of where pieces spawn
+    pub fn in_danger(&self) -> bool {
+        let danger_zone = Self::spawn_point(&self.rules).y + DANGER_ROWS;
+        self.field.iter().take(danger_zone).flatten().any(|cell| cell.is_some())
+    }
+
+    pub fn poll_event(&mut self) -> Option<GameEvent> {
+        self.events.pop_front()
+    }
+
+    // Gets how many lines went with every clear, whether or not the events get polled
+    pub fn on_lines_cleared(&mut self, callback: impl Fn(usize) + Send + Sync + 'static) {
+        self.callbacks.lines_cleared.push(Arc::new(callback));
+    }
+
+    pub fn on_game_over(&mut self, callback: impl Fn(&Tetris<W, H>) + Send + Sync + 'static) {
+        self.callbacks.game_over.push(Arc::new(callback));
     }
 
     pub fn receive_rotate(&mut self) {
+        self.turn(true);
+    }
+
+    pub fn receive_rotate_ccw(&mut self) {
+        self.turn(false);
+    }
+
+    fn turn(&mut self, clockwise: bool) {
+        self.changed = true;
 
         if !matches!(self.state, GameState::RUNNING) {
             return;
         }
+        if self.is_entering() {
+            self.initial_rotation = Some(clockwise);
+            return;
+        }
+        // one held over from the entry delay counts once it gets applied
+        self.keys += 1;
+        self.inputs += 1;
 
-        let mut future_shape = self.falling.shape.clone();
-        future_shape.rotate();
-
-        if !self.can_place_at(&future_shape, &self.falling.loc) {
+        let can_floor_kick = self.floor_kicks < self.rules.floor_kicks;
+        let Some((future_shape, future_loc, floor_kicked)) = self.rotated(&self.falling.shape, &self.falling.loc, clockwise, can_floor_kick) else {
             return;
+        };
+        if floor_kicked {
+            self.floor_kicks += 1;
         }
         self.falling.shape = future_shape;
+        self.falling.loc = future_loc;
+        self.rotated_last = true;
+        self.reset_lock_delay();
+        self.events.push_back(GameEvent::Rotated);
     }
 
-    fn can_place_at(&self, shape: &Shape, loc: &Point) -> bool {
+    // Swaps the falling piece with the held one, or with the next piece when nothing is held yet
+    pub fn receive_hold(&mut self) {
+        self.changed = true;
+
+        if !matches!(self.state, GameState::RUNNING) {
+            return;
+        }
+        if self.is_entering() {
+            self.initial_hold = true;
+            return;
+        }
+        self.keys += 1;
+        match self.rules.hold {
+            Hold::Disabled => return,
+            Hold::Once if !self.can_hold => return,
+            _ => {},
+        }
+
+        let current = PreparedShape { shape: Shape::of(self.falling.shape.name), color: self.falling.color };
+        let piece = match self.held.replace(current) {
+            Some(held) => held,
+            None => self.advance_queue(),
+        };
+        self.spawn(piece);
+        self.can_hold = false;
+        self.since_step = self.clock.now();
+
+        if !self.can_place_at(&self.falling.shape, &self.falling.loc) {
+            self.loose();
+        }
+    }
+
+    // The pieces coming up, starting with `next`
+    pub fn previews(&self) -> impl Iterator<Item = &PreparedShape> {
+        std::iter::once(&self.next).chain(self.preview.iter())
+    }
+
+    // No piece is falling while the next one waits for the entry delay
+    pub fn is_entering(&self) -> bool {
+        self.entering_since.is_some()
+    }
+
+    // Rows being cleared and how far into the line clear delay the game is, from 0 to 1
+    pub fn line_clear(&self) -> Option<(&[usize], f64)> {
+        let entering_since = self.entering_since.filter(|_| !self.clearing.is_empty())?;
+        let elapsed = self.clock.now().saturating_sub(entering_since).as_millis();
+        Some((&self.clearing, (elapsed as f64 / self.rules.line_clear_delay as f64).min(1.0)))
+    }
+
+    // Where the falling piece would land, when the rules show it
+    pub fn ghost(&self) -> Option<Point> {
+        if !self.rules.ghost || !matches!(self.state, GameState::RUNNING) || self.is_entering() {
+            return None;
+        }
+        Some(self.landing_position(&self.falling.shape, &self.falling.loc))
+    }
+
+    // Rows the falling piece would fill up if it were hard dropped right now, top to bottom
+    pub fn projected_clears(&self) -> Vec<usize> {
+        if !matches!(self.state, GameState::RUNNING) || self.is_entering() {
+            return vec![];
+        }
+        let landing = self.landing_position(&self.falling.shape, &self.falling.loc);
+        let mut cells: Vec<Point> = vec![];
+        ShapeIter::new(&self.falling.shape, &landing).for_each_mut(|p| cells.push(p));
+        if cells.iter().any(|p| p.is_above_field()) {
+            return vec![];
+        }
+        let mut rows: Vec<usize> = cells.iter().map(|p| p.y).collect();
+        rows.sort();
+        rows.dedup();
+        rows.retain(|y| (0..W).all(|x| self.field[*y][x].is_some() || cells.iter().any(|p| p.x == x && p.y == *y)));
+        rows
+    }
+
+    // The held piece and queue on top, then the field with `@` for the falling piece, `#` for
+    // the stack and `x` for garbage
+    pub fn to_ascii(&self) -> String {
+        let held = self.held.as_ref().map_or('-', |piece| piece.shape.name.to_char());
+        let falling = self.falling.shape.name.to_char();
+        let queue: Vec<String> = self.previews().chain(self.queue.iter()).map(|piece| piece.shape.name.to_char().to_string()).collect();
+        let mut ascii = format!("hold: {held}  falling: {falling}  next: {queue}\n", queue = queue.join(" "));
+
+        let falling: Vec<(usize, usize)> = {
+            let mut points = vec![];
+            self.falling.iter().for_each_mut(|p| points.push((p.x, p.y)));
+            points
+        };
+        for (y, row) in self.field.iter().enumerate() {
+            ascii.push('|');
+            for (x, cell) in row.iter().enumerate() {
+                ascii.push(match cell {
+                    _ if !self.is_entering() && falling.contains(&(x, y)) => '@',
+                    Some(cell) if cell.garbage => 'x',
+                    Some(_) => '#',
+                    None => '.',
+                });
+            }
+            ascii.push_str("|\n");
+        }
+        ascii.push('+');
+        ascii.push_str(&"-".repeat(W));
+        ascii.push_str("+\n");
+        ascii
+    }
+
+    // Reads what to_ascii writes. The header line and the bottom border are optional, `@` cells move the
+    // falling piece there, turned to match them, and `#` or `x` cells fill the field
+    pub fn from_ascii(ascii: &str, rules: RuleSet) -> Result<Tetris<W, H>, String> {
+        let piece = |name: &str| -> Result<Option<Tetromino>, String> {
+            match name {
+                "-" => Ok(None),
+                _ => name.chars().next().filter(|_| name.len() == 1).and_then(Tetromino::from_char).map(Some).ok_or(format!("unknown piece `{name}`")),
+            }
+        };
+
+        let (mut held, mut falling, mut next) = (None, None, vec![]);
+        let mut rows = vec![];
+        for line in ascii.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            if let Some(row) = line.strip_prefix('|') {
+                rows.push(row.strip_suffix('|').ok_or(format!("row `{line}` isn't closed with `|`"))?);
+            } else if !line.starts_with('+') {
+                // `hold: T  falling: S  next: I O`
+                let mut words = line.split_whitespace().peekable();
+                while let Some(label) = words.next() {
+                    let mut names = vec![];
+                    while let Some(name) = words.next_if(|word| !word.ends_with(':')) {
+                        names.push(name);
+                    }
+                    match label {
+                        "hold:" => held = names.first().map_or(Ok(None), |name| piece(name))?,
+                        "falling:" => falling = names.first().map_or(Ok(None), |name| piece(name))?,
+                        "next:" => next = names.iter().map(|name| piece(name)).collect::<Result<Vec<_>, _>>()?.into_iter().flatten().collect(),
+                        _ => return Err(format!("unknown label `{label}`")),
+                    }
+                }
+            }
+        }
+        if rows.len() != H {
+            return Err(format!("expected {H} rows, got {rows}", rows = rows.len()));
+        }
+
+        let mut field = [[None; W]; H];
+        let mut marked = vec![];
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() != W {
+                return Err(format!("row {n} isn't {W} cells wide", n = y + 1));
+            }
+            for (x, cell) in row.chars().enumerate() {
+                field[y][x] = match cell {
+                    '.' => None,
+                    '#' => Some(Cell::block(ASCII_STACK_COLOR)),
+                    'x' => Some(Cell::garbage(Duration::ZERO)),
+                    '@' => {
+                        marked.push((x, y));
+                        None
+                    },
+                    _ => return Err(format!("unknown cell `{cell}` in row {n}", n = y + 1)),
+                };
+            }
+        }
+
+        let queue = falling.iter().chain(next.iter()).copied().collect();
+        let mut tetris = Self::with_setup(field, queue, rules);
+        tetris.held = held.map(|name| PreparedShape::colored(Shape::of(name), &mut tetris.rng));
+        if !marked.is_empty() {
+            let names = falling.map_or(vec![Tetromino::I, Tetromino::O, Tetromino::T, Tetromino::S, Tetromino::Z, Tetromino::J, Tetromino::L], |name| vec![name]);
+            let (shape, loc) = names.into_iter().find_map(|name| Self::fit_shape(name, &marked))
+                .ok_or("the `@` cells don't make up a piece".to_string())?;
+            if falling.is_none() {
+                tetris.falling.color = PreparedShape::colored(shape, &mut tetris.rng).color;
+            }
+            tetris.falling.shape = shape;
+            tetris.falling.loc = loc;
+            tetris.spawned_as = tetris.falling.clone();
+        }
+        Ok(tetris)
+    }
+
+    // The turn of the piece and the spot it needs to be in to cover exactly these cells
+    fn fit_shape(name: Tetromino, cells: &[(usize, usize)]) -> Option<(Shape, Point)> {
+        let mut shape = Shape::of(name);
+        let (anchor_x, anchor_y) = cells[0];
+        for _ in 0..4 {
+            for anchor in shape.points().iter() {
+                let (x, y) = (anchor_x as i32 - anchor.dx, anchor_y as i32 - anchor.dy);
+                if x < 0 || y < 0 {
+                    continue;
+                }
+                let loc = Point::new(x as usize, y as usize);
+                let covers = shape.points().len() == cells.len() && shape.points().iter().all(|point| {
+                    let p = point.to_abs(&loc);
+                    cells.contains(&(p.x, p.y))
+                });
+                if covers {
+                    return Some((shape, loc));
+                }
+            }
+            shape.rotate();
+        }
+        None
+    }
+
+    // Events still waiting to be polled aren't part of it, they've happened already.
+    // Callbacks aren't either, they stay with the game that gets restored
+    pub fn snapshot(&self) -> Snapshot<W, H> {
+        let mut tetris = Tetris { events: VecDeque::new(), callbacks: Callbacks::default(), ..self.clone() };
+        tetris.clock.stop();
+        Snapshot { tetris }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot<W, H>) {
+        self.changed = true;
+        let callbacks = std::mem::take(&mut self.callbacks);
+        *self = snapshot.tetris.clone();
+        self.callbacks = callbacks;
+        if matches!(self.state, GameState::RUNNING) {
+            self.clock.run();
+        }
+    }
+
+    pub fn state_hash(&self) -> u64 {
+        let mut hash = StateHash::new();
+
+        for row in self.field.iter() {
+            let bits = row.iter().enumerate()
+                .filter(|(_, cell)| cell.is_some())
+                .fold(0u64, |bits, (x, _)| bits | 1 << x);
+            hash.write(&bits.to_le_bytes());
+        }
+
+        hash.write(&(self.falling.loc.x as u64).to_le_bytes());
+        hash.write(&(self.falling.loc.y as u64).to_le_bytes());
+        // every piece that's coming, not just the one shown next, so a copy whose generator went
+        // another way shows up before those pieces do
+        let upcoming = self.preview.iter().chain(self.queue.iter()).map(|prepared| &prepared.shape);
+        for shape in [&self.falling.shape, &self.next.shape].into_iter().chain(upcoming) {
+            for point in shape.points().iter() {
+                hash.write(&point.dx.to_le_bytes());
+                hash.write(&point.dy.to_le_bytes());
+            }
+            // so where one piece stops and the next starts goes into the hash as well
+            hash.write(&[0xff]);
+        }
+
+        hash.write(&(self.score as u64).to_le_bytes());
+        hash.finish()
+    }
+
+    pub(crate) fn can_place_at(&self, shape: &Shape, loc: &Point) -> bool {
+        // pieces spawn partly above the field, where there's nothing in the way
         !ShapeIter::new(&shape, &loc).any(|p| {
-            p.y >= H || p.x >= W || self.field[p.y][p.x].is_some()
+            p.x >= W || !p.is_above_field() && (p.y >= H || self.field[p.y][p.x].is_some())
         })
     }
 
+    // Where the shape comes to rest dropped straight down from `loc`. The ghost, hard and sonic drops
+    // and the bot all go by this
+    pub fn landing_position(&self, shape: &Shape, loc: &Point) -> Point {
+        let mut landing = *loc;
+        while self.can_place_at(shape, &landing.add(0, 1)) {
+            landing = landing.add(0, 1);
+        }
+        landing
+    }
+
     fn is_row_packed(&self, y: usize) -> bool {
         for x in 0..W {
             if !self.field[y][x].is_some() {
@@ -289,26 +1503,330 @@ impl<const W: usize, const H:usize> Tetris<W, H> {
         true
     }
 
-    fn destroy_full_rows(&mut self) -> usize {
-        let mut moving = 0;
-        for y in (0..H).rev() {
-            self.field.swap(y, y + moving);
-            if self.is_row_packed(y + moving) {
-                self.field[y + moving].fill(None);
-                moving += 1;
+    fn rise_garbage(&mut self) {
+        let lines = self.pending_garbage.min(H);
+        self.pending_garbage = 0;
+
+        if lines == 0 {
+            return;
+        }
+
+        let pushed_out = self.field[..lines].iter().any(|row| row.iter().any(|cell| cell.is_some()));
+
+        self.field.rotate_left(lines);
+        let mut hole = self.rng.gen_range(0..W);
+        let garbage = Cell::garbage(self.clock.now());
+        // bottom row first, so every row's hole moves away from the one below it
+        for row in self.field[H - lines..].iter_mut().rev() {
+            row.fill(Some(garbage));
+            row[hole] = None;
+            if self.rng.gen_ratio(self.rules.garbage_messiness as u32, 100) {
+                hole = self.rng.gen_range(0..W);
             }
         }
-        moving
+
+        if pushed_out {
+            self.loose();
+        }
+    }
+
+    // The shape turned either way and where it ends up after the rules' kicks, None if every kick is blocked.
+    // A resting piece that can't turn otherwise gets kicked up a cell when it may, which comes back as true
+    fn rotated(&self, shape: &Shape, loc: &Point, clockwise: bool, can_floor_kick: bool) -> Option<(Shape, Point, bool)> {
+        let mut future_shape = *shape;
+        // three turns clockwise are one the other way
+        for _ in 0..if clockwise {1} else {3} {
+            future_shape.rotate();
+        }
+
+        // SRS turning back out of an orientation kicks the opposite way to turning clockwise into it
+        let back_from = (shape.orientation + 3) % 4;
+        let (kicks, sign): (&[(i32, i32)], i32) = match (self.rules.rotation, shape.name, clockwise) {
+            (Rotation::Simple, _, _) => (&[(0, 0)], 1),
+            (Rotation::Nudge, Tetromino::I, _) => (&NUDGE_I_KICKS, 1),
+            (Rotation::Nudge, _, _) => (&NUDGE_KICKS, 1),
+            (Rotation::Srs, Tetromino::I, true) => (&SRS_I_KICKS[shape.orientation], 1),
+            (Rotation::Srs, Tetromino::I, false) => (&SRS_I_KICKS[back_from], -1),
+            (Rotation::Srs, _, true) => (&SRS_KICKS[shape.orientation], 1),
+            (Rotation::Srs, _, false) => (&SRS_KICKS[back_from], -1),
+        };
+        if let Some(future_loc) = kicks.iter().map(|(dx, dy)| loc.add(sign * dx, sign * dy)).find(|loc| self.can_place_at(&future_shape, loc)) {
+            return Some((future_shape, future_loc, false));
+        }
+
+        let resting = !self.can_place_at(shape, &loc.add(0, 1));
+        let kicked = loc.add(0, -1);
+        if can_floor_kick && resting && self.can_place_at(&future_shape, &kicked) {
+            return Some((future_shape, kicked, true));
+        }
+        None
+    }
+
+    // The game as it was when the falling piece came in, before it got moved or rotated
+    pub fn at_spawn(&self) -> Tetris<W, H> {
+        Tetris { falling: self.spawned_as.clone(), ..self.clone() }
+    }
+
+    // Every spot the falling piece can be locked in, tucks and spins under overhangs included.
+    // Spots that cover the same cells are listed once
+    pub fn legal_moves(&self) -> Vec<Move> {
+        self.moves_from(&self.falling.shape, &self.falling.loc, self.floor_kicks)
+    }
+
+    fn moves_from(&self, shape: &Shape, loc: &Point, floor_kicks: usize) -> Vec<Move> {
+        let mut moves = vec![];
+        let mut placed: HashSet<Vec<(usize, usize)>> = HashSet::new();
+        let mut visited = HashSet::new();
+        // taps after a soft drop count first, so a spot gets reached without a tuck whenever it can be,
+        // then the fewest taps, then the fewest soft drops
+        let mut reached = vec![(Move { shape: *shape, loc: *loc, inputs: vec![] }, floor_kicks)];
+        let mut queue = BinaryHeap::from([Reverse(((0, 0, 0), 0))]);
+
+        while let Some(Reverse(((late, taps, downs), idx))) = queue.pop() {
+            let (Move { shape, loc, inputs }, floor_kicks) = reached[idx].clone();
+            let dropped = inputs.contains(&Input::Down);
+            if !visited.insert((shape.orientation, loc.x, loc.y, dropped, floor_kicks)) {
+                continue;
+            }
+
+            let lower = loc.add(0, 1);
+            if !self.can_place_at(&shape, &lower) {
+                let mut cells = vec![];
+                ShapeIter::new(&shape, &loc).for_each_mut(|p| cells.push((p.x, p.y)));
+                cells.sort();
+                if placed.insert(cells) {
+                    moves.push(reached[idx].0.clone());
+                }
+            }
+
+            let rotated = self.rotated(&shape, &loc, true, floor_kicks < self.rules.floor_kicks);
+            let next = [
+                (Input::Left, Some((shape, loc.add(-1, 0), false))),
+                (Input::Right, Some((shape, loc.add(1, 0), false))),
+                (Input::Rotate, rotated),
+                (Input::Down, Some((shape, lower, false))),
+            ];
+            for (input, next) in next {
+                let Some((shape, loc, floor_kicked)) = next.filter(|(shape, loc, _)| self.can_place_at(shape, loc)) else {
+                    continue;
+                };
+                let cost = match input {
+                    Input::Down => (late, taps, downs + 1),
+                    _ if dropped => (late + 1, taps + 1, downs),
+                    _ => (late, taps + 1, downs),
+                };
+                let mut inputs = inputs.clone();
+                inputs.push(input);
+                reached.push((Move { shape, loc, inputs }, floor_kicks + floor_kicked as usize));
+                queue.push(Reverse((cost, reached.len() - 1)));
+            }
+        }
+        moves
+    }
+
+    // Fewest moves and rotations that put the spawned piece into the cells the falling piece occupies now,
+    // soft drops left out since they aren't counted either
+    fn finesse_optimum(&self) -> Option<usize> {
+        let cells_at = |shape: &Shape, loc: &Point| -> Vec<(usize, usize)> {
+            let mut cells = vec![];
+            ShapeIter::new(shape, loc).for_each_mut(|p| cells.push((p.x, p.y)));
+            cells.sort();
+            cells
+        };
+
+        let target = cells_at(&self.falling.shape, &self.falling.loc);
+        self.moves_from(&self.spawned_as.shape, &self.spawned_as.loc, 0).into_iter()
+            .find(|candidate| cells_at(&candidate.shape, &candidate.loc) == target)
+            .map(|candidate| candidate.inputs.iter().filter(|input| **input != Input::Down).count())
+    }
+
+    // A T that got rotated into place with three of the four corners around its centre blocked.
+    // Walls and the floor count as blocked.
+    fn is_t_spin(&self) -> bool {
+        if !self.rules.t_spins || self.falling.shape.name != Tetromino::T || !self.rotated_last {
+            return false;
+        }
+
+        let centre = self.falling.loc;
+        let blocked = [(-1, -1), (1, -1), (-1, 1), (1, 1)].iter()
+            .filter(|(dx, dy)| {
+                let (x, y) = (centre.x as i32 + dx, centre.y as i32 + dy);
+                x < 0 || y < 0 || x >= W as i32 || y >= H as i32 || self.field[y as usize][x as usize].is_some()
+            })
+            .count();
+        blocked >= 3
     }
 
     fn loose(&mut self) {
+        self.changed = true;
         self.state = GameState::LOST;
+        self.clock.stop();
+        self.events.push_back(GameEvent::GameOver);
+        for callback in &self.callbacks.game_over {
+            callback(self);
+        }
+    }
+
+    fn take_upcoming(&mut self) -> PreparedShape {
+        if let Some(piece) = self.queue.pop_front() {
+            return piece;
+        }
+
+        let mut piece = match self.rules.randomizer {
+            Randomizer::Random => PreparedShape::random(self.rules.pieces, &mut self.rng),
+            // with pentominoes, the bag holds one of each
+            Randomizer::SevenBag => {
+                if self.bag.is_empty() {
+                    self.bag = match self.rules.pieces {
+                        PieceSet::Tetrominoes => vec![Tetromino::O, Tetromino::J, Tetromino::L, Tetromino::I, Tetromino::S, Tetromino::Z, Tetromino::T],
+                        PieceSet::Pentominoes => (0..PENTOMINOES.len()).map(Tetromino::Pentomino).collect(),
+                        PieceSet::Custom(set) => (0..PIECE_SETS[set].pieces.len()).map(|idx| Tetromino::Custom(set, idx)).collect(),
+                    };
+                    self.bag.shuffle(&mut self.rng);
+                }
+                PreparedShape::colored(Shape::of(self.bag.pop().unwrap()), &mut self.rng)
+            },
+        };
+        if self.rules.mirror {
+            piece.shape.mirror();
+        }
+        piece
+    }
+
+    fn palette_at(&self, level: usize) -> &'static [Color; 4] {
+        if self.rules.level_colors {&LEVEL_COLORS[level % LEVEL_COLORS.len()]} else {&COLORS}
+    }
+
+    // Swaps every color of the old palette for its counterpart in the current one, garbage stays gray
+    fn repaint(&mut self, from: &[Color; 4]) {
+        let to = self.palette_at(self.level());
+        let repaint = |color: Color| repainted(color, from, to);
+
+        for cell in self.field.iter_mut().flatten().flatten() {
+            cell.color = repaint(cell.color);
+        }
+        self.falling.color = repaint(self.falling.color);
+        self.spawned_as.color = self.falling.color;
+        self.next.color = repaint(self.next.color);
+        for piece in self.preview.iter_mut().chain(self.held.iter_mut()) {
+            piece.color = repaint(piece.color);
+        }
+    }
+
+    fn shift(&mut self, dx: i32) -> bool {
+        let future_loc = self.falling.loc.add(dx, 0);
+        if !self.can_place_at(&self.falling.shape, &future_loc) {
+            return false;
+        }
+        self.falling.loc = future_loc;
+        self.rotated_last = false;
+        self.reset_lock_delay();
+        self.events.push_back(GameEvent::Moved);
+        true
+    }
+
+    // pressing the other way takes over from the key held so far
+    fn press_shift(&mut self, right: bool) {
+        if self.handling.tap_only {
+            self.tap_shift(right);
+            return;
+        }
+        if self.shifting.is_some_and(|shifting| shifting.right == right) {
+            return;
+        }
+        self.shifting = Some(Shifting { right, since: self.clock.now(), last: None });
+        if right {self.receive_right()} else {self.receive_left()}
+    }
+
+    fn release_shift(&mut self, right: bool) {
+        if !self.handling.tap_only && self.shifting.is_some_and(|shifting| shifting.right == right) {
+            self.shifting = None;
+        }
+    }
+
+    // A slide started by a double tap goes on until either move key is tapped or the next piece comes in
+    fn tap_shift(&mut self, right: bool) {
+        let now = self.clock.now();
+        if self.shifting.take().is_some_and(|shifting| shifting.right == right) {
+            self.last_tap = None;
+            return;
+        }
+        let double = self.last_tap.is_some_and(|(same, at)| same == right && now.saturating_sub(at).as_millis() < self.handling.das);
+        if right {self.receive_right()} else {self.receive_left()}
+        if double {
+            // already past the DAS, so the slide goes on at the ARR right away
+            let since = now.saturating_sub(Duration::from_millis(self.handling.das as u64));
+            self.shifting = Some(Shifting { right, since, last: Some(now) });
+            self.last_tap = None;
+        } else {
+            self.last_tap = Some((right, now));
+        }
+    }
+
+    // Slides the piece along for a move key held past the DAS. Steps of a slide aren't keys of their own
+    fn auto_shift(&mut self, now: Duration) {
+        let Some(shifting) = self.shifting else {
+            return;
+        };
+        if !matches!(self.state, GameState::RUNNING) || now.saturating_sub(shifting.since).as_millis() < self.handling.das {
+            return;
+        }
+        if shifting.last.is_some_and(|last| now.saturating_sub(last).as_millis() < self.handling.arr) {
+            return;
+        }
+        let steps = if self.handling.arr == 0 {W} else {1};
+        let dx = if shifting.right {1} else {-1};
+        for _ in 0..steps {
+            if !self.shift(dx) {
+                break;
+            }
+            self.changed = true;
+        }
+        self.shifting = Some(Shifting { last: Some(now), ..shifting });
+    }
+
+    fn reset_lock_delay(&mut self) {
+        if self.landed_at.is_some() && self.lock_resets < self.rules.lock_resets {
+            self.landed_at = Some(self.clock.now());
+            self.lock_resets += 1;
+        }
+    }
+
+    fn spawn(&mut self, piece: PreparedShape) {
+        let PreparedShape {mut shape, color} = piece;
+        if self.rules.flat_spawn {
+            shape = Shape::lying_flat(shape.name);
+        }
+        self.falling = SpawnedShape { shape, color, loc: Self::spawn_point(&self.rules) };
+        self.spawned_as = self.falling.clone();
+        self.inputs = 0;
+        self.rotated_last = false;
+        self.landed_at = None;
+        self.lock_resets = 0;
+        self.floor_kicks = 0;
+        // a held key carries over to the next piece, what the taps turned on doesn't
+        if self.handling.tap_only {
+            self.shifting = None;
+            self.last_tap = None;
+            self.is_sped_up = false;
+        }
+    }
+
+    // Moves every upcoming piece one step closer, returns the one that was next
+    fn advance_queue(&mut self) -> PreparedShape {
+        let mut upcoming = self.take_upcoming();
+        upcoming.color = repainted(upcoming.color, &COLORS, self.palette_at(self.level()));
+        self.preview.push_back(upcoming);
+
+        let upcoming = self.preview.pop_front().unwrap();
+        std::mem::replace(&mut self.next, upcoming)
     }
 
     fn spawn_new_shape(&mut self) {
-        self.falling = SpawnedShape::random::<W, H>();
-        std::mem::swap(&mut self.falling.shape, &mut self.next.shape);
-        std::mem::swap(&mut self.falling.color, &mut self.next.color);
+        self.pieces += 1;
+        let piece = self.advance_queue();
+        self.spawn(piece);
+        self.can_hold = true;
 
         if !self.can_place_at(&self.falling.shape, &self.falling.loc) {
             self.loose();
@@ -316,15 +1834,149 @@ impl<const W: usize, const H:usize> Tetris<W, H> {
     }
 
     fn ground_falling_shape(&mut self) {
+        if self.tracks_finesse && self.finesse_optimum().is_some_and(|optimum| self.inputs > optimum) {
+            self.finesse_faults += 1;
+        }
+
+        if self.falling.iter().any(|p| !p.is_above_field() && self.field[p.y][p.x].is_some()) {
+            eprintln!("locking a piece that overlaps the stack:\n{ascii}", ascii = self.to_ascii());
+        }
+        let t_spin = self.is_t_spin();
+        let mut locked_out = false;
         self.falling.iter().for_each_mut(|p| {
-            self.field[p.y][p.x] = Some(self.falling.color)
+            if p.is_above_field() {
+                locked_out = true;
+            } else {
+                self.field[p.y][p.x] = Some(Cell {
+                    color: self.falling.color,
+                    piece: Some(self.falling.shape.name),
+                    garbage: false,
+                    locked_at: self.clock.now(),
+                });
+            }
         });
+        self.events.push_back(GameEvent::Locked);
+        // the field has no room for what stuck out over the top
+        if locked_out {
+            self.loose();
+            return;
+        }
+
+        let level = self.level();
+        // worked out on a copy, chains included, so a perfect clear is known before the rows go
+        let mut cleared = self.field;
+        let rounds = clear::resolve(&mut cleared, self.rules.clear_gravity);
+        let perfect_clear = cleared.iter().all(|row| row.iter().all(|cell| cell.is_none()));
+        // the rows that were full as the piece locked score like any clear, every round they set off
+        // scores on its own as a chain further down. All of them count as lines
+        let lines = rounds.first().copied().unwrap_or(0);
+        self.lines += rounds.iter().sum::<usize>();
+        if self.rules.line_clear_delay > 0 {
+            self.clearing = (0..H).filter(|y| self.is_row_packed(*y)).collect();
+        } else {
+            self.field = cleared;
+        }
+
+        let score = match (t_spin, self.rules.t_spin_scoring) {
+            (true, Some(t_spin_scoring)) => t_spin_scoring[lines.min(3)],
+            _ => self.rules.scoring[lines.min(4)],
+        };
+        let difficult = lines >= 4 || t_spin;
+        let back_to_back = if lines > 0 && difficult && self.back_to_back && self.rules.back_to_back_bonus {score / 2} else {0};
+        let combo = if lines > 0 {self.combo * self.rules.combo_bonus} else {0};
+        let multiplier = if self.rules.level_multiplier {level + 1} else {1};
+        self.score += (score + back_to_back + combo) * multiplier;
+
+        let breakdown = &mut self.breakdown;
+        let part = match lines {
+            _ if t_spin => &mut breakdown.t_spins,
+            0 => &mut breakdown.drops,
+            1 => &mut breakdown.singles,
+            2 => &mut breakdown.doubles,
+            3 => &mut breakdown.triples,
+            _ => &mut breakdown.tetrises,
+        };
+        *part += score * multiplier;
+        breakdown.back_to_back += back_to_back * multiplier;
+        breakdown.combos += combo * multiplier;
+
+        if t_spin {
+            self.events.push_back(GameEvent::TSpin(lines));
+        }
+
+        if lines > 0 {
+            self.events.push_back(GameEvent::LinesCleared(lines));
+            for callback in &self.callbacks.lines_cleared {
+                callback(lines);
+            }
+
+            if difficult && self.back_to_back {
+                self.events.push_back(GameEvent::BackToBack);
+            }
+            self.back_to_back = difficult;
+
+            if self.combo > 0 {
+                self.events.push_back(GameEvent::Combo(self.combo));
+            }
+            self.combo += 1;
+
+            // every round of a chain is worth one more time its rows than the round before it
+            for (chain, rows) in rounds.iter().enumerate().skip(1) {
+                let points = self.rules.scoring[(*rows).min(4)] * (chain + 1) * multiplier;
+                self.score += points;
+                self.breakdown.chains += points;
+                self.events.push_back(GameEvent::Chain(chain));
+            }
+
+            if perfect_clear {
+                self.events.push_back(GameEvent::PerfectClear);
+            }
+
+            if self.item.is_none() && self.rng.gen_ratio(self.rules.item_chance as u32, 100) {
+                let item = Item::ALL[self.rng.gen_range(0..Item::ALL.len())];
+                self.item = Some(item);
+                self.events.push_back(GameEvent::ItemGained);
+            }
+        } else {
+            self.combo = 0;
+            self.rise_garbage();
+        }
+
+        if self.level() > level {
+            self.events.push_back(GameEvent::LevelUp(self.level()));
+            self.repaint(self.palette_at(level));
+        }
+
+        if matches!(self.state, GameState::LOST) {
+            return;
+        }
+        if self.rules.entry_delay > 0 || !self.clearing.is_empty() {
+            self.entering_since = Some(self.clock.now());
+            return;
+        }
+        self.spawn_new_shape();
+    }
 
-        self.score += self.destroy_full_rows();
+    // Brings the next piece in once the entry delay is over, held or rotated already if that was asked for
+    fn enter(&mut self) {
+        if !self.clearing.is_empty() {
+            // scored and counted when the piece locked, chains included
+            clear::resolve(&mut self.field, self.rules.clear_gravity);
+            self.clearing.clear();
+        }
+        self.entering_since = None;
         self.spawn_new_shape();
+        self.since_step = self.clock.now();
+
+        if std::mem::take(&mut self.initial_hold) {
+            self.receive_hold();
+        }
+        if let Some(clockwise) = self.initial_rotation.take() {
+            self.turn(clockwise);
+        }
     }
 
-    fn step(&mut self) {
+    pub(crate) fn step(&mut self) {
 
         if !matches!(self.state, GameState::RUNNING) {
             return;
@@ -333,14 +1985,45 @@ impl<const W: usize, const H:usize> Tetris<W, H> {
         let future_pos = self.falling.loc.add(0, 1);
 
         if self.can_place_at(&self.falling.shape, &future_pos) {
-            self.falling.loc.y += 1;
+            self.falling.loc = future_pos;
+            self.rotated_last = false;
+            self.landed_at = None;
             return;
         }
 
+        let now = self.clock.now();
+        let landed_at = *self.landed_at.get_or_insert(now);
+        if now.saturating_sub(landed_at).as_millis() < self.rules.lock_delay {
+            return;
+        }
         self.ground_falling_shape();
     }
 }
 
+fn repainted(color: Color, from: &[Color; 4], to: &[Color; 4]) -> Color {
+    from.iter().position(|old| *old == color).map_or(color, |idx| to[idx])
+}
+
+// FNV-1a, spelled out so hashes agree between builds and platforms
+struct StateHash(u64);
+
+impl StateHash {
+    fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
 pub struct ShapeIter<'a> {
     shape: &'a Shape,
     loc: &'a Point,
@@ -357,7 +2040,7 @@ impl<'a> ShapeIter<'a> {
     }
 
     pub fn any<T>(&self, predicate: T) -> bool where T: Fn(Point) -> bool {
-        for rel_point in self.shape.points.iter() {
+        for rel_point in self.shape.points().iter() {
             if predicate(rel_point.to_abs(self.loc)) {
                 return true;
             }
@@ -366,7 +2049,7 @@ impl<'a> ShapeIter<'a> {
     }
 
     pub fn any_mut<T>(&mut self, mut predicate: T) -> bool where T: FnMut(Point) -> bool {
-        for rel_point in self.shape.points.iter() {
+        for rel_point in self.shape.points().iter() {
             if predicate(rel_point.to_abs(self.loc)) {
                 return true;
             }
@@ -388,3 +2071,178 @@ impl<'a> ShapeIter<'a> {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The bottom rows of a 10 by 20 field, with empty rows above them
+    fn field(rows: &[&str]) -> String {
+        let mut ascii = "|..........|\n".repeat(20 - rows.len());
+        for row in rows {
+            ascii.push_str(&format!("|{row}|\n"));
+        }
+        ascii.push_str("+----------+\n");
+        ascii
+    }
+
+    // The cells the falling piece covers once it's dropped straight down
+    fn landed(tetris: &Tetris<10, 20>) -> Vec<(usize, usize)> {
+        let landing = tetris.landing_position(&tetris.falling.shape, &tetris.falling.loc);
+        let mut cells = vec![];
+        ShapeIter::new(&tetris.falling.shape, &landing).for_each_mut(|p| cells.push((p.x, p.y)));
+        cells.sort();
+        cells
+    }
+
+    fn board(rows: &[&str]) -> Tetris<10, 20> {
+        Tetris::from_ascii(&field(rows), RuleSet::default()).unwrap()
+    }
+
+    #[test]
+    fn lands_on_the_floor_of_an_empty_field() {
+        let mut rows = vec![".@@.......", ".@@......."];
+        rows.extend([".........."; 10]);
+        let tetris = board(&rows);
+        assert_eq!(landed(&tetris), vec![(1, 18), (1, 19), (2, 18), (2, 19)]);
+    }
+
+    #[test]
+    fn lands_on_an_overhang_rather_than_under_it() {
+        let tetris = board(&[
+            ".@@.......",
+            ".@@.......",
+            "..........",
+            "..........",
+            "####......",
+            "...#......",
+            "...#......",
+        ]);
+        assert_eq!(landed(&tetris), vec![(1, 15), (1, 16), (2, 15), (2, 16)]);
+    }
+
+    #[test]
+    fn a_resting_piece_lands_where_it_is() {
+        let tetris = board(&[
+            "....@@@...",
+            ".....@....",
+            "##.######.",
+        ]);
+        let landing = tetris.landing_position(&tetris.falling.shape, &tetris.falling.loc);
+        assert_eq!((landing.x, landing.y), (tetris.falling.loc.x, tetris.falling.loc.y));
+    }
+
+    #[test]
+    fn ascii_round_trip() {
+        let ascii = format!("hold: T  falling: S  next: I O L\n{field}", field = field(&[
+            "....@@....",
+            "...@@.....",
+            "x........#",
+            "xx.######.",
+        ]));
+        let tetris = Tetris::<10, 20>::from_ascii(&ascii, RuleSet::default()).unwrap();
+        assert_eq!(tetris.to_ascii(), ascii);
+    }
+
+    #[test]
+    fn ascii_rejects_malformed_board() {
+        let ascii = field(&["....#....."]).replacen("|..........|", "|.........|", 1);
+        assert!(Tetris::<10, 20>::from_ascii(&ascii, RuleSet::default()).is_err());
+    }
+
+    #[test]
+    fn a_piece_above_the_top_row_falls_and_lands() {
+        let mut rows = vec!["...@@@@..."];
+        rows.extend([".........."; 19]);
+        let mut tetris = board(&rows);
+        // where a kick off the top row leaves its centre
+        tetris.falling.loc = tetris.falling.loc.add(0, -1);
+        assert!(tetris.falling.loc.is_above_field());
+        assert_eq!(landed(&tetris), vec![(3, 19), (4, 19), (5, 19), (6, 19)]);
+        tetris.start();
+        tetris.step();
+        assert_eq!(tetris.falling.loc.y, 0);
+    }
+
+    // The field as to_ascii writes it, without the header
+    fn field_of(tetris: &Tetris<10, 20>) -> String {
+        tetris.to_ascii().split_once('\n').unwrap().1.to_string()
+    }
+
+    fn garbage_row(hole: usize) -> [Option<Cell>; 10] {
+        let mut row = [Some(Cell::garbage(Duration::ZERO)); 10];
+        row[hole] = None;
+        row
+    }
+
+    #[test]
+    fn an_inserted_row_pushes_the_stack_and_the_piece_in_its_way_up() {
+        let mut tetris = board(&[
+            ".@@.......",
+            ".@@.......",
+            "#########.",
+        ]);
+        tetris.start();
+        tetris.insert_row(garbage_row(9));
+        assert_eq!(field_of(&tetris), field(&[
+            ".@@.......",
+            ".@@.......",
+            "#########.",
+            "xxxxxxxxx.",
+        ]));
+        assert!(matches!(tetris.state, GameState::RUNNING));
+    }
+
+    #[test]
+    fn a_row_pushing_into_a_piece_on_the_top_row_ends_the_game() {
+        let mut rows = vec!["...@@@@..."];
+        rows.extend(["#########."; 19]);
+        let mut tetris = board(&rows);
+        tetris.start();
+        tetris.insert_row(garbage_row(0));
+        assert!(matches!(tetris.state, GameState::LOST));
+    }
+
+    #[test]
+    fn survival_ends_once_the_garbage_reaches_the_top() {
+        let mut tetris = Tetris::<10, 20>::with_seed(RuleSet::survival(), 7);
+        tetris.start();
+        tetris.replay(Duration::from_millis(RuleSet::survival().garbage_interval as u64), Recorded::Tick);
+        assert!(matches!(tetris.state, GameState::RUNNING));
+        assert_eq!(tetris.garbage_rows(), 1);
+        tetris.replay(Duration::from_secs(200), Recorded::Tick);
+        assert!(matches!(tetris.state, GameState::LOST));
+    }
+
+    #[test]
+    fn rows_a_clear_sets_off_score_as_a_chain() {
+        let rules = RuleSet { clear_gravity: crate::rules::ClearGravity::Sticky, ..RuleSet::default() };
+        let mut tetris = Tetris::<10, 20>::from_ascii(&field(&[
+            "..#......@",
+            "..#......@",
+            "#########@",
+            "##.######@",
+        ]), rules).unwrap();
+        tetris.start();
+        tetris.receive_hard_drop();
+        assert_eq!(tetris.lines, 2);
+        assert_eq!(tetris.breakdown.singles, tetris.rules.scoring[1]);
+        assert_eq!(tetris.breakdown.chains, 2 * tetris.rules.scoring[1]);
+        assert!(tetris.events.iter().any(|event| matches!(event, GameEvent::Chain(1))));
+    }
+
+    #[test]
+    fn rotating_counter_clockwise_is_three_turns_clockwise() {
+        let mut rows = vec!["....@.....", "...@@@...."];
+        rows.extend([".........."; 16]);
+        let (mut once, mut thrice) = (board(&rows), board(&rows));
+        once.start();
+        thrice.start();
+        once.receive_rotate_ccw();
+        for _ in 0..3 {
+            thrice.receive_rotate();
+        }
+        assert_eq!(landed(&once), landed(&thrice));
+        assert_ne!(landed(&once), landed(&board(&rows)));
+    }
+}
@@ -0,0 +1,72 @@
+use std::fs;
+use std::time::Duration;
+use crate::game::Tetris;
+use crate::save::save_or_report;
+
+// The field of a run every time a piece locked, to race against it later.
+// The lines cleared with every frame also give the run's splits.
+pub struct GhostRun {
+    frames: Vec<GhostFrame>,
+}
+
+pub struct GhostFrame {
+    pub time: Duration,
+    pub lines: usize,
+    // one bit per filled cell, lowest bit on the left
+    rows: Vec<u32>,
+}
+
+impl GhostFrame {
+    pub fn is_filled(&self, x: usize, y: usize) -> bool {
+        self.rows.get(y).is_some_and(|row| row & 1 << x != 0)
+    }
+}
+
+impl GhostRun {
+    pub fn new() -> Self {
+        Self { frames: vec![] }
+    }
+
+    // Saved as one `millis lines row row ...` line per frame, a broken file gives no ghost at all
+    pub fn load(file: &str) -> Option<Self> {
+        let frames = fs::read_to_string(file).ok()?
+            .lines()
+            .map(|line| {
+                let mut numbers = line.split_whitespace();
+                let time = Duration::from_millis(numbers.next()?.parse().ok()?);
+                let lines = numbers.next()?.parse().ok()?;
+                let rows = numbers.map(|row| row.parse().ok()).collect::<Option<_>>()?;
+                Some(GhostFrame { time, lines, rows })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self { frames })
+    }
+
+    pub fn save(&self, file: &str) {
+        let lines: Vec<String> = self.frames.iter()
+            .map(|frame| {
+                let rows: Vec<String> = frame.rows.iter().map(u32::to_string).collect();
+                format!("{time} {lines} {rows}", time = frame.time.as_millis(), lines = frame.lines, rows = rows.join(" "))
+            })
+            .collect();
+        save_or_report(file, lines.join("\n") + "\n");
+    }
+
+    pub fn record<const W: usize, const H: usize>(&mut self, tetris: &Tetris<W, H>) {
+        let rows = tetris.field.iter()
+            .map(|row| row.iter().enumerate().filter(|(_, cell)| cell.is_some()).fold(0, |bits, (x, _)| bits | 1 << x))
+            .collect();
+        self.frames.push(GhostFrame { time: tetris.elapsed(), lines: tetris.lines, rows });
+    }
+
+    // How long the run took to clear that many lines, the splits of the run
+    pub fn split(&self, lines: usize) -> Option<Duration> {
+        self.frames.iter().find(|frame| frame.lines >= lines).map(|frame| frame.time)
+    }
+
+    // Where the run was at that point in time
+    pub fn at(&self, time: Duration) -> Option<&GhostFrame> {
+        let idx = self.frames.partition_point(|frame| frame.time <= time);
+        self.frames.get(idx.checked_sub(1)?)
+    }
+}
@@ -0,0 +1,51 @@
+use std::time::Duration;
+use crate::game::Tetris;
+
+// score needed for every grade from 9 up to S9, the way the arcade game hands them out
+const THRESHOLDS: [(&str, usize); 18] = [
+    ("9", 0),
+    ("8", 400),
+    ("7", 800),
+    ("6", 1400),
+    ("5", 2000),
+    ("4", 3500),
+    ("3", 5500),
+    ("2", 8000),
+    ("1", 12000),
+    ("S1", 16000),
+    ("S2", 22000),
+    ("S3", 30000),
+    ("S4", 40000),
+    ("S5", 52000),
+    ("S6", 66000),
+    ("S7", 82000),
+    ("S8", 100000),
+    ("S9", 120000),
+];
+
+// GM takes an S9 score, reaching this level, and doing it quickly enough
+const GM_SCORE: usize = 126000;
+const GM_LEVEL: usize = 20;
+const GM_TIME: Duration = Duration::from_secs(13 * 60 + 30);
+
+// Follows a Master mode game. The grade only ever goes up, like in the arcade.
+#[derive(Default)]
+pub struct Grading {
+    grade: usize,
+    is_gm: bool,
+}
+
+impl Grading {
+    pub fn update<const W: usize, const H: usize>(&mut self, tetris: &Tetris<W, H>) {
+        let earned = THRESHOLDS.iter().rposition(|(_, score)| tetris.score >= *score).unwrap_or(0);
+        self.grade = self.grade.max(earned);
+
+        if tetris.score >= GM_SCORE && tetris.level() >= GM_LEVEL && tetris.elapsed() <= GM_TIME {
+            self.is_gm = true;
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        if self.is_gm {"GM"} else {THRESHOLDS[self.grade].0}
+    }
+}
@@ -0,0 +1,167 @@
+use fltk::enums::Key;
+use crate::locale::tr;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Action {
+    Left,
+    Right,
+    SoftDrop,
+    Rotate,
+    RotateCcw,
+    HardDrop,
+    SonicDrop,
+    Hold,
+    UseItem,
+}
+
+impl Action {
+    pub const ALL: [Action; 9] = [
+        Action::Left,
+        Action::Right,
+        Action::SoftDrop,
+        Action::Rotate,
+        Action::RotateCcw,
+        Action::HardDrop,
+        Action::SonicDrop,
+        Action::Hold,
+        Action::UseItem,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Left => tr("action.left"),
+            Action::Right => tr("action.right"),
+            Action::SoftDrop => tr("action.soft_drop"),
+            Action::Rotate => tr("action.rotate"),
+            Action::RotateCcw => tr("action.rotate_ccw"),
+            Action::HardDrop => tr("action.hard_drop"),
+            Action::SonicDrop => tr("action.sonic_drop"),
+            Action::Hold => tr("action.hold"),
+            Action::UseItem => tr("action.use_item"),
+        }
+    }
+
+    // key of the action's line in config.toml
+    pub fn config_key(&self) -> &'static str {
+        match self {
+            Action::Left => "key_left",
+            Action::Right => "key_right",
+            Action::SoftDrop => "key_soft_drop",
+            Action::Rotate => "key_rotate",
+            Action::RotateCcw => "key_rotate_ccw",
+            Action::HardDrop => "key_hard_drop",
+            Action::SonicDrop => "key_sonic_drop",
+            Action::Hold => "key_hold",
+            Action::UseItem => "key_use_item",
+        }
+    }
+}
+
+// Which key does what in a game, in the same order as Action::ALL
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyBindings {
+    keys: [Key; 9],
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            keys: [
+                Key::from_char('a'),
+                Key::from_char('d'),
+                Key::from_char('s'),
+                Key::from_char('w'),
+                Key::from_char('q'),
+                Key::from_char(' '),
+                Key::from_char('x'),
+                Key::from_char('c'),
+                Key::from_char('e'),
+            ],
+        }
+    }
+}
+
+impl KeyBindings {
+    // Every action under the right hand, on the arrows and the block of keys above them, the way
+    // the default keys are all under the left. Letters on the right side of the keyboard are left
+    // alone, the game page has some of them
+    pub fn one_handed() -> Self {
+        Self {
+            keys: [
+                Key::Left,
+                Key::Right,
+                Key::Down,
+                Key::Up,
+                Key::Home,
+                Key::End,
+                Key::PageDown,
+                Key::Delete,
+                Key::Insert,
+            ],
+        }
+    }
+
+    fn index(action: Action) -> usize {
+        Action::ALL.iter().position(|other| *other == action).unwrap()
+    }
+
+    pub fn key(&self, action: Action) -> Key {
+        self.keys[Self::index(action)]
+    }
+
+    pub fn set(&mut self, action: Action, key: Key) {
+        self.keys[Self::index(action)] = key;
+    }
+
+    pub fn action(&self, key: Key) -> Option<Action> {
+        Action::ALL.iter().copied().find(|action| self.key(*action) == key)
+    }
+}
+
+// The game page's own keys: the ghost, the hint, the layout, the music, the next and restarted puzzle,
+// pause and Ctrl+Z. They're checked before the bindings, so none of them can be bound to an action
+pub const KEY_G: Key = Key::from_char('g');
+pub const KEY_H: Key = Key::from_char('h');
+pub const KEY_L: Key = Key::from_char('l');
+pub const KEY_M: Key = Key::from_char('m');
+pub const KEY_N: Key = Key::from_char('n');
+pub const KEY_P: Key = Key::from_char('p');
+pub const KEY_R: Key = Key::from_char('r');
+pub const KEY_Z: Key = Key::from_char('z');
+const PAGE_KEYS: [Key; 8] = [KEY_G, KEY_H, KEY_L, KEY_M, KEY_N, KEY_P, KEY_R, KEY_Z];
+
+const NAMED_KEYS: [(&str, Key); 13] = [
+    ("Space", Key::from_char(' ')),
+    ("Left", Key::Left),
+    ("Right", Key::Right),
+    ("Up", Key::Up),
+    ("Down", Key::Down),
+    ("Shift", Key::ShiftL),
+    ("Enter", Key::Enter),
+    ("Tab", Key::Tab),
+    ("Insert", Key::Insert),
+    ("Home", Key::Home),
+    ("Delete", Key::Delete),
+    ("End", Key::End),
+    ("PageDown", Key::PageDown),
+];
+
+// Either one of the NAMED_KEYS or a single character, in any case, as long as it isn't one of the PAGE_KEYS
+pub fn parse_key(name: &str) -> Option<Key> {
+    let name = name.to_lowercase();
+    if let Some((_, key)) = NAMED_KEYS.iter().find(|(named, _)| named.to_lowercase() == name) {
+        return Some(*key);
+    }
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(char), None) => Some(Key::from_char(char)).filter(|key| !PAGE_KEYS.contains(key)),
+        _ => None,
+    }
+}
+
+pub fn key_name(key: Key) -> String {
+    if let Some((name, _)) = NAMED_KEYS.iter().find(|(_, named)| *named == key) {
+        return name.to_string();
+    }
+    char::from_u32(key.bits() as u32).filter(|char| !char.is_control()).map_or("?".to_string(), |char| char.to_uppercase().to_string())
+}
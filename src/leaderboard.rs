@@ -0,0 +1,48 @@
+use std::fs;
+use std::time::Duration;
+use crate::save::save_or_report;
+
+const MAX_ENTRIES: usize = 10;
+
+// The fastest times of a race mode, kept next to the game as one millisecond count per line
+pub struct Leaderboard {
+    file: &'static str,
+    times: Vec<Duration>,
+}
+
+impl Leaderboard {
+    pub fn load(file: &'static str) -> Self {
+        let mut times: Vec<Duration> = fs::read_to_string(file).unwrap_or_default()
+            .lines()
+            .filter_map(|line| line.trim().parse().ok())
+            .map(Duration::from_millis)
+            .collect();
+        times.sort();
+        times.truncate(MAX_ENTRIES);
+        Self { file, times }
+    }
+
+    pub fn times(&self) -> &[Duration] {
+        &self.times
+    }
+
+    // Returns the place the time got, None when it's too slow to make the list
+    pub fn record(&mut self, time: Duration) -> Option<usize> {
+        let place = self.times.partition_point(|best| *best <= time);
+        if place >= MAX_ENTRIES {
+            return None;
+        }
+        self.times.insert(place, time);
+        self.times.truncate(MAX_ENTRIES);
+
+        let lines: Vec<String> = self.times.iter().map(|time| time.as_millis().to_string()).collect();
+        save_or_report(self.file, lines.join("\n") + "\n");
+        Some(place)
+    }
+}
+
+// Like 1:05.32
+pub fn format_time(time: Duration) -> String {
+    let centis = time.as_millis() / 10;
+    format!("{minutes}:{seconds:02}.{centis:02}", minutes = centis / 6000, seconds = centis / 100 % 60, centis = centis % 100)
+}
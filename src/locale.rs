@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const LOCALES_DIR: &str = "locales";
+
+// The built-in languages, English first since it's what missing texts fall back to,
+// followed by every `<code>.txt` in the locales folder next to the game
+pub static LOCALES: std::sync::LazyLock<Vec<Locale>> = std::sync::LazyLock::new(|| {
+    let mut locales = vec![
+        parse("en", "assets/locales/en.txt", include_str!("../assets/locales/en.txt")),
+        parse("de", "assets/locales/de.txt", include_str!("../assets/locales/de.txt")),
+    ];
+    let mut files: Vec<_> = fs::read_dir(LOCALES_DIR).into_iter().flatten().flatten().map(|entry| entry.path()).collect();
+    files.sort();
+    for path in files {
+        let (Some(code), Ok(data)) = (path.file_stem().and_then(|code| code.to_str()), fs::read_to_string(&path)) else {
+            continue;
+        };
+        if path.extension().is_some_and(|extension| extension == "txt") {
+            let locale = parse(code, &path.display().to_string(), &data);
+            // a file for a built-in language replaces it
+            match locales.iter().position(|other| other.code == locale.code) {
+                Some(idx) => locales[idx] = locale,
+                None => locales.push(locale),
+            }
+        }
+    }
+    locales
+});
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+
+pub struct Locale {
+    pub code: String,
+    pub name: String,
+    texts: HashMap<String, String>,
+}
+
+fn parse(code: &str, file: &str, data: &str) -> Locale {
+    let mut texts = HashMap::new();
+    for (number, line) in data.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, text)) = line.split_once('=') else {
+            eprintln!("{file}:{line}: expected `key = text`", line = number + 1);
+            continue;
+        };
+        texts.insert(key.trim().to_string(), text.trim().to_string());
+    }
+    let name = texts.get("language").cloned().unwrap_or_else(|| code.to_string());
+    Locale { code: code.to_string(), name, texts }
+}
+
+pub fn set_language(code: &str) {
+    CURRENT.store(LOCALES.iter().position(|locale| locale.code == code).unwrap_or(0), Ordering::Relaxed);
+}
+
+pub fn language() -> &'static Locale {
+    &LOCALES[CURRENT.load(Ordering::Relaxed)]
+}
+
+// The language after the current one, going back to the first after the last
+pub fn next_language() -> &'static Locale {
+    &LOCALES[(CURRENT.load(Ordering::Relaxed) + 1) % LOCALES.len()]
+}
+
+// Texts the language doesn't have come out in English, and as their key if English doesn't have them either
+pub fn tr(key: &'static str) -> &'static str {
+    language().texts.get(key)
+        .or_else(|| LOCALES[0].texts.get(key))
+        .map_or(key, |text| text.as_str())
+}
+
+// Fills in the text's `{name}` placeholders
+pub fn tr_with(key: &'static str, args: &[(&str, &dyn Display)]) -> String {
+    args.iter().fold(tr(key).to_string(), |text, (name, value)| text.replace(&format!("{{{name}}}"), &value.to_string()))
+}
@@ -1,16 +1,85 @@
+mod access;
+mod audio;
+mod awake;
+mod bench;
+mod bot;
+mod clear;
+mod config;
+mod daily;
+mod editor;
+mod engine;
+mod export;
+mod external;
+mod focus;
 mod game;
+mod ghost;
+mod grade;
+mod keys;
+mod leaderboard;
+mod locale;
+mod mode;
+mod net;
+mod online;
+mod opening;
+mod pieces;
+mod presence;
+mod puzzle;
+mod replay;
+mod rules;
+mod save;
+mod stats;
+mod twitch;
+mod undo;
+mod versus;
 
-use std::cell::{RefCell};
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
 use fltk::*;
-use fltk::app::{App, Sender};
+use fltk::app::{App, Sender, TimeoutHandle};
 use fltk::button::Button;
-use fltk::enums::{Align, Color, Event, Font, Key};
+use fltk::enums::{Align, CallbackTrigger, Color, ColorDepth, Event, Font, Key};
+use fltk::frame::Frame;
+use fltk::image::RgbImage;
 
-use fltk::group::{Pack};
-use fltk::prelude::{GroupExt, ImageExt, WidgetBase, WidgetExt};
+use fltk::group::{Pack, PackType};
+use fltk::prelude::{GroupExt, ImageExt, ValuatorExt, WidgetBase, WidgetExt, WindowExt};
+use fltk::valuator::HorNiceSlider;
+use fltk::widget::Widget;
 use fltk::window::{DoubleWindow, Window};
-use game::{ShapeIter, GameState, Point, Tetris};
+use access::Announcer;
+use audio::{sound_packs, Audio, Track, Volume};
+use bot::{Bot, Difficulty, Placement};
+use config::{Accessibility, ChromaKey, Config, Theme, MAX_ARR, MAX_CELL_SIZE, MAX_DAS, MAX_SOFT_DROP_FACTOR, MIN_CELL_SIZE, MIN_GAME_SPEED};
+use daily::DailyBest;
+use editor::BoardSetup;
+use engine::{Engine, Simulation};
+use focus::{Focusable, MenuFocus};
+use game::{GameEvent, Handling, ShapeIter, GameState, Point, PreparedShape, Tetris, Tetromino};
+use ghost::GhostRun;
+use grade::Grading;
+use keys::{key_name, Action, KeyBindings, KEY_G, KEY_H, KEY_L, KEY_M, KEY_N, KEY_P, KEY_R, KEY_Z};
+use leaderboard::{format_time, Leaderboard};
+use locale::{tr, tr_with};
+use mode::{Mode, SPRINT_LINES, SPRINT_SPLITS};
+use net::protocol::Message;
+use net::room::BoardWall;
+use net::snapshot::{self, Block};
+use online::Entry;
+use opening::{Opening, OPENINGS};
+use pieces::PIECE_SETS;
+use presence::Activity;
+use puzzle::{Outcome, Puzzle, PuzzleProgress, PUZZLES};
+use replay::{Recorded, Replay};
+use rules::{Hold, PieceSet, RuleSet};
+use stats::Performance;
+use twitch::{ChatVotes, Command};
+use undo::UndoHistory;
+use versus::{Handicap, Match, Player, Targeting};
 
 const CELL_SIZE: i32 = 40;
 const CANVAS_X: i32 = 0;
@@ -18,157 +87,2581 @@ const CANVAS_Y: i32 = 40;
 
 const WINDOW_W: i32 = 680;
 const WINDOW_H: i32 = 880;
+// as tall as the usual window, and as wide as a 16:9 capture of it has to be
+const STREAM_W: i32 = WINDOW_H * 16 / 9;
+// hold, score and stats go left of the field in the stream layout, this far from it
+const STREAM_SIDE_PANEL_W: i32 = 10 * CELL_SIZE + 10;
 
-const KEY_A: Key = Key::from_char('a');
-const KEY_D: Key = Key::from_char('d');
-const KEY_S: Key = Key::from_char('s');
-const KEY_W: Key = Key::from_char('w');
+const SMALL_CELL_SIZE: i32 = 16;
+// how far from the edge a piece is laid out before being measured, enough for any custom piece
+const PREVIEW_SLOT: usize = 3;
+const QUEUE_LEN: usize = 5;
+const QUEUE_BOX_W: i32 = 72;
+const QUEUE_BOX_H: i32 = 42;
+const QUEUE_GAP: i32 = 4;
+const QUEUE_LABEL_W: i32 = 50;
+const QUEUE_BOX_COLOR: Color = Color::from_rgb(148, 151, 192);
+const VERSUS_CELL_SIZE: i32 = 24;
+const VERSUS_MARGIN: i32 = 20;
+const VERSUS_GAP: i32 = 60;
+const VERSUS_BOARD_Y: i32 = CANVAS_Y + 120;
+const MAX_CPU_OPPONENTS: usize = 15;
+// from this many players on, yours is the only full field and everyone else's goes on the board wall
+const BOARD_WALL_FROM: usize = 5;
+const WALL_CELL_SIZE: i32 = 28;
+const MINI_CELL_SIZE: i32 = 5;
+const MINI_GAP: i32 = 10;
+// for the two lines under a small field
+const MINI_LABEL_H: i32 = 24;
+const MINI_STACK_COLOR: Color = Color::from_rgb(150, 150, 150);
+const MINI_GARBAGE_COLOR: Color = Color::from_rgb(90, 90, 90);
+const PENTOMINO_FIELD_W: usize = 12;
+const DEMO_IDLE_SECONDS: f64 = 30.0;
+const UNDO_DEPTH: usize = 50;
+const CONTROLS_LINE_H: i32 = 16;
+const SPRINT_GHOST_FILE: &str = "sprint_ghost.txt";
+// the latest leaderboard game, for --verify
+const REPLAY_FILE: &str = "replay.bin";
+const GHOST_CELL_SIZE: i32 = 12;
+const GHOST_COLOR_FILLED: Color = Color::from_rgb(70, 70, 90);
+const SPLIT_AHEAD_COLOR: Color = Color::from_rgb(60, 200, 80);
+const SPLIT_BEHIND_COLOR: Color = Color::from_rgb(230, 60, 60);
+const CHAIN_POPUP_TIME: Duration = Duration::from_millis(1200);
+// chat gets this long to vote for each move
+const VOTE_ROUND: Duration = Duration::from_secs(2);
+const CHAIN_POPUP_COLOR: Color = Color::from_rgb(255, 200, 80);
+// how many of the best times get shown next to the game
+const SHOWN_TIMES: usize = 5;
+// how often the online boards are checked on while any of them is still loading
+const SCORES_POLL_SECONDS: f64 = 0.2;
+
+const KEY_SPACE: Key = Key::from_char(' ');
+
+#[derive(Copy, Clone)]
+struct MatchSettings {
+    opponents: usize,
+    targeting: Targeting,
+    difficulty: Difficulty,
+    items: bool,
+    your_handicap: Handicap,
+    cpu_handicap: Handicap,
+}
 
 #[derive(Copy, Clone)]
 enum Page {
     Menu,
-    Game,
+    Game(Mode),
+    Versus(MatchSettings),
+    Demo,
+    Editor,
+    Settings,
+    Handling,
+    HighScores,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum PauseOption {
+    Resume,
+    Restart,
+    Settings,
+    Quit,
+}
+
+const PAUSE_OPTIONS: [PauseOption; 4] = [PauseOption::Resume, PauseOption::Restart, PauseOption::Settings, PauseOption::Quit];
+const PAUSE_OPTION_W: i32 = 240;
+const PAUSE_OPTION_H: i32 = 50;
+const PAUSE_MENU_Y: i32 = CANVAS_Y + 200;
+// alpha of the black laid over the game while it's paused
+const PAUSE_DIM: u8 = 170;
+
+impl PauseOption {
+    fn name(&self) -> &'static str {
+        match self {
+            PauseOption::Resume => tr("pause.resume"),
+            PauseOption::Restart => tr("pause.restart"),
+            PauseOption::Settings => tr("pause.settings"),
+            PauseOption::Quit => tr("pause.quit"),
+        }
+    }
+
+    // (x, y, w, h) of the option's box
+    fn rect(idx: usize) -> (i32, i32, i32, i32) {
+        ((window_w() - PAUSE_OPTION_W) / 2, PAUSE_MENU_Y + idx as i32 * (PAUSE_OPTION_H + 10), PAUSE_OPTION_W, PAUSE_OPTION_H)
+    }
+
+    fn at((x, y): (i32, i32)) -> Option<usize> {
+        (0..PAUSE_OPTIONS.len()).find(|idx| {
+            let (left, top, w, h) = Self::rect(*idx);
+            (left..left + w).contains(&x) && (top..top + h).contains(&y)
+        })
+    }
+}
+
+#[derive(Clone, Default)]
+struct IdleTimer(Rc<Cell<Option<TimeoutHandle>>>);
+
+impl IdleTimer {
+    fn restart(&self, sender: Sender<Page>) {
+        self.cancel();
+        self.0.set(Some(app::add_timeout3(DEMO_IDLE_SECONDS, move |_| sender.send(Page::Demo))));
+    }
+
+    fn cancel(&self) {
+        if let Some(handle) = self.0.take() {
+            app::remove_timeout3(handle);
+        }
+    }
+}
+
+// Redraws the view of the current page's game at a steady rate of its own, whatever else the window is
+// up to, until the page changes. The game itself runs on an engine thread, and the view's callback is
+// what takes in the copies of it that came back
+#[derive(Clone)]
+struct GameTimer {
+    frame_seconds: f64,
+    handle: Rc<Cell<Option<TimeoutHandle>>>,
+}
+
+impl GameTimer {
+    fn new(render_fps: u32) -> Self {
+        GameTimer { frame_seconds: 1.0 / render_fps as f64, handle: Rc::default() }
+    }
+
+    // Frames are only drawn once the view is marked as changed, by the game moving or by an event
+    fn start(&self, mut view: Widget) {
+        self.stop();
+        view.set_changed();
+        let frame_seconds = self.frame_seconds;
+        let frame = app::add_timeout3(frame_seconds, move |handle| {
+            view.do_callback();
+            if view.changed() {
+                view.clear_changed();
+                view.redraw();
+            }
+            app::repeat_timeout3(frame_seconds, handle);
+        });
+        self.handle.set(Some(frame));
+    }
+
+    fn stop(&self) {
+        if let Some(frame) = self.handle.take() {
+            app::remove_timeout3(frame);
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        self.handle.get().is_some()
+    }
+}
+
+// Keeps the window's loop to a round per frame at the target rate. It measures how long the round took
+// and sleeps only what's left of the frame, so the rounds stay evenly spaced however long each one takes
+struct FramePacer {
+    frame: Duration,
+    next: Instant,
+}
+
+impl FramePacer {
+    fn new(fps: u32) -> Self {
+        FramePacer { frame: Duration::from_secs_f64(1.0 / fps as f64), next: Instant::now() }
+    }
+
+    fn wait(&mut self) {
+        let now = Instant::now();
+        // a round that ran past its frame starts the count over, rather than the next ones rushing to catch up
+        self.next = (self.next + self.frame).max(now);
+        thread::sleep(self.next - now);
+    }
+}
+
+// Text laid out once into an offscreen buffer and copied from there every frame for as long as it
+// reads the same
+struct CachedText {
+    font: Font,
+    size: i32,
+    color: Color,
+    w: i32,
+    h: i32,
+    drawn: Option<(String, draw::Offscreen)>,
+}
+
+impl CachedText {
+    fn new(font: Font, size: i32, color: Color, w: i32, h: i32) -> Self {
+        CachedText { font, size, color, w, h, drawn: None }
+    }
+
+    // left aligned in the text's box, on the background of a game's view
+    fn draw(&mut self, text: &str, x: i32, y: i32) {
+        if self.drawn.as_ref().is_none_or(|(drawn, _)| drawn != text) {
+            let Some(offscreen) = draw::Offscreen::new(self.w, self.h) else {
+                draw::set_font(self.font, self.size);
+                draw::set_draw_color(self.color);
+                draw::draw_text2(text, x, y, self.w, self.h, Align::Left);
+                return;
+            };
+            offscreen.begin();
+            draw::draw_rect_fill(0, 0, self.w, self.h, background());
+            draw::set_font(self.font, self.size);
+            draw::set_draw_color(self.color);
+            draw::draw_text2(text, 0, 0, self.w, self.h, Align::Left);
+            offscreen.end();
+            self.drawn = Some((text.to_string(), offscreen));
+        }
+        let (_, offscreen) = self.drawn.as_ref().unwrap();
+        offscreen.copy(x, y, self.w, self.h, 0, 0);
+    }
+}
+
+// The game page's labels that stay the same most frames
+struct Hud {
+    score: CachedText,
+    mode: CachedText,
+    hint_key: CachedText,
+    undo_key: CachedText,
+}
+
+impl Hud {
+    fn new() -> Self {
+        Hud {
+            score: CachedText::new(Font::Courier, 44, Color::Red, 10 * CELL_SIZE, 50),
+            mode: CachedText::new(Font::Courier, 24, Color::Red, 6 * CELL_SIZE, 30),
+            hint_key: CachedText::new(Font::Courier, 24, Color::Red, 6 * CELL_SIZE, 30),
+            undo_key: CachedText::new(Font::Courier, 24, Color::Red, 6 * CELL_SIZE, 30),
+        }
+    }
+}
+
+struct Canvas {
+    x: i32,
+    y: i32,
+    cell: i32,
+}
+
+// The field sits on the left with the panels right of it, or the other way round when left-handed.
+// The stream layout puts it in the middle whichever hand it's for
+fn game_canvas<const W: usize>() -> Canvas {
+    let x = if STREAM_LAYOUT.load(Ordering::Relaxed) {
+        (STREAM_W - W as i32 * CELL_SIZE) / 2
+    } else if LEFT_HANDED.load(Ordering::Relaxed) {
+        WINDOW_W - W as i32 * CELL_SIZE - CANVAS_X
+    } else {
+        CANVAS_X
+    };
+    Canvas { x, y: CANVAS_Y, cell: CELL_SIZE }
+}
+
+// Where next and the rest of the panels start
+fn panel_x<const W: usize>() -> i32 {
+    if STREAM_LAYOUT.load(Ordering::Relaxed) {
+        game_canvas::<W>().x + W as i32 * CELL_SIZE + 10
+    } else if LEFT_HANDED.load(Ordering::Relaxed) {
+        10
+    } else {
+        W as i32 * CELL_SIZE + 10
+    }
+}
+
+// Where hold, score and the stats start, which the stream layout moves to the other side of the field
+fn side_panel_x<const W: usize>() -> i32 {
+    if STREAM_LAYOUT.load(Ordering::Relaxed) {game_canvas::<W>().x - STREAM_SIDE_PANEL_W} else {panel_x::<W>()}
+}
+
+fn window_w() -> i32 {
+    if STREAM_LAYOUT.load(Ordering::Relaxed) {STREAM_W} else {WINDOW_W}
+}
+
+// Behind everything but the fields, which stay as they are
+fn background() -> Color {
+    let theme = if high_contrast() {Color::Black} else {Color::Background};
+    CHROMA_KEY.read().unwrap().color().unwrap_or(theme)
+}
+
+fn high_contrast() -> bool {
+    *THEME.read().unwrap() == Theme::HighContrast
+}
+
+// What a block of `piece` is drawn in. The high contrast theme gives every kind of piece a color of its
+// own rather than the one it was dealt
+fn piece_color(color: Color, piece: Option<Tetromino>) -> Color {
+    if !high_contrast() {
+        return color;
+    }
+    match piece {
+        Some(Tetromino::I) => Color::from_rgb(0, 255, 255),
+        Some(Tetromino::O) => Color::from_rgb(255, 255, 0),
+        Some(Tetromino::T) => Color::from_rgb(255, 0, 255),
+        Some(Tetromino::S) => Color::from_rgb(0, 255, 0),
+        Some(Tetromino::Z) => Color::from_rgb(255, 0, 0),
+        Some(Tetromino::J) => Color::from_rgb(40, 100, 255),
+        Some(Tetromino::L) => Color::from_rgb(255, 140, 0),
+        Some(Tetromino::Pentomino(_) | Tetromino::Custom(..)) => Color::White,
+        // garbage and the blocks a game was set up with
+        None => color,
+    }
 }
+const GHOST_COLOR: Color = Color::from_rgb(90, 90, 110);
+// the handling page's board, small enough to fit under its sliders
+const TEST_CELL_SIZE: i32 = 22;
+const BREAKDOWN_LINE_H: i32 = 26;
+const CLEAR_PREVIEW_COLOR: Color = Color::from_rgb(30, 34, 52);
 
 fn main() {
+    let mut args = std::env::args().skip(1);
+    // a board written out by Tetris::to_ascii, e.g. from a bug report, to play on right away
+    let mut board = None;
+    let mut export_file = None;
+    while let Some(arg) = args.next() {
+        if arg == "--bench-bot" {
+            let games = args.next().and_then(|games| games.parse().ok()).expect("--bench-bot needs the number of games to play");
+            bench::run::<10, 20>(games);
+            return;
+        }
+        if arg == "--verify" {
+            let file = args.next().expect("--verify needs the replay to check");
+            let replay = Replay::load(&file).unwrap_or_else(|err| panic!("can't read {file}: {err}"));
+            match replay.verify(&RuleSet::load()) {
+                Ok(()) => println!("{file}: {mode}, score {score}, plays out the same", mode = replay.mode.key(), score = replay.score),
+                Err(err) => {
+                    eprintln!("{file}: {err}");
+                    std::process::exit(1);
+                },
+            }
+            return;
+        }
+        if arg == "--export" {
+            export_file = Some(args.next().expect("--export needs the file to write the results to"));
+        }
+        if arg == "--board" {
+            let file = args.next().expect("--board needs the file with the board");
+            let ascii = std::fs::read_to_string(&file).unwrap_or_else(|err| panic!("can't read {file}: {err}"));
+            board = Some(Tetris::<10, 20>::from_ascii(&ascii, RuleSet::default()).unwrap_or_else(|err| panic!("{file}: {err}")));
+        }
+    }
+
     let app = App::default();
-    let mut wind = Window::new(100, 100, WINDOW_W, WINDOW_H, "Tetris!");
+    let config = Rc::new(RefCell::new(Config::load()));
+    locale::set_language(&config.borrow().language);
+    if let Some(file) = export_file.or_else(|| config.borrow().export_file.clone()) {
+        let _ = export::EXPORT_FILE.set(file);
+    }
+    if let Some(app_id) = config.borrow().discord_app_id.as_deref() {
+        presence::start(app_id);
+    }
+    if let Some(port) = config.borrow().bot_port {
+        external::listen(port);
+    }
+    apply_zoom(config.borrow().cell_size);
+    // the window's size follows from it, so it has to be known before the window is made
+    STREAM_LAYOUT.store(config.borrow().stream_layout, Ordering::Relaxed);
+    *CHROMA_KEY.write().unwrap() = config.borrow().chroma_key;
+    *THEME.write().unwrap() = config.borrow().theme;
+    *access::ACCESSIBILITY.write().unwrap() = config.borrow().accessibility;
+    // a position left on a screen that's no longer plugged in would put the window out of sight
+    let (x, y) = config.borrow().window_position
+        .filter(|(x, y)| (0..app::screen_count()).map(app::screen_xywh).any(|(sx, sy, sw, sh)| (sx..sx + sw).contains(x) && (sy..sy + sh).contains(y)))
+        .unwrap_or((100, 100));
+    let mut wind = Window::new(x, y, window_w(), WINDOW_H, tr("window.title"));
+    wind.set_color(background());
     let mut pack = Pack::new(0, 0, wind.width(), wind.height(), "");
     let (sender, receiver) = app::channel::<Page>();
+    let match_settings = Rc::new(RefCell::new(MatchSettings {
+        opponents: 1,
+        targeting: Targeting::Random,
+        difficulty: Difficulty::Normal,
+        items: false,
+        your_handicap: Handicap::None,
+        cpu_handicap: Handicap::None,
+    }));
+    let idle_timer = IdleTimer::default();
+    let board_setup = Rc::new(RefCell::new(BoardSetup::<10, 20>::new()));
+    let puzzle_progress = Rc::new(RefCell::new(PuzzleProgress::load()));
+    let rules = RuleSet::load();
+    let audio = Arc::new(Audio::new(config.borrow().volume));
+    audio.set_sound_pack(config.borrow().sound_pack.as_deref());
 
-    setup_menu(&mut wind, &mut pack, sender);
-
+    let game_timer = GameTimer::new(config.borrow().render_fps);
+    let mut pacer = FramePacer::new(config.borrow().render_fps);
+    match board {
+        Some(board) => game_timer.start(setup_game(board, Mode::Custom, &mut wind, &mut pack, sender, puzzle_progress.clone(), audio.clone())),
+        None => setup_menu(&mut wind, &mut pack, sender, match_settings.clone(), puzzle_progress.clone(), idle_timer.clone()),
+    }
     wind.end();
     wind.show();
+    wind.fullscreen(config.borrow().fullscreen);
+    COMPACT_LAYOUT.store(config.borrow().compact_layout, Ordering::Relaxed);
+    LEFT_HANDED.store(config.borrow().left_handed, Ordering::Relaxed);
+    CLEAR_PREVIEW.store(config.borrow().clear_preview, Ordering::Relaxed);
+    REDUCE_MOTION.store(config.borrow().reduce_motion, Ordering::Relaxed);
+    AUTO_PAUSE.store(config.borrow().auto_pause, Ordering::Relaxed);
+    app::add_handler(toggle_fullscreen);
+    audio.play_music(Some(Track::Menu));
 
     while app.wait() {
-        match receiver.recv() {
-            Some(Page::Game) => {
-                setup_game(Tetris::<10, 20>::new(), &mut wind, &mut pack, sender);
-                app::sleep(0.016);
+        let page = receiver.recv();
+        if page.is_some() {
+            idle_timer.cancel();
+        }
+
+        if let Some(page) = page {
+            let track = match page {
+                Page::Game(_) | Page::Versus(_) => Track::Game,
+                Page::Menu | Page::Editor | Page::Demo | Page::Settings | Page::Handling | Page::HighScores => Track::Menu,
+            };
+            audio.play_music(Some(track));
+            audio.pause_music(false);
+            game_timer.stop();
+            // a game puts its own activity back up once it's drawn
+            presence::show(None);
+            awake::keep_awake(false);
+            // only a game asks before the window closes
+            wind.set_callback(|wind| wind.hide());
+        }
+
+        match page {
+            // bigger pieces need a wider field, so this one can't share the board type with the other modes
+            Some(Page::Game(Mode::Pentomino)) => {
+                let tetris = Tetris::<PENTOMINO_FIELD_W, 20>::with_seed(RuleSet::pentomino(), game_seed());
+                game_timer.start(setup_game(tetris, Mode::Pentomino, &mut wind, &mut pack, sender, puzzle_progress.clone(), audio.clone()));
+            },
+            Some(Page::Game(mode)) => {
+                let seed = game_seed();
+                let tetris = match mode {
+                    Mode::Trainer(idx) => Tetris::<10, 20>::with_queue(OPENINGS[idx].queue()),
+                    Mode::Custom => board_setup.borrow().to_tetris(RuleSet::default()),
+                    Mode::Sandbox => board_setup.borrow().to_tetris(RuleSet::sandbox()),
+                    Mode::Puzzle(idx) => PUZZLES[idx].to_tetris(),
+                    Mode::Marathon | Mode::Sprint | Mode::DigRace | Mode::Daily => mode.ranked_game(&rules, seed, daily::today()).unwrap(),
+                    Mode::TwitchPlays => Tetris::<10, 20>::with_seed(rules.clone(), seed),
+                    // practice is for trying things out, so the held piece can go back and forth
+                    Mode::Practice => Tetris::<10, 20>::with_seed(RuleSet { hold: Hold::Unlimited, ..rules.clone() }, seed),
+                    Mode::Mirror => Tetris::<10, 20>::with_seed(RuleSet { mirror: true, ..rules.clone() }, seed),
+                    Mode::Classic => Tetris::<10, 20>::with_seed(RuleSet::classic(), seed),
+                    Mode::Master => Tetris::<10, 20>::with_seed(RuleSet::master(), seed),
+                    Mode::Guideline => Tetris::<10, 20>::with_seed(RuleSet::guideline(), seed),
+                    Mode::Survival => Tetris::<10, 20>::with_seed(RuleSet::survival(), seed),
+                    Mode::PieceSet(idx) => Tetris::<10, 20>::with_seed(RuleSet { pieces: PieceSet::Custom(idx), ..rules.clone() }, seed),
+                    // has a page arm of its own above
+                    Mode::Pentomino => unreachable!(),
+                };
+                game_timer.start(setup_game(tetris, mode, &mut wind, &mut pack, sender, puzzle_progress.clone(), audio.clone()));
+            },
+            Some(Page::Versus(settings)) => {
+                let rules = if settings.items {RuleSet::party()} else {RuleSet::default()};
+                let mut players = vec![Player::human(rules.clone(), settings.your_handicap)];
+                // bots connected to the bot socket take the first seats
+                let mut externals = external::available(settings.opponents).into_iter();
+                players.extend((0..settings.opponents).map(|_| {
+                    let bot = externals.next().map_or(Bot::new(settings.difficulty), |external| Bot::external(settings.difficulty, external));
+                    Player::cpu(rules.clone(), bot, settings.cpu_handicap)
+                }));
+                game_timer.start(setup_versus(Match::<10, 20>::new(players, settings.targeting), &mut wind, &mut pack, sender, audio.clone()));
+            },
+            Some(Page::Demo) => {
+                game_timer.start(setup_demo::<10, 20>(&mut wind, &mut pack, sender));
+            },
+            Some(Page::Editor) => {
+                setup_editor(board_setup.clone(), &mut wind, &mut pack, sender);
+            },
+            Some(Page::Settings) => {
+                setup_settings(config.clone(), audio.clone(), &mut wind, &mut pack, sender);
+            },
+            Some(Page::Handling) => {
+                game_timer.start(setup_handling::<10, 20>(config.clone(), &mut wind, &mut pack, sender));
+            },
+            Some(Page::HighScores) => {
+                setup_high_scores(config.borrow().leaderboard_url.as_deref(), &mut wind, &mut pack, sender);
             },
             Some(Page::Menu) => {
-                setup_menu(&mut wind, &mut pack, sender);
-                app::sleep(0.016);
+                setup_menu(&mut wind, &mut pack, sender, match_settings.clone(), puzzle_progress.clone(), idle_timer.clone());
             }
             _ => ()
         }
-        wind.redraw();
+        // a game's timer redraws its view, the rest of the window only needs it when the page changes
+        if page.is_some() || !game_timer.is_running() {
+            wind.redraw();
+        }
+        pacer.wait();
     }
+
+    // fullscreen takes the whole screen, the spot it was in before is the one worth coming back to
+    let mut config = config.borrow_mut();
+    config.fullscreen = wind.fullscreen_active();
+    config.compact_layout = COMPACT_LAYOUT.load(Ordering::Relaxed);
+    config.left_handed = LEFT_HANDED.load(Ordering::Relaxed);
+    config.stream_layout = STREAM_LAYOUT.load(Ordering::Relaxed);
+    if !config.fullscreen {
+        config.window_position = Some((wind.x(), wind.y()));
+    }
+    config.save();
 }
 
-fn setup_menu(wind: &mut DoubleWindow, pack: &mut Pack, sender: Sender<Page>) {
+fn setup_menu(wind: &mut DoubleWindow, pack: &mut Pack, sender: Sender<Page>, match_settings: Rc<RefCell<MatchSettings>>, puzzle_progress: Rc<RefCell<PuzzleProgress>>, idle_timer: IdleTimer) {
     pack.clear();
     pack.begin();
 
-    Button::new(100, 100, 200, 40, "Start!").emit(sender, Page::Game);
+    // two columns, single player games on the left, everything else on the right, so it all fits the window
+    let mut columns = Pack::new(0, 0, WINDOW_W, WINDOW_H, "");
+    columns.set_type(PackType::Horizontal);
+    let left = Pack::new(0, 0, WINDOW_W / 2, WINDOW_H, "");
+    Button::new(100, 100, 200, 40, tr("menu.start")).emit(sender, Page::Game(Mode::Marathon));
+    Button::new(100, 140, 200, 40, Mode::Classic.name()).emit(sender, Page::Game(Mode::Classic));
+    Button::new(100, 140, 200, 40, Mode::Master.name()).emit(sender, Page::Game(Mode::Master));
+    Button::new(100, 140, 200, 40, Mode::Guideline.name()).emit(sender, Page::Game(Mode::Guideline));
+    Button::new(100, 140, 200, 40, Mode::Pentomino.name()).emit(sender, Page::Game(Mode::Pentomino));
+    Button::new(100, 140, 200, 40, Mode::Survival.name()).emit(sender, Page::Game(Mode::Survival));
+    Button::new(100, 140, 200, 40, Mode::Sprint.name()).emit(sender, Page::Game(Mode::Sprint));
+    Button::new(100, 140, 200, 40, Mode::DigRace.name()).emit(sender, Page::Game(Mode::DigRace));
+    Button::new(100, 140, 200, 40, Mode::Daily.name()).emit(sender, Page::Game(Mode::Daily));
+
+    for (idx, set) in PIECE_SETS.iter().enumerate() {
+        let mut button = Button::new(100, 140, 200, 40, "");
+        button.set_label(&tr_with("menu.pieces", &[("name", &set.name)]));
+        button.emit(sender, Page::Game(Mode::PieceSet(idx)));
+    }
+    Button::new(100, 140, 200, 40, Mode::Practice.name()).emit(sender, Page::Game(Mode::Practice));
+    Button::new(100, 140, 200, 40, Mode::Mirror.name()).emit(sender, Page::Game(Mode::Mirror));
+
+    let seed_label = |seed: Option<u64>| tr_with("menu.seed", &[("seed", &seed.map_or(tr("menu.seed_random").to_string(), |seed| seed.to_string()))]);
+    let mut seed = Button::new(100, 140, 200, 40, "");
+    seed.set_label(&seed_label(*CHOSEN_SEED.read().unwrap()));
+    seed.set_callback(move |button| {
+        let current = CHOSEN_SEED.read().unwrap().map(|seed| seed.to_string()).unwrap_or_default();
+        // cancelled leaves it as it was, and nothing typed goes back to a random one
+        let Some(typed) = dialog::input_default(tr("menu.seed_question"), &current) else {
+            return;
+        };
+        let typed = typed.trim();
+        let chosen = match typed.parse::<u64>() {
+            Ok(seed) => Some(seed),
+            Err(_) if typed.is_empty() => None,
+            Err(_) => {
+                dialog::alert_default(tr("menu.seed_invalid"));
+                return;
+            },
+        };
+        *CHOSEN_SEED.write().unwrap() = chosen;
+        button.set_label(&seed_label(chosen));
+    });
+
+    for (idx, opening) in OPENINGS.iter().enumerate() {
+        let mut button = Button::new(100, 180 + idx as i32 * 40, 200, 40, "");
+        button.set_label(&tr_with("menu.trainer", &[("name", &opening.name)]));
+        button.emit(sender, Page::Game(Mode::Trainer(idx)));
+    }
+    left.end();
+
+    let right = Pack::new(0, 0, WINDOW_W / 2, WINDOW_H, "");
+    Button::new(100, 180, 200, 40, tr("menu.editor")).emit(sender, Page::Editor);
+    Button::new(100, 180, 200, 40, Mode::Sandbox.name()).emit(sender, Page::Game(Mode::Sandbox));
+    // only there once a channel is set in config.toml
+    if Config::load().twitch_channel.is_some() {
+        Button::new(100, 180, 200, 40, Mode::TwitchPlays.name()).emit(sender, Page::Game(Mode::TwitchPlays));
+    }
+
+    for (idx, puzzle) in PUZZLES.iter().enumerate() {
+        let label = if puzzle_progress.borrow().is_solved(puzzle) {"menu.puzzle_solved"} else {"menu.puzzle"};
+        let mut button = Button::new(100, 180, 200, 40, "");
+        button.set_label(&tr_with(label, &[("n", &(idx + 1)), ("name", &puzzle.name)]));
+        button.emit(sender, Page::Game(Mode::Puzzle(idx)));
+    }
+
+    Button::new(100, 180, 200, 40, tr("menu.high_scores")).emit(sender, Page::HighScores);
+    Button::new(100, 180, 200, 40, tr("menu.settings")).emit(sender, Page::Settings);
+
+    let settings = match_settings.clone();
+    Button::new(100, 180, 200, 40, tr("menu.versus")).set_callback(move |_| sender.send(Page::Versus(*settings.borrow())));
+
+    let settings = match_settings.clone();
+    let mut opponents = Button::new(100, 220, 200, 40, "");
+    opponents.set_label(&tr_with("menu.opponents", &[("n", &settings.borrow().opponents)]));
+    opponents.set_callback(move |button| {
+        let mut settings = settings.borrow_mut();
+        settings.opponents = settings.opponents % MAX_CPU_OPPONENTS + 1;
+        button.set_label(&tr_with("menu.opponents", &[("n", &settings.opponents)]));
+    });
+
+    let settings = match_settings.clone();
+    let mut targeting = Button::new(100, 260, 200, 40, "");
+    targeting.set_label(&tr_with("menu.targeting", &[("name", &settings.borrow().targeting.name())]));
+    targeting.set_callback(move |button| {
+        let mut settings = settings.borrow_mut();
+        settings.targeting = settings.targeting.next();
+        button.set_label(&tr_with("menu.targeting", &[("name", &settings.targeting.name())]));
+    });
+
+    let settings = match_settings.clone();
+    let mut difficulty = Button::new(100, 300, 200, 40, "");
+    difficulty.set_label(&tr_with("menu.difficulty", &[("name", &settings.borrow().difficulty.name())]));
+    difficulty.set_callback(move |button| {
+        let mut settings = settings.borrow_mut();
+        settings.difficulty = settings.difficulty.next();
+        button.set_label(&tr_with("menu.difficulty", &[("name", &settings.difficulty.name())]));
+    });
+
+    let settings = match_settings.clone();
+    let items_label = |items: bool| tr_with("menu.items", &[("state", &on_off(items))]);
+    let mut items = Button::new(100, 340, 200, 40, "");
+    items.set_label(&items_label(settings.borrow().items));
+    items.set_callback(move |button| {
+        let mut settings = settings.borrow_mut();
+        settings.items = !settings.items;
+        button.set_label(&items_label(settings.items));
+    });
+
+    let settings = match_settings.clone();
+    let mut your_handicap = Button::new(100, 380, 200, 40, "");
+    your_handicap.set_label(&tr_with("menu.your_handicap", &[("name", &settings.borrow().your_handicap.name())]));
+    your_handicap.set_callback(move |button| {
+        let mut settings = settings.borrow_mut();
+        settings.your_handicap = settings.your_handicap.next();
+        button.set_label(&tr_with("menu.your_handicap", &[("name", &settings.your_handicap.name())]));
+    });
+
+    let settings = match_settings.clone();
+    let mut cpu_handicap = Button::new(100, 420, 200, 40, "");
+    cpu_handicap.set_label(&tr_with("menu.cpu_handicap", &[("name", &settings.borrow().cpu_handicap.name())]));
+    cpu_handicap.set_callback(move |button| {
+        let mut settings = settings.borrow_mut();
+        settings.cpu_handicap = settings.cpu_handicap.next();
+        button.set_label(&tr_with("menu.cpu_handicap", &[("name", &settings.cpu_handicap.name())]));
+    });
+    right.end();
+    columns.end();
 
     pack.end();
     pack.show();
     pack.draw(|_|{});
     pack.redraw();
-    wind.handle(|_, _| false);
+
+    let buttons = |column: &Pack| (0..column.children()).filter_map(|idx| column.child(idx)).map(Focusable::Button).collect();
+    let mut focus = MenuFocus::new(vec![buttons(&left), buttons(&right)]);
+
+    idle_timer.restart(sender);
+    wind.handle(move |_, ev| {
+        if matches!(ev, Event::Move | Event::Push | Event::KeyDown | Event::MouseWheel) {
+            idle_timer.restart(sender);
+        }
+        ev == Event::KeyDown && focus.receive_key(app::event_key())
+    });
 }
 
-fn draw_cell(x: usize, y: usize, color: Color){
-    draw::set_draw_color(color);
-    draw::draw_rectf(x as i32 * CELL_SIZE + CANVAS_X, y as i32 * CELL_SIZE + CANVAS_Y, CELL_SIZE, CELL_SIZE);
-    draw::set_draw_color(Color::from_rgb(148, 151, 192));
-    draw::draw_rectf(x as i32 * CELL_SIZE + CANVAS_X, y as i32 * CELL_SIZE + CANVAS_Y, 3, CELL_SIZE-3);
-    draw::draw_rectf(x as i32 * CELL_SIZE + CANVAS_X, y as i32 * CELL_SIZE + CANVAS_Y, CELL_SIZE-3, 3);
+fn volume_slider(label: &str, part: fn(&mut Volume) -> &mut f32, config: Rc<RefCell<Config>>, audio: Arc<Audio>) -> HorNiceSlider {
+    let mut frame = Frame::new(0, 0, WINDOW_W, 40, "");
+    frame.set_label(label);
+    frame.set_align(Align::Left | Align::Inside);
+
+    let mut slider = HorNiceSlider::new(0, 0, WINDOW_W, 30, "");
+    slider.set_range(0.0, 1.0);
+    slider.set_step(0.05, 1);
+    slider.set_value(*part(&mut config.borrow_mut().volume) as f64);
+
+    // applied right away so the change can be heard while dragging
+    slider.set_callback(move |slider| {
+        let mut config = config.borrow_mut();
+        *part(&mut config.volume) = slider.value() as f32;
+        audio.set_volume(config.volume);
+    });
+    slider
 }
 
+// Toggled from the game as well as the settings, so they're kept here and written to the config whenever that's saved
+static COMPACT_LAYOUT: AtomicBool = AtomicBool::new(false);
+static LEFT_HANDED: AtomicBool = AtomicBool::new(false);
+static STREAM_LAYOUT: AtomicBool = AtomicBool::new(false);
+static CHROMA_KEY: std::sync::RwLock<ChromaKey> = std::sync::RwLock::new(ChromaKey::Off);
+static THEME: std::sync::RwLock<Theme> = std::sync::RwLock::new(Theme::Classic);
+// an assist, drawn on every field including the versus ones
+static CLEAR_PREVIEW: AtomicBool = AtomicBool::new(false);
+static REDUCE_MOTION: AtomicBool = AtomicBool::new(false);
+static AUTO_PAUSE: AtomicBool = AtomicBool::new(true);
+// typed in on the menu, every game started from then on gets it until it's cleared again
+static CHOSEN_SEED: std::sync::RwLock<Option<u64>> = std::sync::RwLock::new(None);
 
-fn draw_game<const W: usize, const H: usize>(tetris: Rc<RefCell<Tetris<W, H>>>) {
+fn on_off(on: bool) -> &'static str {
+    if on {tr("on")} else {tr("off")}
+}
 
-    {
-        let tetris_mut = tetris.borrow_mut();
+fn game_seed() -> u64 {
+    CHOSEN_SEED.read().unwrap().unwrap_or_else(rand::random)
+}
 
-        tetris_mut.falling.iter().for_each(
-            |p| draw_cell(p.x, p.y, tetris_mut.falling.color)
-        );
+// F11 works on every page, so it's handled once here rather than by each page's window handler
+fn toggle_fullscreen(ev: Event) -> bool {
+    if ev != Event::Shortcut || app::event_key() != Key::F11 {
+        return false;
+    }
+    if let Some(mut wind) = app::first_window() {
+        wind.fullscreen(!wind.fullscreen_active());
+    }
+    true
+}
 
-        let next_shape_display_loc = Point::new(W + 3, 3);
-        ShapeIter::new(&tetris_mut.next.shape, &next_shape_display_loc).for_each(
-            |p| draw_cell(p.x, p.y, tetris_mut.next.color)
-        );
+// FLTK scales every widget and everything drawn, so the layout stays the same at any size.
+// The scale the desktop asks for is kept on top of it, so the game doesn't shrink on high DPI screens.
+static SYSTEM_SCALES: std::sync::LazyLock<Vec<f32>> = std::sync::LazyLock::new(|| {
+    (0..app::screen_count()).map(app::screen_scale).collect()
+});
 
-        for x in 0..W {
-            for y in 0..H {
-                match tetris_mut.field[y][x] {
-                    Some(color) => draw_cell(x, y, color),
-                    _ => {}
-                }
-            }
+fn apply_zoom(cell_size: i32) {
+    if !app::screen_scaling_supported() {
+        return;
+    }
+    for (screen, scale) in SYSTEM_SCALES.iter().enumerate() {
+        app::set_screen_scale(screen as i32, scale * cell_size as f32 / CELL_SIZE as f32);
+    }
+}
+
+fn setup_settings(config: Rc<RefCell<Config>>, audio: Arc<Audio>, wind: &mut DoubleWindow, pack: &mut Pack, sender: Sender<Page>) {
+    pack.clear();
+    pack.begin();
+
+    let saved = config.clone();
+    let mut back = Button::new(0, 0, 50, 40, tr("back"));
+    back.set_callback(move |_| {
+        let mut config = saved.borrow_mut();
+        config.compact_layout = COMPACT_LAYOUT.load(Ordering::Relaxed);
+        config.left_handed = LEFT_HANDED.load(Ordering::Relaxed);
+        config.stream_layout = STREAM_LAYOUT.load(Ordering::Relaxed);
+        config.clear_preview = CLEAR_PREVIEW.load(Ordering::Relaxed);
+        config.reduce_motion = REDUCE_MOTION.load(Ordering::Relaxed);
+        config.auto_pause = AUTO_PAUSE.load(Ordering::Relaxed);
+        config.save();
+        sender.send(Page::Menu);
+    });
+
+    // how the game looks and sounds on the left, how it plays on the right
+    let mut columns = Pack::new(0, 0, WINDOW_W, WINDOW_H - 40, "");
+    columns.set_type(PackType::Horizontal);
+    let left = Pack::new(0, 0, WINDOW_W / 2, WINDOW_H - 40, "");
+
+    let master = volume_slider(tr("settings.master_volume"), |volume| &mut volume.master, config.clone(), audio.clone());
+    let music = volume_slider(tr("settings.music_volume"), |volume| &mut volume.music, config.clone(), audio.clone());
+    let effects = volume_slider(tr("settings.effects_volume"), |volume| &mut volume.effects, config.clone(), audio.clone());
+
+    let zoom_label = |cell_size: i32| tr_with("settings.zoom", &[("cell_size", &cell_size)]);
+    let mut zoom_frame = Frame::new(0, 0, WINDOW_W, 40, "");
+    zoom_frame.set_label(&zoom_label(config.borrow().cell_size));
+    zoom_frame.set_align(Align::Left | Align::Inside);
+    let mut zoom = HorNiceSlider::new(0, 0, WINDOW_W, 30, "");
+    zoom.set_range(MIN_CELL_SIZE as f64, MAX_CELL_SIZE as f64);
+    zoom.set_step(4.0, 1);
+    zoom.set_value(config.borrow().cell_size as f64);
+    // the slider would move away from under the mouse if the window grew while dragging
+    zoom.set_trigger(CallbackTrigger::Release);
+    let zoomed = config.clone();
+    zoom.set_callback(move |slider| {
+        let mut config = zoomed.borrow_mut();
+        config.cell_size = slider.value() as i32;
+        zoom_frame.set_label(&zoom_label(config.cell_size));
+        apply_zoom(config.cell_size);
+    });
+
+    let chosen = config.clone();
+    let keyed = config.clone();
+    let themed = config.clone();
+    let preset = config.clone();
+    let accessible = config.clone();
+    let slowed = config.clone();
+    let pack_label = |pack: Option<&str>| tr_with("settings.sound_pack", &[("name", &pack.unwrap_or(tr("settings.built_in_sounds")))]);
+    let mut sound_pack = Button::new(0, 0, WINDOW_W, 40, "");
+    sound_pack.set_label(&pack_label(config.borrow().sound_pack.as_deref()));
+    sound_pack.set_callback(move |button| {
+        let packs = sound_packs();
+        let mut config = config.borrow_mut();
+        // cycles through the built-in sounds and then every pack on disk
+        let current = config.sound_pack.as_ref().and_then(|current| packs.iter().position(|pack| pack == current));
+        config.sound_pack = match current {
+            None => packs.first().cloned(),
+            Some(idx) => packs.get(idx + 1).cloned(),
+        };
+        audio.set_sound_pack(config.sound_pack.as_deref());
+        button.set_label(&pack_label(config.sound_pack.as_deref()));
+    });
+
+    let mut language = Button::new(0, 0, WINDOW_W, 40, "");
+    language.set_label(&tr_with("settings.language", &[("name", &locale::language().name)]));
+    language.set_callback(move |_| {
+        let next = locale::next_language();
+        locale::set_language(&next.code);
+        chosen.borrow_mut().language = next.code.clone();
+        // built again so every label is in the new language
+        sender.send(Page::Settings);
+    });
+
+    let layout_label = |compact: bool| tr_with("settings.layout", &[("name", &if compact {tr("settings.layout_compact")} else {tr("settings.layout_full")})]);
+    let mut layout = Button::new(0, 0, WINDOW_W, 40, "");
+    layout.set_label(&layout_label(COMPACT_LAYOUT.load(Ordering::Relaxed)));
+    layout.set_callback(move |button| {
+        let compact = !COMPACT_LAYOUT.fetch_xor(true, Ordering::Relaxed);
+        button.set_label(&layout_label(compact));
+    });
+
+    let side_label = |left_handed: bool| tr_with("settings.field_side", &[("side", &if left_handed {tr("settings.side_right")} else {tr("settings.side_left")})]);
+    let mut side = Button::new(0, 0, WINDOW_W, 40, "");
+    side.set_label(&side_label(LEFT_HANDED.load(Ordering::Relaxed)));
+    side.set_callback(move |button| {
+        let left_handed = !LEFT_HANDED.fetch_xor(true, Ordering::Relaxed);
+        button.set_label(&side_label(left_handed));
+    });
+
+    let stream_label = |on: bool| tr_with("settings.stream_layout", &[("state", &on_off(on))]);
+    let mut stream = Button::new(0, 0, WINDOW_W, 40, "");
+    stream.set_label(&stream_label(STREAM_LAYOUT.load(Ordering::Relaxed)));
+    let (mut stream_wind, mut stream_pack) = (wind.clone(), pack.clone());
+    stream.set_callback(move |_| {
+        STREAM_LAYOUT.fetch_xor(true, Ordering::Relaxed);
+        stream_wind.set_size(window_w(), WINDOW_H);
+        stream_pack.set_size(window_w(), WINDOW_H);
+        // built again to fill the window at its new width
+        sender.send(Page::Settings);
+    });
+
+    let chroma_key_label = |chroma_key: ChromaKey| tr_with("settings.chroma_key", &[("name", &match chroma_key {
+        ChromaKey::Off => tr("settings.chroma_key_off"),
+        ChromaKey::Green => tr("settings.chroma_key_green"),
+        ChromaKey::Magenta => tr("settings.chroma_key_magenta"),
+    })]);
+    let mut chroma_key = Button::new(0, 0, WINDOW_W, 40, "");
+    chroma_key.set_label(&chroma_key_label(keyed.borrow().chroma_key));
+    let mut keyed_wind = wind.clone();
+    chroma_key.set_callback(move |button| {
+        let mut config = keyed.borrow_mut();
+        let idx = ChromaKey::ALL.iter().position(|chroma_key| *chroma_key == config.chroma_key).unwrap();
+        config.chroma_key = ChromaKey::ALL[(idx + 1) % ChromaKey::ALL.len()];
+        *CHROMA_KEY.write().unwrap() = config.chroma_key;
+        keyed_wind.set_color(background());
+        keyed_wind.redraw();
+        button.set_label(&chroma_key_label(config.chroma_key));
+    });
+
+    let theme_label = |theme: Theme| tr_with("settings.theme", &[("name", &match theme {
+        Theme::Classic => tr("settings.theme_classic"),
+        Theme::HighContrast => tr("settings.theme_high_contrast"),
+    })]);
+    let mut theme = Button::new(0, 0, WINDOW_W, 40, "");
+    theme.set_label(&theme_label(themed.borrow().theme));
+    let mut themed_wind = wind.clone();
+    theme.set_callback(move |button| {
+        let mut config = themed.borrow_mut();
+        let idx = Theme::ALL.iter().position(|theme| *theme == config.theme).unwrap();
+        config.theme = Theme::ALL[(idx + 1) % Theme::ALL.len()];
+        *THEME.write().unwrap() = config.theme;
+        themed_wind.set_color(background());
+        themed_wind.redraw();
+        button.set_label(&theme_label(config.theme));
+    });
+
+    left.end();
+    let right = Pack::new(0, 0, WINDOW_W / 2, WINDOW_H - 40, "");
+
+    let speed_label = |speed: f64| tr_with("settings.game_speed", &[("percent", &(speed * 100.0).round())]);
+    let mut speed_frame = Frame::new(0, 0, WINDOW_W, 40, "");
+    speed_frame.set_label(&speed_label(slowed.borrow().game_speed));
+    speed_frame.set_align(Align::Left | Align::Inside);
+    let mut speed = HorNiceSlider::new(0, 0, WINDOW_W, 30, "");
+    speed.set_range(MIN_GAME_SPEED, 1.0);
+    speed.set_step(0.05, 1);
+    speed.set_value(slowed.borrow().game_speed);
+    speed.set_callback(move |slider| {
+        let mut config = slowed.borrow_mut();
+        config.game_speed = slider.value();
+        speed_frame.set_label(&speed_label(config.game_speed));
+    });
+
+    let mut handling = Button::new(0, 0, WINDOW_W, 40, tr("settings.handling"));
+    handling.emit(sender, Page::Handling);
+
+    // keys changed one by one in config.toml count as neither preset, and go back to the default
+    let keys_label = |keys: &KeyBindings| tr_with("settings.keys", &[("name", &if *keys == KeyBindings::default() {
+        tr("settings.keys_default")
+    } else if *keys == KeyBindings::one_handed() {
+        tr("settings.keys_one_handed")
+    } else {
+        tr("settings.keys_custom")
+    })]);
+    let mut keys = Button::new(0, 0, WINDOW_W, 40, "");
+    keys.set_label(&keys_label(&preset.borrow().keys));
+    keys.set_callback(move |button| {
+        let mut config = preset.borrow_mut();
+        config.keys = if config.keys == KeyBindings::default() {KeyBindings::one_handed()} else {KeyBindings::default()};
+        button.set_label(&keys_label(&config.keys));
+    });
+
+    let accessibility_label = |accessibility: Accessibility| tr_with("settings.accessibility", &[("name", &match accessibility {
+        Accessibility::Off => tr("settings.accessibility_off"),
+        Accessibility::Speech => tr("settings.accessibility_speech"),
+        Accessibility::Cues => tr("settings.accessibility_cues"),
+    })]);
+    let mut accessibility = Button::new(0, 0, WINDOW_W, 40, "");
+    accessibility.set_label(&accessibility_label(accessible.borrow().accessibility));
+    accessibility.set_callback(move |button| {
+        let mut config = accessible.borrow_mut();
+        let idx = Accessibility::ALL.iter().position(|accessibility| *accessibility == config.accessibility).unwrap();
+        config.accessibility = Accessibility::ALL[(idx + 1) % Accessibility::ALL.len()];
+        *access::ACCESSIBILITY.write().unwrap() = config.accessibility;
+        button.set_label(&accessibility_label(config.accessibility));
+    });
+
+    let clear_preview_label = |on: bool| tr_with("settings.clear_preview", &[("state", &on_off(on))]);
+    let mut clear_preview = Button::new(0, 0, WINDOW_W, 40, "");
+    clear_preview.set_label(&clear_preview_label(CLEAR_PREVIEW.load(Ordering::Relaxed)));
+    clear_preview.set_callback(move |button| {
+        let on = !CLEAR_PREVIEW.fetch_xor(true, Ordering::Relaxed);
+        button.set_label(&clear_preview_label(on));
+    });
+
+    let reduce_motion_label = |on: bool| tr_with("settings.reduce_motion", &[("state", &on_off(on))]);
+    let mut reduce_motion = Button::new(0, 0, WINDOW_W, 40, "");
+    reduce_motion.set_label(&reduce_motion_label(REDUCE_MOTION.load(Ordering::Relaxed)));
+    reduce_motion.set_callback(move |button| {
+        let on = !REDUCE_MOTION.fetch_xor(true, Ordering::Relaxed);
+        button.set_label(&reduce_motion_label(on));
+    });
+
+    let auto_pause_label = |on: bool| tr_with("settings.auto_pause", &[("state", &on_off(on))]);
+    let mut auto_pause = Button::new(0, 0, WINDOW_W, 40, "");
+    auto_pause.set_label(&auto_pause_label(AUTO_PAUSE.load(Ordering::Relaxed)));
+    auto_pause.set_callback(move |button| {
+        let on = !AUTO_PAUSE.fetch_xor(true, Ordering::Relaxed);
+        button.set_label(&auto_pause_label(on));
+    });
+
+    right.end();
+    columns.end();
+
+    pack.end();
+    pack.show();
+    pack.draw(|_|{});
+    pack.redraw();
+
+    let button = |button: &Button| Focusable::Button(Widget::from_dyn_widget(button).unwrap());
+    let mut focus = MenuFocus::new(vec![
+        vec![
+            button(&back),
+            Focusable::Slider(master),
+            Focusable::Slider(music),
+            Focusable::Slider(effects),
+            Focusable::Slider(zoom),
+            button(&sound_pack),
+            button(&layout),
+            button(&side),
+            button(&stream),
+            button(&chroma_key),
+            button(&theme),
+            button(&language),
+        ],
+        vec![
+            Focusable::Slider(speed),
+            button(&handling),
+            button(&keys),
+            button(&accessibility),
+            button(&clear_preview),
+            button(&reduce_motion),
+            button(&auto_pause),
+        ],
+    ]);
+    wind.handle(move |_, ev| {
+        if ev == Event::KeyDown && app::event_key() == Key::Escape {
+            back.do_callback();
+            return true;
         }
+        ev == Event::KeyDown && focus.receive_key(app::event_key())
+    });
+}
+
+// The board on the handling page, to try the handling on. It starts over whenever it tops out
+#[derive(Clone)]
+struct TestBoard<const W: usize, const H: usize> {
+    tetris: Tetris<W, H>,
+    handling: Handling,
+}
+
+impl<const W: usize, const H: usize> TestBoard<W, H> {
+    fn set_handling(&mut self, handling: Handling) {
+        self.handling = handling;
+        self.tetris.set_handling(handling);
     }
+}
 
-    {
-        draw::set_font(Font::Courier, 44);
-        draw::set_draw_color(Color::Red);
-        draw::draw_text2(
-            &*format!("Score: {score}", score = tetris.borrow().score),
-            W as i32 * CELL_SIZE + 10,
-            CANVAS_Y + 6 * CELL_SIZE,
-            10 * CELL_SIZE,
-            50,
-            Align::Left
-        );
+impl<const W: usize, const H: usize> Simulation for TestBoard<W, H> {
+    fn receive_tick(&mut self) {
+        if matches!(self.tetris.state, GameState::LOST) {
+            self.tetris = Tetris::new();
+            self.tetris.set_handling(self.handling);
+            self.tetris.start();
+        }
+        self.tetris.receive_tick();
+        while self.tetris.poll_event().is_some() {}
     }
 
-    if matches!(tetris.borrow().state, GameState::LOST) {
-        draw::set_font(Font::Courier, 50);
-        draw::set_draw_color(Color::Red);
-        draw::draw_text2("Game Over", 0, CANVAS_Y + 50, 10 * CELL_SIZE, 50, Align::Center);
+    fn take_changed(&mut self) -> bool {
+        self.tetris.take_changed()
     }
 }
 
-fn setup_game<const W: usize, const H: usize>(tetris: Tetris<W, H>, wind: &mut DoubleWindow, pack: &mut Pack, sender: Sender<Page>) {
+// A slider for one part of the handling, which the test board takes on while it's dragged so it can be felt
+fn handling_slider<const W: usize, const H: usize>(label: fn(&Handling) -> String, (min, max, step): (f64, f64, f64), get: fn(&Handling) -> f64, set: fn(&mut Handling, f64), config: Rc<RefCell<Config>>, engine: Rc<RefCell<Engine<TestBoard<W, H>>>>) -> HorNiceSlider {
+    let mut frame = Frame::new(0, 0, WINDOW_W, 40, "");
+    frame.set_label(&label(&config.borrow().handling));
+    frame.set_align(Align::Left | Align::Inside);
+
+    let mut slider = HorNiceSlider::new(0, 0, WINDOW_W, 30, "");
+    slider.set_range(min, max);
+    slider.set_step(step, 1);
+    slider.set_value(get(&config.borrow().handling));
+    // the keys are for the test board, not for nudging the slider
+    slider.clear_visible_focus();
+    slider.set_callback(move |slider| {
+        let mut config = config.borrow_mut();
+        set(&mut config.handling, slider.value());
+        frame.set_label(&label(&config.handling));
+        let handling = config.handling;
+        engine.borrow().send(move |test| test.set_handling(handling));
+    });
+    slider
+}
+
+// DAS, ARR and the soft drop factor, over a board to try them on. They're kept along with the rest of
+// the settings, and saved when those are
+fn setup_handling<const W: usize, const H: usize>(config: Rc<RefCell<Config>>, wind: &mut DoubleWindow, pack: &mut Pack, sender: Sender<Page>) -> Widget {
+    let Config { keys, tick_rate, handling, .. } = config.borrow().clone();
+    let mut test = TestBoard { tetris: Tetris::<W, H>::new(), handling };
+    test.set_handling(handling);
+    test.tetris.start();
+    let engine_rc = Rc::new(RefCell::new(Engine::spawn(test, tick_rate)));
+
     pack.clear();
     pack.begin();
 
-    Button::new(0, 0, 50, 40, "Back!").emit(sender, Page::Menu);
+    Button::new(0, 0, 50, 40, tr("back")).emit(sender, Page::Settings);
+    handling_slider(
+        |handling| tr_with("handling.das", &[("das", &handling.das)]), (0.0, MAX_DAS as f64, 10.0),
+        |handling| handling.das as f64, |handling, das| handling.das = das as u128, config.clone(), engine_rc.clone(),
+    );
+    handling_slider(
+        |handling| tr_with("handling.arr", &[("arr", &handling.arr)]), (0.0, MAX_ARR as f64, 5.0),
+        |handling| handling.arr as f64, |handling, arr| handling.arr = arr as u128, config.clone(), engine_rc.clone(),
+    );
+    handling_slider(
+        |handling| tr_with("handling.soft_drop_factor", &[("factor", &handling.soft_drop_factor)]), (1.0, MAX_SOFT_DROP_FACTOR as f64, 1.0),
+        |handling| handling.soft_drop_factor as f64, |handling, factor| handling.soft_drop_factor = factor as u32, config.clone(), engine_rc.clone(),
+    );
+    let tap_only_label = |on: bool| tr_with("handling.tap_only", &[("state", &on_off(on))]);
+    let mut tap_only = Button::new(0, 0, WINDOW_W, 40, "");
+    tap_only.set_label(&tap_only_label(handling.tap_only));
+    tap_only.clear_visible_focus();
+    let config_tap = config.clone();
+    let engine_tap = engine_rc.clone();
+    tap_only.set_callback(move |button| {
+        let mut config = config_tap.borrow_mut();
+        config.handling.tap_only = !config.handling.tap_only;
+        button.set_label(&tap_only_label(config.handling.tap_only));
+        let handling = config.handling;
+        engine_tap.borrow().send(move |test| test.set_handling(handling));
+    });
+    let mut view = Widget::new(0, 0, window_w(), WINDOW_H - 2 * 40 - 3 * 70, "");
 
     pack.end();
     pack.show();
+    pack.draw(|_|{});
+
+    let engine_tick = engine_rc.clone();
+    view.set_callback(move |view| {
+        if engine_tick.borrow_mut().receive_frames(|_| {}) {
+            view.set_changed();
+        }
+    });
 
-    let mut tetris_rc = Rc::new(RefCell::new(tetris));
-    tetris_rc.borrow_mut().start();
+    let engine_draw = engine_rc.clone();
+    view.draw(move |view| {
+        clear_view(view);
+        let canvas = Canvas { x: (window_w() - W as i32 * TEST_CELL_SIZE) / 2, y: view.y() + 10, cell: TEST_CELL_SIZE };
+        draw_board(&engine_draw.borrow().game().tetris, &canvas);
 
-    let tetris_rc1 = tetris_rc.clone();
-    let tetris_rc2 = tetris_rc.clone();
+        draw::set_font(Font::Courier, 20);
+        draw::set_draw_color(Color::Red);
+        draw::draw_text2(tr("handling.try"), 10, canvas.y, canvas.x - 20, 30, Align::Right);
+    });
 
-    pack.draw(move |_| {
-        draw::set_draw_color(Color::Black);
-        draw::draw_rectf(0, CANVAS_Y, W as i32 * CELL_SIZE, H as i32 * CELL_SIZE);
-
-        tetris_rc1.borrow_mut().receive_tick();
-        draw_game(tetris_rc1.clone());
-    });
-
-    wind.handle(
-        move |_, ev| {
-            match ev {
-                Event::Focus => true,
-                Event::KeyUp => {
-                    let key = app::event_key();
-                    if key == KEY_S {
-                        tetris_rc2.borrow_mut().receive_down_release();
-                    }
-                    return true;
-                },
-                Event::KeyDown => {
-                    match app::event_key() {
-                        KEY_A => tetris_rc2.borrow_mut().receive_left(),
-                        KEY_D => tetris_rc2.borrow_mut().receive_right(),
-                        KEY_S => tetris_rc2.borrow_mut().receive_down_press(),
-                        KEY_W => tetris_rc2.borrow_mut().receive_rotate(),
-                        _ => {},
-                    }
-                    true
-                },
-                _ => false,
-            }
+    wind.handle(move |_, ev| {
+        if ev == Event::KeyDown && app::event_key() == Key::Escape {
+            sender.send(Page::Settings);
+            return true;
         }
-    );
+        let engine = engine_rc.borrow();
+        handle_game_input(ev, &keys, |input| engine.send(move |test: &mut TestBoard<W, H>| input.apply(&mut test.tetris)))
+    });
+    view
+}
+
+// Sides of a block that touch another block of the same piece
+#[derive(Clone, Copy, Default)]
+struct Joins {
+    above: bool,
+    left: bool,
+    right: bool,
+    below: bool,
+}
+
+impl Joins {
+    fn around(x: usize, y: usize, joined: impl Fn(usize, usize) -> bool) -> Self {
+        Joins {
+            above: y > 0 && joined(x, y - 1),
+            left: x > 0 && joined(x - 1, y),
+            right: joined(x + 1, y),
+            below: joined(x, y + 1),
+        }
+    }
+}
+
+fn draw_cell(canvas: &Canvas, x: usize, y: usize, color: Color){
+    draw_joined_cell(canvas, x, y, color, Joins::default());
+}
+
+// No border between blocks of one piece, and the borders along the outside run on into the next block
+fn draw_joined_cell(canvas: &Canvas, x: usize, y: usize, color: Color, joins: Joins) {
+    let (cell_x, cell_y) = (x as i32 * canvas.cell + canvas.x, y as i32 * canvas.cell + canvas.y);
+    draw::set_draw_color(color);
+    draw::draw_rectf(cell_x, cell_y, canvas.cell, canvas.cell);
+    draw::set_draw_color(if high_contrast() {Color::Black} else {Color::from_rgb(148, 151, 192)});
+    if !joins.left {
+        draw::draw_rectf(cell_x, cell_y, 3, canvas.cell - if joins.below {0} else {3});
+    }
+    if !joins.above {
+        draw::draw_rectf(cell_x, cell_y, canvas.cell - if joins.right {0} else {3}, 3);
+    }
+}
+
+fn draw_outline(canvas: &Canvas, x: usize, y: usize, color: Color) {
+    draw_thick_outline(canvas, x, y, color, 2);
+}
+
+fn draw_thick_outline(canvas: &Canvas, x: usize, y: usize, color: Color, width: i32) {
+    draw::set_draw_color(color);
+    draw::set_line_style(draw::LineStyle::Solid, width);
+    let inset = width / 2 + 1;
+    draw::draw_rect(x as i32 * canvas.cell + canvas.x + inset, y as i32 * canvas.cell + canvas.y + inset, canvas.cell - 2 * inset, canvas.cell - 2 * inset);
+    draw::set_line_style(draw::LineStyle::Solid, 0);
+}
+
+fn draw_board<const W: usize, const H: usize>(tetris: &Tetris<W, H>, canvas: &Canvas) {
+    draw::set_draw_color(Color::Black);
+    draw::draw_rectf(canvas.x, canvas.y, W as i32 * canvas.cell, H as i32 * canvas.cell);
+
+    if high_contrast() {
+        draw::set_draw_color(Color::White);
+        for x in 0..=W as i32 {
+            draw::draw_line(canvas.x + x * canvas.cell, canvas.y, canvas.x + x * canvas.cell, canvas.y + H as i32 * canvas.cell);
+        }
+        for y in 0..=H as i32 {
+            draw::draw_line(canvas.x, canvas.y + y * canvas.cell, canvas.x + W as i32 * canvas.cell, canvas.y + y * canvas.cell);
+        }
+    }
+
+    if CLEAR_PREVIEW.load(Ordering::Relaxed) {
+        draw::set_draw_color(CLEAR_PREVIEW_COLOR);
+        for y in tetris.projected_clears() {
+            draw::draw_rectf(canvas.x, canvas.y + y as i32 * canvas.cell, W as i32 * canvas.cell, canvas.cell);
+        }
+    }
+
+    if let Some(ghost) = tetris.ghost() {
+        let (color, width) = if high_contrast() {(Color::White, 4)} else {(GHOST_COLOR, 2)};
+        ShapeIter::new(&tetris.falling.shape, &ghost).for_each(
+            |p| draw_thick_outline(canvas, p.x, p.y, color, width)
+        );
+    }
+
+    if !tetris.is_entering() {
+        let mut falling = vec![];
+        tetris.falling.iter().for_each_mut(|p| falling.push(p));
+        // part of the way to the row it falls into next, unless motion is kept down
+        let progress = if REDUCE_MOTION.load(Ordering::Relaxed) {0.0} else {tetris.fall_progress()};
+        let lowered = Canvas { y: canvas.y + (progress * canvas.cell as f64) as i32, ..*canvas };
+        for p in falling.iter().filter(|p| !p.is_above_field()) {
+            let joins = Joins::around(p.x, p.y, |x, y| falling.iter().any(|other| other.x == x && other.y == y));
+            draw_joined_cell(&lowered, p.x, p.y, piece_color(tetris.falling.color, Some(tetris.falling.shape.name())), joins);
+        }
+    }
+
+    for x in 0..W {
+        for y in 0..H {
+            if let Some(cell) = tetris.field[y][x] {
+                let joins = Joins::around(x, y, |x, y| {
+                    x < W && y < H && tetris.field[y][x].is_some_and(|other| other.is_same_piece(&cell))
+                });
+                draw_joined_cell(canvas, x, y, piece_color(cell.color, cell.piece), joins);
+            }
+        }
+    }
+
+    // cleared rows get wiped out from the middle towards the walls, or all at once with reduced motion
+    if let Some((rows, progress)) = tetris.line_clear() {
+        let progress = if REDUCE_MOTION.load(Ordering::Relaxed) {1.0} else {progress};
+        let gone = (progress * W as f64 / 2.0).ceil() as usize;
+        draw::set_draw_color(Color::Black);
+        for y in rows {
+            for x in (W / 2).saturating_sub(gone)..(W / 2 + gone).min(W) {
+                draw::draw_rectf(canvas.x + x as i32 * canvas.cell, canvas.y + *y as i32 * canvas.cell, canvas.cell, canvas.cell);
+            }
+        }
+    }
+}
+
+// Events the page handles get a frame drawn for them, whatever it is they changed
+fn handle_game_page(wind: &mut DoubleWindow, view: &Widget, mut handle: impl FnMut(Event) -> bool + 'static) {
+    let mut view = view.clone();
+    wind.handle(move |_, ev| {
+        let handled = handle(ev);
+        if handled {
+            view.set_changed();
+        }
+        handled
+    });
+}
+
+// A game page's view only ever gets redrawn on its own, so nothing else paints its background
+fn clear_view(view: &Widget) {
+    draw::draw_rect_fill(view.x(), view.y(), view.w(), view.h(), background());
+}
+
+// The compact layout leaves out the score and shows only the next piece
+fn draw_game<const W: usize, const H: usize>(tetris: &Tetris<W, H>, compact: bool, hud: &mut Hud) {
+    draw_board(tetris, &game_canvas::<W>());
+
+    draw_queue(tetris, if compact {1} else {QUEUE_LEN});
+    draw_hold(tetris);
+
+    if compact {
+        draw_game_over(tetris);
+        return;
+    }
+    hud.score.draw(&tr_with("hud.score", &[("score", &tetris.score)]), side_panel_x::<W>(), CANVAS_Y + 6 * CELL_SIZE);
+    draw_performance(tetris);
+    draw_game_over(tetris);
+}
+
+// Next to the held piece, where there's room for it whatever the mode
+fn draw_performance<const W: usize, const H: usize>(tetris: &Tetris<W, H>) {
+    let (x, y) = (side_panel_x::<W>() + QUEUE_BOX_W + 16, CANVAS_Y + 12 * CELL_SIZE - 20);
+    draw::set_font(Font::Courier, 16);
+    draw::set_draw_color(Color::Red);
+    for (idx, line) in Performance::of(tetris).lines().iter().enumerate() {
+        draw::draw_text2(line, x, y + idx as i32 * 20, 4 * CELL_SIZE, 20, Align::Left);
+    }
+}
+
+fn draw_game_over<const W: usize, const H: usize>(tetris: &Tetris<W, H>) {
+    if matches!(tetris.state, GameState::LOST) {
+        draw::set_font(Font::Courier, 50);
+        draw::set_draw_color(Color::Red);
+        let field_x = game_canvas::<W>().x;
+        draw::draw_text2(tr("hud.game_over"), field_x, CANVAS_Y + 50, W as i32 * CELL_SIZE, 50, Align::Center);
+
+        let fault_rate = tetris.finesse_faults as f64 * 100.0 / tetris.pieces.max(1) as f64;
+        draw::set_font(Font::Courier, 24);
+        draw::draw_text2(
+            &tr_with("hud.finesse_faults", &[("faults", &tetris.finesse_faults), ("rate", &format!("{fault_rate:.0}"))]),
+            field_x,
+            CANVAS_Y + 110,
+            W as i32 * CELL_SIZE,
+            30,
+            Align::Center
+        );
+
+        draw::set_font(Font::Courier, 20);
+        for (idx, (name, points)) in tetris.breakdown.parts().iter().enumerate() {
+            let y = CANVAS_Y + 160 + idx as i32 * BREAKDOWN_LINE_H;
+            draw::draw_text2(name, field_x + 40, y, W as i32 * CELL_SIZE - 80, BREAKDOWN_LINE_H, Align::Left);
+            draw::draw_text2(&points.to_string(), field_x + 40, y, W as i32 * CELL_SIZE - 80, BREAKDOWN_LINE_H, Align::Right);
+        }
+        let y = game_over_summary_y(tetris);
+        draw::draw_text2(&Performance::of(tetris).lines().join("  "), field_x, y, W as i32 * CELL_SIZE, BREAKDOWN_LINE_H, Align::Center);
+        // to share, so whoever types it in on the menu gets the same pieces
+        draw::draw_text2(&tr_with("hud.seed", &[("seed", &tetris.seed)]), field_x, y + BREAKDOWN_LINE_H, W as i32 * CELL_SIZE, BREAKDOWN_LINE_H, Align::Center);
+    }
+}
+
+// Where the two lines under the breakdown go, the performance and then the seed
+fn game_over_summary_y<const W: usize, const H: usize>(tetris: &Tetris<W, H>) -> i32 {
+    CANVAS_Y + 170 + tetris.breakdown.parts().len() as i32 * BREAKDOWN_LINE_H
+}
+
+// Draws the piece scaled down and centred in a box, so even wide custom pieces fit
+fn draw_piece_box(piece: &PreparedShape, x: i32, y: i32) {
+    draw::set_draw_color(QUEUE_BOX_COLOR);
+    draw::draw_rect(x, y, QUEUE_BOX_W, QUEUE_BOX_H);
+
+    let mut points = vec![];
+    let origin = Point::new(PREVIEW_SLOT, PREVIEW_SLOT);
+    ShapeIter::new(&piece.shape, &origin).for_each_mut(|p| points.push(p));
+    let (Some(min_x), Some(max_x)) = (points.iter().map(|p| p.x).min(), points.iter().map(|p| p.x).max()) else {
+        return;
+    };
+    let (min_y, max_y) = (points.iter().map(|p| p.y).min().unwrap(), points.iter().map(|p| p.y).max().unwrap());
+    let (w, h) = ((max_x - min_x + 1) as i32, (max_y - min_y + 1) as i32);
+
+    let cell = SMALL_CELL_SIZE.min((QUEUE_BOX_W - 8) / w).min((QUEUE_BOX_H - 8) / h);
+    let canvas = Canvas {
+        x: x + (QUEUE_BOX_W - w * cell) / 2 - min_x as i32 * cell,
+        y: y + (QUEUE_BOX_H - h * cell) / 2 - min_y as i32 * cell,
+        cell,
+    };
+    for p in points.iter() {
+        let joins = Joins::around(p.x, p.y, |x, y| points.iter().any(|other| other.x == x && other.y == y));
+        draw_joined_cell(&canvas, p.x, p.y, piece_color(piece.color, Some(piece.shape.name())), joins);
+    }
+}
+
+// The next pieces top to bottom right of the field, the one coming first on top
+fn draw_queue<const W: usize, const H: usize>(tetris: &Tetris<W, H>, len: usize) {
+    let x = panel_x::<W>();
+    draw::set_font(Font::Courier, 16);
+    for (idx, piece) in tetris.previews().take(len).enumerate() {
+        let y = CANVAS_Y + 8 + idx as i32 * (QUEUE_BOX_H + QUEUE_GAP);
+        let label = if idx == 0 {tr("hud.next").to_string()} else {format!("{n}", n = idx + 1)};
+        draw::set_draw_color(Color::Red);
+        draw::draw_text2(&label, x, y, QUEUE_LABEL_W, QUEUE_BOX_H, Align::Left);
+        draw_piece_box(piece, x + QUEUE_LABEL_W, y);
+    }
+}
+
+// Boxed under the HUD, the same way the queue is
+fn draw_hold<const W: usize, const H: usize>(tetris: &Tetris<W, H>) {
+    let Some(held) = tetris.held.as_ref() else {
+        return;
+    };
+    let (x, y) = (side_panel_x::<W>(), CANVAS_Y + 12 * CELL_SIZE);
+    draw::set_font(Font::Courier, 16);
+    draw::set_draw_color(Color::Red);
+    draw::draw_text2(tr("hud.hold"), x, y - 20, QUEUE_BOX_W, 20, Align::Left);
+    draw_piece_box(held, x, y);
+}
+
+// Over the middle of the field for a moment, so it doesn't matter which layout it's in
+fn draw_chain_popup<const W: usize, const H: usize>(tetris: &Tetris<W, H>, chain: usize, at: Duration) {
+    if tetris.elapsed().saturating_sub(at) > CHAIN_POPUP_TIME {
+        return;
+    }
+    draw::set_font(Font::Courier, 40);
+    draw::set_draw_color(CHAIN_POPUP_COLOR);
+    let field_x = game_canvas::<W>().x;
+    draw::draw_text2(&tr_with("hud.chain", &[("chain", &chain)]), field_x, CANVAS_Y + H as i32 * CELL_SIZE / 2 - 25, W as i32 * CELL_SIZE, 50, Align::Center);
+}
+
+// How long the stack has held out, which is what counts in survival
+fn draw_survival_time<const W: usize, const H: usize>(tetris: &Tetris<W, H>) {
+    let seconds = tetris.elapsed().as_secs();
+    draw::set_font(Font::Courier, 32);
+    draw::set_draw_color(Color::Red);
+    draw::draw_text2(
+        &tr_with("hud.survived", &[("time", &format!("{minutes}:{seconds:02}", minutes = seconds / 60, seconds = seconds % 60))]),
+        panel_x::<W>(),
+        CANVAS_Y + 7 * CELL_SIZE,
+        10 * CELL_SIZE,
+        40,
+        Align::Left
+    );
+}
+
+fn draw_grade<const W: usize, const H: usize>(tetris: &Tetris<W, H>, grading: &Grading) {
+    draw::set_font(Font::Courier, 32);
+    draw::set_draw_color(Color::Red);
+    draw::draw_text2(
+        &tr_with("hud.grade", &[("grade", &grading.name())]),
+        panel_x::<W>(),
+        CANVAS_Y + 7 * CELL_SIZE,
+        10 * CELL_SIZE,
+        40,
+        Align::Left
+    );
+
+    if matches!(tetris.state, GameState::LOST) {
+        let seconds = tetris.elapsed().as_secs();
+        draw::set_font(Font::Courier, 24);
+        draw::draw_text2(
+            &tr_with("hud.grade_in", &[("grade", &grading.name()), ("time", &format!("{minutes}:{seconds:02}", minutes = seconds / 60, seconds = seconds % 60))]),
+            game_canvas::<W>().x,
+            // under the seed, the last line of the game over screen
+            game_over_summary_y(tetris) + 2 * BREAKDOWN_LINE_H,
+            W as i32 * CELL_SIZE,
+            30,
+            Align::Center
+        );
+    }
+}
+
+// What an online room would be sent about the match: every field, and every knockout once
+fn room_messages<const W: usize, const H: usize>(versus: &Match<W, H>, reported_kos: &mut [bool]) -> Vec<Message> {
+    let mut messages = vec![];
+    for (idx, player) in versus.players.iter().enumerate() {
+        messages.push(Message::Field { player: idx as u8, cells: snapshot::compress(&player.tetris) });
+        if !player.is_alive() && !reported_kos[idx] {
+            reported_kos[idx] = true;
+            messages.push(Message::KnockedOut { player: idx as u8, by: player.knocked_out_by().map(|by| by as u8) });
+        }
+    }
+    messages
+}
+
+// Your field in the middle, and everyone else's small on the wall round it, filling the columns
+// left and right of it in turn. Theirs are drawn from the wall's snapshots rather than the match
+fn draw_board_wall<const W: usize, const H: usize>(versus: &Match<W, H>, wall: &BoardWall) {
+    let tetris = &versus.players[0].tetris;
+    let board = Canvas { x: (window_w() - W as i32 * WALL_CELL_SIZE) / 2, y: VERSUS_BOARD_Y, cell: WALL_CELL_SIZE };
+    draw_board(tetris, &board);
+    let preview = Canvas { x: board.x + 3 * board.cell, y: CANVAS_Y, cell: board.cell };
+    ShapeIter::new(&tetris.next.shape, &Point::new(2, 2)).for_each(|p| draw_cell(&preview, p.x, p.y, piece_color(tetris.next.color, Some(tetris.next.shape.name()))));
+    let garbage = tetris.pending_garbage.min(H) as i32 * board.cell;
+    draw::set_draw_color(Color::Red);
+    draw::draw_rectf(board.x - 10, board.y + H as i32 * board.cell - garbage, 6, garbage);
+
+    let knockout = wall.latest_knockout().map(|(player, by)| {
+        let player = &wall.players[player].name;
+        match by {
+            Some(by) => tr_with("versus.knocked_out", &[("by", &wall.players[by].name), ("player", player)]),
+            None => tr_with("versus.topped_out", &[("player", player)]),
+        }
+    });
+    let lines = [
+        tr_with("versus.players_left", &[("left", &wall.players_left()), ("total", &wall.players.len())]),
+        tr_with("versus.kos", &[("kos", &wall.players[0].kos)]),
+        wall.players[0].place.map(|place| tr_with("versus.place", &[("place", &place)])).unwrap_or_default(),
+        knockout.unwrap_or_default(),
+    ];
+    draw::set_font(Font::Courier, 20);
+    draw::set_draw_color(Color::Red);
+    for (line, text) in lines.iter().enumerate() {
+        draw::draw_text2(text, 0, board.y + H as i32 * board.cell + 10 + line as i32 * 25, window_w(), 25, Align::Center);
+    }
+
+    let (mini_w, mini_h) = (W as i32 * MINI_CELL_SIZE, H as i32 * MINI_CELL_SIZE);
+    let columns = ((board.x - 2 * MINI_GAP) / (mini_w + MINI_GAP)).max(1);
+    let right_x = board.x + W as i32 * board.cell + 2 * MINI_GAP;
+    for (slot, player) in wall.players.iter().skip(1).enumerate() {
+        let (side, slot) = (slot as i32 % 2, slot as i32 / 2);
+        let x = if side == 0 {MINI_GAP} else {right_x} + slot % columns * (mini_w + MINI_GAP);
+        let y = CANVAS_Y + slot / columns * (mini_h + MINI_LABEL_H + MINI_GAP);
+
+        draw::set_draw_color(Color::Black);
+        draw::draw_rectf(x, y, mini_w, mini_h);
+        for (idx, block) in player.blocks.iter().enumerate() {
+            let color = match block {
+                Block::Empty => continue,
+                Block::Stack => MINI_STACK_COLOR,
+                Block::Garbage => MINI_GARBAGE_COLOR,
+            };
+            draw::set_draw_color(color);
+            draw::draw_rectf(x + (idx % W) as i32 * MINI_CELL_SIZE, y + (idx / W) as i32 * MINI_CELL_SIZE, MINI_CELL_SIZE, MINI_CELL_SIZE);
+        }
+
+        draw::set_draw_color(Color::Red);
+        draw::set_font(Font::Courier, 10);
+        draw::draw_text2(&player.name, x, y + mini_h, mini_w, MINI_LABEL_H / 2, Align::Left);
+        draw::draw_text2(&tr_with("versus.kos", &[("kos", &player.kos)]), x, y + mini_h + MINI_LABEL_H / 2, mini_w, MINI_LABEL_H / 2, Align::Left);
+        if let Some(place) = player.place {
+            draw::set_font(Font::Courier, 16);
+            draw::draw_text2(&format!("{ko}\n#{place}", ko = tr("versus.ko")), x, y, mini_w, mini_h, Align::Center);
+        }
+    }
+
+    if versus.is_over() {
+        let message = if versus.winner() == Some(0) {tr("versus.win")} else {tr("versus.lose")};
+        draw::set_font(Font::Courier, 50);
+        draw::set_draw_color(Color::Red);
+        draw::draw_text2(message, 0, VERSUS_BOARD_Y + 200, window_w(), 50, Align::Center);
+    }
+}
+
+fn draw_versus<const W: usize, const H: usize>(versus: &Match<W, H>) {
+    let boards = versus.players.len() as i32;
+    let cell = ((window_w() - 2 * VERSUS_MARGIN - (boards - 1) * VERSUS_GAP) / (boards * W as i32)).min(VERSUS_CELL_SIZE);
+    let board_w = W as i32 * cell;
+    let first_x = (window_w() - boards * board_w - (boards - 1) * VERSUS_GAP) / 2;
+
+    for (idx, player) in versus.players.iter().enumerate() {
+        let board = Canvas {
+            x: first_x + idx as i32 * (board_w + VERSUS_GAP),
+            y: VERSUS_BOARD_Y,
+            cell,
+        };
+        draw_board(&player.tetris, &board);
+
+        let preview = Canvas { x: board.x + 3 * board.cell, y: CANVAS_Y, cell: board.cell };
+        ShapeIter::new(&player.tetris.next.shape, &Point::new(2, 2)).for_each(
+            |p| draw_cell(&preview, p.x, p.y, piece_color(player.tetris.next.color, Some(player.tetris.next.shape.name())))
+        );
+
+        let garbage = player.tetris.pending_garbage.min(H) as i32 * board.cell;
+        draw::set_draw_color(Color::Red);
+        draw::draw_rectf(board.x - 10, board.y + H as i32 * board.cell - garbage, 6, garbage);
+
+        let name = if player.bot.is_some() {tr_with("versus.cpu", &[("n", &idx)])} else {tr("versus.you").to_string()};
+        let stats = [
+            name,
+            tr_with("versus.lines", &[("lines", &player.tetris.lines)]),
+            tr_with("versus.kos", &[("kos", &player.kos)]),
+            player.tetris.item.map(|item| tr_with("versus.item", &[("name", &item.name())])).unwrap_or_default(),
+        ];
+        draw::set_font(Font::Courier, 20);
+        for (line, text) in stats.iter().enumerate() {
+            draw::draw_text2(text, board.x, board.y + H as i32 * board.cell + 10 + line as i32 * 25, board_w, 25, Align::Left);
+        }
+
+        if !player.is_alive() && !versus.is_over() {
+            draw::set_font(Font::Courier, 40);
+            draw::draw_text2(tr("versus.ko"), board.x, board.y + H as i32 * board.cell / 2 - 25, board_w, 50, Align::Center);
+        }
+    }
+
+    if versus.is_over() {
+        let message = if versus.winner() == Some(0) {tr("versus.win")} else {tr("versus.lose")};
+        draw::set_font(Font::Courier, 50);
+        draw::set_draw_color(Color::Red);
+        draw::draw_text2(message, 0, VERSUS_BOARD_Y + 200, window_w(), 50, Align::Center);
+    }
+}
+
+// What the event does to the game goes to `apply`, to run wherever the game is
+fn handle_game_input(ev: Event, keys: &KeyBindings, apply: impl FnOnce(Recorded)) -> bool {
+    match ev {
+        Event::Focus => true,
+        Event::KeyUp => {
+            let input = match keys.action(app::event_key()) {
+                Some(Action::Left) => Some(Recorded::LeftRelease),
+                Some(Action::Right) => Some(Recorded::RightRelease),
+                Some(Action::SoftDrop) => Some(Recorded::DownRelease),
+                _ => None,
+            };
+            if let Some(input) = input {
+                apply(input);
+            }
+            true
+        },
+        Event::KeyDown => {
+            let input = match keys.action(app::event_key()) {
+                Some(Action::Left) => Some(Recorded::LeftPress),
+                Some(Action::Right) => Some(Recorded::RightPress),
+                Some(Action::SoftDrop) => Some(Recorded::DownPress),
+                Some(Action::Rotate) => Some(Recorded::Rotate),
+                Some(Action::RotateCcw) => Some(Recorded::RotateCcw),
+                Some(Action::HardDrop) => Some(Recorded::HardDrop),
+                Some(Action::SonicDrop) => Some(Recorded::SonicDrop),
+                Some(Action::Hold) => Some(Recorded::Hold),
+                Some(Action::UseItem) => Some(Recorded::UseItem),
+                None => None,
+            };
+            if let Some(input) = input {
+                apply(input);
+            }
+            true
+        },
+        _ => false,
+    }
+}
+
+// The bound keys under the field, wrapped to its width
+fn draw_controls<const W: usize, const H: usize>(keys: &KeyBindings) {
+    let canvas = game_canvas::<W>();
+    let (x, y, w) = (canvas.x + 4, canvas.y + H as i32 * canvas.cell + 2, W as i32 * canvas.cell - 8);
+    draw::set_font(Font::Courier, 12);
+    draw::set_draw_color(Color::Red);
+
+    let mut lines = vec![String::new()];
+    let legend = Action::ALL.iter().map(|action| format!("{key} {name}", key = key_name(keys.key(*action)), name = action.name()))
+        .chain(std::iter::once(tr("hud.controls_key").to_string()));
+    for item in legend {
+        let line = lines.last_mut().unwrap();
+        let longer = if line.is_empty() {item.clone()} else {format!("{line}  {item}")};
+        if line.is_empty() || draw::width(&longer) as i32 <= w {
+            *line = longer;
+        } else {
+            lines.push(item);
+        }
+    }
+    for (idx, line) in lines.iter().enumerate() {
+        draw::draw_text2(line, x, y + idx as i32 * CONTROLS_LINE_H, w, CONTROLS_LINE_H, Align::Left);
+    }
+}
+
+// Speeds the game music up while the stack is close to the top, and back down once it's dug out
+fn update_danger_music(audio: &Audio, danger: &Cell<bool>, in_danger: bool) {
+    if danger.replace(in_danger) != in_danger {
+        audio.play_music(Some(if in_danger {Track::Danger} else {Track::Game}));
+    }
+}
+
+fn draw_mute_icon(audio: &Audio) {
+    if !audio.is_muted() {
+        return;
+    }
+
+    // kept off the bottom of the field, which is on the right when left-handed
+    let x = if LEFT_HANDED.load(Ordering::Relaxed) && !STREAM_LAYOUT.load(Ordering::Relaxed) {10} else {window_w() - 50};
+    let y = WINDOW_H - 45;
+    draw::set_draw_color(Color::White);
+    draw::draw_rectf(x, y + 10, 8, 12);
+    draw::draw_polygon(x + 8, y + 10, x + 18, y + 2, x + 18, y + 30);
+    draw::draw_rectf(x + 8, y + 10, 10, 12);
+
+    draw::set_draw_color(Color::Red);
+    draw::set_line_style(draw::LineStyle::Solid, 3);
+    draw::draw_line(x + 24, y + 10, x + 36, y + 22);
+    draw::draw_line(x + 36, y + 10, x + 24, y + 22);
+    draw::set_line_style(draw::LineStyle::Solid, 0);
+}
+
+fn draw_pause_menu(dim: &mut RgbImage, selected: usize) {
+    dim.draw(0, CANVAS_Y, window_w(), WINDOW_H - CANVAS_Y);
+
+    draw::set_font(Font::Courier, 50);
+    draw::set_draw_color(Color::Red);
+    draw::draw_text2(tr("pause.paused"), 0, PAUSE_MENU_Y - 100, window_w(), 50, Align::Center);
+
+    draw::set_font(Font::Courier, 30);
+    for (idx, option) in PAUSE_OPTIONS.iter().enumerate() {
+        let (x, y, w, h) = PauseOption::rect(idx);
+        if idx == selected {
+            draw::set_draw_color(Color::Red);
+            draw::draw_rectf(x, y, w, h);
+            draw::set_draw_color(Color::Black);
+        } else {
+            draw::set_draw_color(Color::Red);
+            draw::draw_rect(x, y, w, h);
+        }
+        draw::draw_text2(option.name(), x, y, w, h, Align::Center);
+    }
+}
+
+fn draw_hint<const W: usize>(placement: &Placement) {
+    ShapeIter::new(&placement.shape, &placement.loc).for_each(
+        |p| draw_outline(&game_canvas::<W>(), p.x, p.y, Color::White)
+    );
+}
+
+fn draw_opening<const W: usize, const H: usize>(tetris: &Tetris<W, H>, opening: &Opening) {
+    draw::set_font(Font::Courier, 24);
+    draw::set_draw_color(Color::Red);
+    draw::draw_text2(&opening.name, panel_x::<W>(), CANVAS_Y + 9 * CELL_SIZE, 6 * CELL_SIZE, 30, Align::Left);
+
+    if tetris.pieces > opening.setup_pieces() {
+        return;
+    }
+
+    let progress = opening.progress(tetris);
+    for (x, y) in progress.missing.iter() {
+        draw_outline(&game_canvas::<W>(), *x, *y, Color::from_rgb(90, 90, 90));
+    }
+    for (x, y) in progress.misplaced.iter() {
+        draw_outline(&game_canvas::<W>(), *x, *y, Color::Red);
+    }
+
+    if progress.is_complete {
+        let result = match progress.misplaced.len() {
+            0 => tr("trainer.complete").to_string(),
+            1 => tr("trainer.misplaced_one").to_string(),
+            count => tr_with("trainer.misplaced", &[("count", &count)]),
+        };
+        draw::set_draw_color(Color::Red);
+        draw::draw_text2(&result, panel_x::<W>(), CANVAS_Y + 10 * CELL_SIZE, 6 * CELL_SIZE, 30, Align::Left);
+    }
+}
+
+fn draw_puzzle<const W: usize, const H: usize>(tetris: &Tetris<W, H>, puzzle: &Puzzle, outcome: Outcome, progress: &PuzzleProgress) {
+    let lines = [
+        puzzle.name.clone(),
+        puzzle.goal.description(),
+        tr_with("puzzle.pieces_left", &[("n", &puzzle.pieces_left(tetris))]),
+        tr_with("puzzle.solved_count", &[("solved", &progress.solved_count()), ("total", &PUZZLES.len())]),
+        match outcome {
+            Outcome::Playing => tr("puzzle.retry").to_string(),
+            Outcome::Solved => tr("puzzle.solved").to_string(),
+            Outcome::Failed => tr("puzzle.failed").to_string(),
+        },
+        match outcome {
+            Outcome::Playing => String::new(),
+            _ => tr("puzzle.next_or_retry").to_string(),
+        },
+    ];
+
+    draw::set_font(Font::Courier, 20);
+    draw::set_draw_color(Color::Red);
+    for (line, text) in lines.iter().enumerate() {
+        draw::draw_text2(text, panel_x::<W>(), CANVAS_Y + 9 * CELL_SIZE + line as i32 * 30, 7 * CELL_SIZE, 30, Align::Left);
+    }
+}
+
+// Sprints compare their splits with the personal best's, other races list the best times
+fn draw_race<const W: usize, const H: usize>(tetris: &Tetris<W, H>, mode: Mode, outcome: Outcome, times: &Leaderboard, place: Option<usize>, run: &GhostRun, best: Option<&GhostRun>) {
+    let plain = |text: String| (text, Color::Red);
+    let mut lines = vec![
+        plain(match mode {
+            Mode::DigRace => tr_with("race.garbage_left", &[("rows", &tetris.garbage_rows())]),
+            _ => tr_with("race.lines_left", &[("lines", &SPRINT_LINES.saturating_sub(tetris.lines))]),
+        }),
+        plain(tr_with("race.time", &[("time", &format_time(tetris.elapsed()))])),
+        plain(match (outcome, place) {
+            (Outcome::Solved, Some(place)) => tr_with("race.done_place", &[("place", &(place + 1))]),
+            (Outcome::Solved, None) => tr("race.done").to_string(),
+            _ => String::new(),
+        }),
+    ];
+    if outcome == Outcome::Solved {
+        lines.push(plain(tr_with("hud.seed", &[("seed", &tetris.seed)])));
+    }
+
+    if mode == Mode::Sprint {
+        lines.push(plain(tr_with("race.pb", &[("time", &times.times().first().map_or("-".to_string(), |time| format_time(*time)))])));
+        for split in SPRINT_SPLITS {
+            let Some(time) = run.split(split) else {
+                lines.push(plain(format!("{split}: -")));
+                continue;
+            };
+            let line = match best.and_then(|best| best.split(split)) {
+                Some(best) if time <= best => (format!("{split}: {time} -{delta}", time = format_time(time), delta = format_delta(best - time)), SPLIT_AHEAD_COLOR),
+                Some(best) => (format!("{split}: {time} +{delta}", time = format_time(time), delta = format_delta(time - best)), SPLIT_BEHIND_COLOR),
+                None => plain(format!("{split}: {time}", time = format_time(time))),
+            };
+            lines.push(line);
+        }
+    } else {
+        lines.push(plain(tr("race.best_times").to_string()));
+        lines.extend(times.times().iter().take(SHOWN_TIMES).enumerate().map(|(idx, time)| plain(format!("{n}. {time}", n = idx + 1, time = format_time(*time)))));
+    }
+
+    draw::set_font(Font::Courier, 20);
+    for (line, (text, color)) in lines.iter().enumerate() {
+        draw::set_draw_color(*color);
+        draw::draw_text2(text, panel_x::<W>(), CANVAS_Y + 9 * CELL_SIZE + line as i32 * 25, 7 * CELL_SIZE, 25, Align::Left);
+    }
+}
+
+// Seconds and hundredths, like 1.25
+fn format_delta(delta: Duration) -> String {
+    format!("{seconds}.{centis:02}", seconds = delta.as_secs(), centis = delta.subsec_millis() / 10)
+}
+
+// The personal best's field at the same point in time, small and faded, with how far ahead it was
+fn draw_ghost<const W: usize, const H: usize>(tetris: &Tetris<W, H>, ghost: &GhostRun) {
+    let canvas = Canvas { x: panel_x::<W>(), y: CANVAS_Y + 14 * CELL_SIZE, cell: GHOST_CELL_SIZE };
+    draw::set_draw_color(Color::Black);
+    draw::draw_rectf(canvas.x, canvas.y, W as i32 * canvas.cell, H as i32 * canvas.cell);
+
+    let Some(frame) = ghost.at(tetris.elapsed()) else {
+        return;
+    };
+    for y in 0..H {
+        for x in (0..W).filter(|x| frame.is_filled(*x, y)) {
+            draw::set_draw_color(GHOST_COLOR_FILLED);
+            draw::draw_rectf(canvas.x + x as i32 * canvas.cell, canvas.y + y as i32 * canvas.cell, canvas.cell - 1, canvas.cell - 1);
+        }
+    }
+
+    let lead = match tetris.lines.cmp(&frame.lines) {
+        std::cmp::Ordering::Greater => tr_with("ghost.ahead", &[("lines", &(tetris.lines - frame.lines))]),
+        std::cmp::Ordering::Less => tr_with("ghost.behind", &[("lines", &(frame.lines - tetris.lines))]),
+        std::cmp::Ordering::Equal => tr("ghost.even").to_string(),
+    };
+    draw::set_font(Font::Courier, 16);
+    draw::set_draw_color(Color::Red);
+    draw::draw_text2(&tr_with("ghost.lead", &[("lead", &lead)]), canvas.x, canvas.y - 20, W as i32 * canvas.cell, 20, Align::Left);
+}
+
+fn draw_daily<const W: usize>(day: u64, best: &DailyBest, is_new_best: bool) {
+    let lines = [
+        daily::date(day),
+        tr_with("daily.todays_best", &[("best", &best.score.map_or("-".to_string(), |score| score.to_string()))]),
+        if is_new_best {tr("daily.new_best").to_string()} else {String::new()},
+    ];
+
+    draw::set_font(Font::Courier, 20);
+    draw::set_draw_color(Color::Red);
+    for (line, text) in lines.iter().enumerate() {
+        draw::draw_text2(text, panel_x::<W>(), CANVAS_Y + 9 * CELL_SIZE + line as i32 * 30, 7 * CELL_SIZE, 30, Align::Left);
+    }
+}
+
+// Under the held piece, with what each command has got so far this round
+fn draw_votes<const W: usize>(chat: &ChatVotes, left: Duration) {
+    let mut lines = vec![tr_with("hud.votes", &[("seconds", &left.as_secs_f64().ceil())])];
+    lines.extend(Command::ALL.iter().zip(chat.tally()).map(|(command, votes)| format!("{word:<8}{votes}", word = command.word())));
+
+    draw::set_font(Font::Courier, 20);
+    draw::set_draw_color(Color::Red);
+    for (line, text) in lines.iter().enumerate() {
+        draw::draw_text2(text, panel_x::<W>(), CANVAS_Y + 15 * CELL_SIZE + line as i32 * 25, 7 * CELL_SIZE, 25, Align::Left);
+    }
+}
+
+// Asks before throwing away a game that's still going. The game is paused while the question is up.
+// The engine only gets borrowed for a moment, since the window keeps taking in frames while the dialog is up
+fn confirm_leave<const W: usize, const H: usize>(engine: &RefCell<Engine<Tetris<W, H>>>, audio: &Audio) -> bool {
+    let was_running = matches!(engine.borrow().game().state, GameState::RUNNING);
+    if !was_running && !matches!(engine.borrow().game().state, GameState::PAUSED) {
+        return true;
+    }
+    if was_running {
+        engine.borrow().send(|tetris| tetris.receive(Recorded::Pause));
+        audio.pause_music(true);
+    }
+
+    let leave = dialog::choice2_default(tr("leave.question"), tr("leave.stay"), tr("leave.quit"), "") == Some(1);
+    if !leave && was_running {
+        engine.borrow().send(|tetris| tetris.receive(Recorded::Pause));
+        audio.pause_music(false);
+    }
+    leave
+}
+
+// The same for a match, which stops for everyone in it while the question is up
+fn confirm_leave_versus<const W: usize, const H: usize>(engine: &RefCell<Engine<Match<W, H>>>, audio: &Audio) -> bool {
+    if engine.borrow().game().is_over() {
+        return true;
+    }
+    engine.borrow().send(|versus: &mut Match<W, H>| versus.set_paused(true));
+    audio.pause_music(true);
+
+    let leave = dialog::choice2_default(tr("leave.question"), tr("leave.stay"), tr("leave.quit"), "") == Some(1);
+    if !leave {
+        engine.borrow().send(|versus: &mut Match<W, H>| versus.set_paused(false));
+        audio.pause_music(false);
+    }
+    leave
+}
+
+fn setup_game<const W: usize, const H: usize>(mut tetris: Tetris<W, H>, mode: Mode, wind: &mut DoubleWindow, pack: &mut Pack, sender: Sender<Page>, puzzle_progress: Rc<RefCell<PuzzleProgress>>, audio: Arc<Audio>) -> Widget {
+    pack.clear();
+    pack.begin();
+
+    let mut back = Button::new(0, 0, 50, 40, tr("back"));
+    let mut view = Widget::new(0, 0, window_w(), WINDOW_H - CANVAS_Y, "");
+
+    pack.end();
+    pack.show();
+    pack.draw(|_|{});
+
+    // only ever set in config.toml, so the file has the current address and keys
+    let Config { leaderboard_url, keys, tick_rate, twitch_channel, game_speed, handling, .. } = Config::load();
+    tetris.set_handling(handling);
+    // a slowed game or one on a chosen seed plays like any other, it just isn't ranked
+    let slowed = game_speed < 1.0;
+    if slowed {
+        tetris.slow_down(game_speed);
+    }
+    let unranked = slowed || CHOSEN_SEED.read().unwrap().is_some();
+    let leaderboard_url = leaderboard_url.filter(|_| !unranked);
+    let day = daily::today();
+    if let (Mode::Daily, Some(url)) = (mode, leaderboard_url.clone()) {
+        tetris.on_game_over(move |tetris| {
+            online::submit(&url, &format!("daily-{day}"), &Entry { score: tetris.score, time: tetris.elapsed() }, tetris.state_hash());
+        });
+    }
+    if mode.is_leaderboard_eligible() {
+        tetris.record();
+    }
+    tetris.track_finesse();
+    tetris.start();
+    let engine_rc = Rc::new(RefCell::new(Engine::spawn(tetris, tick_rate)));
+
+    let engine_back = engine_rc.clone();
+    let audio_back = audio.clone();
+    back.set_callback(move |_| {
+        if confirm_leave(&engine_back, &audio_back) {
+            sender.send(Page::Menu);
+        }
+    });
+
+    // the window's X throws the game away just the same, so it asks first too
+    let engine_close = engine_rc.clone();
+    let audio_close = audio.clone();
+    wind.set_callback(move |wind| {
+        if app::event() == Event::Close && !confirm_leave(&engine_close, &audio_close) {
+            return;
+        }
+        wind.hide();
+    });
+
+    let engine_rc1 = engine_rc.clone();
+    let engine_rc2 = engine_rc.clone();
+
+    let show_hint = Rc::new(Cell::new(false));
+    let show_hint1 = show_hint.clone();
+    let hint = Bot::new(Difficulty::Hard);
+    // worked out once for each piece, where it came in, so moving it around doesn't move the hint
+    let mut hint_for: Option<((usize, Tetromino), Option<Placement>)> = None;
+
+    let history_rc = Rc::new(RefCell::new(UndoHistory::new(UNDO_DEPTH)));
+    let history_tick = history_rc.clone();
+
+    let outcome = Rc::new(Cell::new(Outcome::Playing));
+    let outcome1 = outcome.clone();
+    let audio1 = audio.clone();
+    let danger = Cell::new(false);
+    let mut grading = Grading::default();
+    let mut race_times = mode.times_file().map(Leaderboard::load);
+    let mut race_place = None;
+    let mut hud = Hud::new();
+    // the latest chain and when on the game clock it happened
+    let mut chain_popup: Option<(usize, Duration)> = None;
+    let mut announcer = Announcer::default();
+    let best_ghost = if mode == Mode::Sprint {GhostRun::load(SPRINT_GHOST_FILE)} else {None};
+    let mut ghost = GhostRun::new();
+    let show_ghost = Rc::new(Cell::new(true));
+    let show_ghost1 = show_ghost.clone();
+    let pause_selection = Rc::new(Cell::new(0));
+    let pause_selection1 = pause_selection.clone();
+    let dim_pixels = [0, 0, 0, PAUSE_DIM].repeat((window_w() * (WINDOW_H - CANVAS_Y)) as usize);
+    let mut dim = RgbImage::new(&dim_pixels, window_w(), WINDOW_H - CANVAS_Y, ColorDepth::Rgba8).unwrap();
+    let mut daily_best = DailyBest::load(day);
+    let mut is_daily_best = false;
+    let show_controls = Rc::new(Cell::new(true));
+    let show_controls1 = show_controls.clone();
+    let keys1 = keys.clone();
+    let mut presence_shown: Option<Instant> = None;
+
+    let chat = twitch_channel.filter(|_| mode == Mode::TwitchPlays).map(|channel| Rc::new(ChatVotes::connect(&channel)));
+    let chat1 = chat.clone();
+    // when the round of votes going on started
+    let round_start = Rc::new(Cell::new(Instant::now()));
+    let round_start1 = round_start.clone();
+    let mut shown_votes = ([0; 4], 0);
+
+    // every copy is kept track of, since each newly spawned piece comes back in a copy of its own
+    let engine_tick = engine_rc.clone();
+    view.set_callback(move |view| {
+        let mut history = history_tick.borrow_mut();
+        if engine_tick.borrow_mut().receive_frames(|tetris| if mode.allows_undo() {history.record(tetris)}) {
+            view.set_changed();
+        }
+
+        let Some(chat) = chat.as_ref() else {
+            return;
+        };
+        if round_start.get().elapsed() >= VOTE_ROUND {
+            round_start.set(Instant::now());
+            if let Some(command) = chat.take_winner() {
+                engine_tick.borrow().send(move |tetris| command.apply(tetris));
+            }
+        }
+        // the tally and the countdown are drawn, so either one changing needs a redraw
+        let votes = (chat.tally(), VOTE_ROUND.saturating_sub(round_start.get().elapsed()).as_secs());
+        if votes != shown_votes {
+            shown_votes = votes;
+            view.set_changed();
+        }
+    });
+
+    view.draw(move |view| {
+        clear_view(view);
+        let mut engine = engine_rc1.borrow_mut();
+        update_danger_music(&audio, &danger, engine.game().in_danger());
+
+        while let Some(event) = engine.poll_event() {
+            let tetris = engine.game();
+            audio.play_event(&event);
+            announcer.receive_event(&event, &audio);
+            if let GameEvent::Chain(chain) = event {
+                chain_popup = Some((chain, tetris.elapsed()));
+            }
+            if mode == Mode::Sprint && matches!(event, GameEvent::Locked) {
+                ghost.record(tetris);
+            }
+            if matches!(event, GameEvent::GameOver) {
+                export::record(tetris, mode);
+                if let Some(replay) = Replay::of(tetris, mode, day, game_speed, handling) {
+                    replay.save(REPLAY_FILE);
+                }
+            }
+            if mode == Mode::Daily && matches!(event, GameEvent::GameOver) {
+                is_daily_best = !unranked && daily_best.record(tetris.score);
+            }
+            if let Mode::Puzzle(idx) = mode {
+                if outcome1.get() == Outcome::Playing && PUZZLES[idx].is_solved_by(&event) {
+                    outcome1.set(Outcome::Solved);
+                    puzzle_progress.borrow_mut().mark_solved(&PUZZLES[idx]);
+                }
+            }
+        }
+        let tetris = engine.game();
+        announcer.receive_state(tetris, &audio);
+        if let Mode::Puzzle(idx) = mode {
+            if outcome1.get() == Outcome::Playing && PUZZLES[idx].is_failed(tetris) {
+                outcome1.set(Outcome::Failed);
+            }
+        }
+        // the clock stops the moment the race is done, so it's over right there
+        if outcome1.get() == Outcome::Playing && mode.is_race_finished(tetris) {
+            outcome1.set(Outcome::Solved);
+            race_place = race_times.as_mut().filter(|_| !unranked).and_then(|times| times.record(tetris.elapsed()));
+            export::record(tetris, mode);
+            if let Some(replay) = Replay::of(tetris, mode, day, game_speed, handling) {
+                replay.save(REPLAY_FILE);
+            }
+            if mode == Mode::Sprint && race_place == Some(0) {
+                ghost.save(SPRINT_GHOST_FILE);
+            }
+            if let (Some(url), Some(board)) = (leaderboard_url.as_deref(), mode.board_name()) {
+                online::submit(url, board, &Entry { score: tetris.score, time: tetris.elapsed() }, tetris.state_hash());
+            }
+        }
+        // a finished puzzle or race stays as it ended
+        engine.set_ticking(outcome1.get() == Outcome::Playing);
+        awake::keep_awake(matches!(tetris.state, GameState::RUNNING) && outcome1.get() == Outcome::Playing);
+        if presence_shown.is_none_or(|shown| shown.elapsed() >= presence::UPDATE_INTERVAL) {
+            presence_shown = Some(Instant::now());
+            let running = matches!(tetris.state, GameState::RUNNING) && outcome1.get() == Outcome::Playing;
+            presence::show(Some(Activity {
+                details: mode.name().to_string(),
+                state: tr_with("presence.state", &[("level", &tetris.level()), ("score", &tetris.score)]),
+                elapsed: running.then(|| tetris.elapsed()),
+            }));
+        }
+        let compact = COMPACT_LAYOUT.load(Ordering::Relaxed);
+        draw_game(tetris, compact, &mut hud);
+        if let Some((chain, at)) = chain_popup {
+            draw_chain_popup(tetris, chain, at);
+        }
+
+        if let Mode::Trainer(idx) = mode {
+            draw_opening(tetris, &OPENINGS[idx]);
+        }
+        if let Mode::Puzzle(idx) = mode {
+            draw_puzzle(tetris, &PUZZLES[idx], outcome1.get(), &puzzle_progress.borrow());
+        }
+        if mode == Mode::Master {
+            grading.update(tetris);
+        }
+
+        if !compact {
+            let mode_name = if slowed {tr_with("hud.slowed", &[("mode", &mode.name()), ("percent", &(game_speed * 100.0).round())])} else {mode.name().to_string()};
+            hud.mode.draw(&mode_name, panel_x::<W>(), CANVAS_Y + 8 * CELL_SIZE);
+            if mode.allows_assist() {
+                hud.hint_key.draw(tr("hud.hint_key"), panel_x::<W>(), CANVAS_Y + 9 * CELL_SIZE);
+            }
+            if mode.allows_undo() {
+                hud.undo_key.draw(tr("hud.undo_key"), panel_x::<W>(), CANVAS_Y + 10 * CELL_SIZE);
+            }
+
+            if mode == Mode::Master {
+                draw_grade(tetris, &grading);
+            }
+            if mode == Mode::Survival {
+                draw_survival_time(tetris);
+            }
+            if let Some(times) = race_times.as_ref() {
+                draw_race(tetris, mode, outcome1.get(), times, race_place, &ghost, best_ghost.as_ref());
+            }
+            if let Some(best_ghost) = best_ghost.as_ref().filter(|_| show_ghost1.get()) {
+                draw_ghost(tetris, best_ghost);
+            }
+            if mode == Mode::Daily {
+                draw_daily::<W>(day, &daily_best, is_daily_best);
+            }
+            if let Some(chat) = chat1.as_ref() {
+                draw_votes::<W>(chat, VOTE_ROUND.saturating_sub(round_start1.get().elapsed()));
+            }
+        }
+
+        if show_hint1.get() && matches!(tetris.state, GameState::RUNNING) {
+            let piece = (tetris.pieces, tetris.falling.shape.name());
+            if hint_for.as_ref().is_none_or(|(of, _)| *of != piece) {
+                hint_for = Some((piece, hint.best_placement(&tetris.at_spawn())));
+            }
+            if let Some((_, Some(placement))) = hint_for.as_ref() {
+                draw_hint::<W>(placement);
+            }
+        }
+        if show_controls1.get() {
+            draw_controls::<W, H>(&keys1);
+        }
+        if matches!(tetris.state, GameState::PAUSED) {
+            draw_pause_menu(&mut dim, pause_selection1.get());
+        }
+        draw_mute_icon(&audio);
+    });
+
+    handle_game_page(wind, &view, move |ev| {
+        // keys let go of while the window was away never come up, so they're let go of here
+        if matches!(ev, Event::Unfocus | Event::Hide) {
+            let engine = engine_rc2.borrow();
+            engine.send(|tetris| {
+                tetris.receive(Recorded::LeftRelease);
+                tetris.receive(Recorded::RightRelease);
+                tetris.receive(Recorded::DownRelease);
+            });
+            // so alt-tabbing away doesn't leave the game to top out on its own
+            if AUTO_PAUSE.load(Ordering::Relaxed) && matches!(engine.game().state, GameState::RUNNING) {
+                engine.send(|tetris| if matches!(tetris.state, GameState::RUNNING) {tetris.receive(Recorded::Pause)});
+                pause_selection.set(0);
+                audio1.pause_music(true);
+            }
+            return true;
+        }
+
+        if mode.allows_assist() && ev == Event::KeyDown && app::event_key() == KEY_H {
+            show_hint.set(!show_hint.get());
+            return true;
+        }
+
+        if ev == Event::KeyDown && app::event_key() == KEY_M {
+            audio1.toggle_mute();
+            return true;
+        }
+
+        if ev == Event::KeyDown && app::event_key() == Key::F1 {
+            show_controls.set(!show_controls.get());
+            return true;
+        }
+
+        if ev == Event::KeyDown && app::event_key() == KEY_L {
+            COMPACT_LAYOUT.fetch_xor(true, Ordering::Relaxed);
+            return true;
+        }
+
+        if ev == Event::KeyDown && app::event_key() == Key::Escape && matches!(engine_rc2.borrow().game().state, GameState::RUNNING) {
+            if confirm_leave(&engine_rc2, &audio1) {
+                sender.send(Page::Menu);
+            }
+            return true;
+        }
+
+        if ev == Event::KeyDown && app::event_key() == KEY_P {
+            let engine = engine_rc2.borrow();
+            // pausing only ever takes a running game
+            let pausing = matches!(engine.game().state, GameState::RUNNING);
+            engine.send(|tetris| tetris.receive(Recorded::Pause));
+            pause_selection.set(0);
+            audio1.pause_music(pausing);
+            return true;
+        }
+
+        // while paused, the keys and clicks go to the pause menu
+        if matches!(engine_rc2.borrow().game().state, GameState::PAUSED) {
+            let chosen = match ev {
+                Event::KeyDown => match app::event_key() {
+                    key if key == Key::Up || key == keys.key(Action::Rotate) => {
+                        pause_selection.set((pause_selection.get() + PAUSE_OPTIONS.len() - 1) % PAUSE_OPTIONS.len());
+                        None
+                    },
+                    key if key == Key::Down || key == keys.key(Action::SoftDrop) => {
+                        pause_selection.set((pause_selection.get() + 1) % PAUSE_OPTIONS.len());
+                        None
+                    },
+                    Key::Enter | KEY_SPACE => Some(PAUSE_OPTIONS[pause_selection.get()]),
+                    Key::Escape => Some(PauseOption::Resume),
+                    _ => None,
+                },
+                Event::Push => PauseOption::at(app::event_coords()).map(|idx| PAUSE_OPTIONS[idx]),
+                _ => None,
+            };
+            match chosen {
+                Some(PauseOption::Resume) => {
+                    engine_rc2.borrow().send(|tetris| tetris.receive(Recorded::Pause));
+                    audio1.pause_music(false);
+                },
+                Some(PauseOption::Restart) => sender.send(Page::Game(mode)),
+                // the settings page has no way back into the game, so going there gives it up
+                Some(PauseOption::Settings) if confirm_leave(&engine_rc2, &audio1) => sender.send(Page::Settings),
+                Some(PauseOption::Quit) => sender.send(Page::Menu),
+                Some(PauseOption::Settings) | None => {},
+            }
+            return ev != Event::Push || chosen.is_some();
+        }
+
+        if let Mode::Puzzle(idx) = mode {
+            if ev == Event::KeyDown && app::event_key() == KEY_R {
+                sender.send(Page::Game(mode));
+                return true;
+            }
+            if outcome.get() != Outcome::Playing {
+                if ev == Event::KeyDown && app::event_key() == KEY_N {
+                    sender.send(Page::Game(Mode::Puzzle((idx + 1) % PUZZLES.len())));
+                }
+                return true;
+            }
+        }
+        if mode == Mode::Sprint && ev == Event::KeyDown && app::event_key() == KEY_G {
+            show_ghost.set(!show_ghost.get());
+            return true;
+        }
+        if mode.is_race() && outcome.get() != Outcome::Playing {
+            return true;
+        }
+
+        let engine = engine_rc2.borrow();
+        if mode.allows_undo() && ev == Event::KeyDown && app::is_event_ctrl() && app::event_key() == KEY_Z {
+            if let Some(snapshot) = history_rc.borrow_mut().undo() {
+                engine.send(move |tetris| tetris.restore(&snapshot));
+            }
+            return true;
+        }
+        // chat does the playing
+        if mode == Mode::TwitchPlays {
+            return false;
+        }
+        handle_game_input(ev, &keys, |input| engine.send(move |tetris| tetris.receive(input)))
+    });
+    view
+}
+
+fn setup_versus<const W: usize, const H: usize>(mut versus: Match<W, H>, wind: &mut DoubleWindow, pack: &mut Pack, sender: Sender<Page>, audio: Arc<Audio>) -> Widget {
+    pack.clear();
+    pack.begin();
+
+    let mut back = Button::new(0, 0, 50, 40, tr("back"));
+    let mut view = Widget::new(0, 0, window_w(), WINDOW_H - CANVAS_Y, "");
+
+    pack.end();
+    pack.show();
+    pack.draw(|_|{});
+
+    // the match polls your events to send garbage, so your clears and knockout are heard through callbacks
+    let cleared = audio.clone();
+    versus.players[0].tetris.on_lines_cleared(move |lines| cleared.play_event(&GameEvent::LinesCleared(lines)));
+    let knocked_out = audio.clone();
+    versus.players[0].tetris.on_game_over(move |_| knocked_out.play_event(&GameEvent::GameOver));
+
+    let Config { keys, tick_rate, handling, .. } = Config::load();
+    versus.players[0].tetris.set_handling(handling);
+    versus.players[0].tetris.track_finesse();
+    let players = versus.players.len();
+    versus.start();
+    let engine_rc = Rc::new(RefCell::new(Engine::spawn(versus, tick_rate)));
+
+    let engine_back = engine_rc.clone();
+    let audio_back = audio.clone();
+    back.set_callback(move |_| {
+        if confirm_leave_versus(&engine_back, &audio_back) {
+            sender.send(Page::Menu);
+        }
+    });
+
+    let engine_close = engine_rc.clone();
+    let audio_close = audio.clone();
+    wind.set_callback(move |wind| {
+        if app::event() == Event::Close && !confirm_leave_versus(&engine_close, &audio_close) {
+            return;
+        }
+        wind.hide();
+    });
+
+    let engine_rc1 = engine_rc.clone();
+    let engine_rc2 = engine_rc.clone();
+    let audio1 = audio.clone();
+    let danger = Cell::new(false);
+
+    let wall = (players >= BOARD_WALL_FROM).then(|| {
+        let names = (0..players).map(|idx| if idx == 0 {tr("versus.you").to_string()} else {tr_with("versus.cpu", &[("n", &idx)])}).collect();
+        Rc::new(RefCell::new(BoardWall::new(names, W, H)))
+    });
+    let wall1 = wall.clone();
+    let mut reported_kos = vec![false; players];
+
+    let engine_tick = engine_rc.clone();
+    view.set_callback(move |view| {
+        let mut engine = engine_tick.borrow_mut();
+        if !engine.receive_frames(|_| {}) {
+            return;
+        }
+        view.set_changed();
+        // the wall gets told about the match the way it would be about an online room, which there's
+        // no way to join yet
+        if let Some(wall) = wall.as_ref() {
+            for message in room_messages(engine.game(), &mut reported_kos) {
+                wall.borrow_mut().receive(&message);
+            }
+        }
+    });
+
+    view.draw(move |view| {
+        clear_view(view);
+        let engine = engine_rc1.borrow();
+        let versus = engine.game();
+        update_danger_music(&audio1, &danger, versus.players[0].tetris.in_danger());
+        awake::keep_awake(!versus.is_over());
+        match wall1.as_ref() {
+            Some(wall) => draw_board_wall(versus, &wall.borrow()),
+            None => draw_versus(versus),
+        }
+        draw_mute_icon(&audio1);
+    });
+
+    handle_game_page(wind, &view, move |ev| {
+        if ev == Event::KeyDown && app::event_key() == KEY_M {
+            audio.toggle_mute();
+            return true;
+        }
+        if ev == Event::KeyDown && app::event_key() == Key::Escape && !engine_rc2.borrow().game().is_over() {
+            if confirm_leave_versus(&engine_rc2, &audio) {
+                sender.send(Page::Menu);
+            }
+            return true;
+        }
+        let engine = engine_rc2.borrow();
+        handle_game_input(ev, &keys, |input| engine.send(move |versus: &mut Match<W, H>| input.apply(&mut versus.players[0].tetris)))
+    });
+    view
+}
+
+// The game of the demo page along with the bot playing it, starting over whenever it loses
+#[derive(Clone)]
+struct Demo<const W: usize, const H: usize> {
+    tetris: Tetris<W, H>,
+    bot: Bot,
+}
+
+impl<const W: usize, const H: usize> Simulation for Demo<W, H> {
+    fn receive_tick(&mut self) {
+        if matches!(self.tetris.state, GameState::LOST) {
+            self.tetris = Tetris::new();
+            self.tetris.start();
+        }
+        self.bot.drive(&mut self.tetris);
+        self.tetris.receive_tick();
+        while self.tetris.poll_event().is_some() {}
+    }
+
+    fn take_changed(&mut self) -> bool {
+        self.tetris.take_changed()
+    }
+}
+
+fn setup_demo<const W: usize, const H: usize>(wind: &mut DoubleWindow, pack: &mut Pack, sender: Sender<Page>) -> Widget {
+    pack.clear();
+    pack.begin();
+    // no buttons, the banner goes where they'd be
+    let mut view = Widget::new(0, 0, window_w(), WINDOW_H, "");
+    pack.end();
+    pack.show();
+    pack.draw(|_|{});
+
+    let mut demo = Demo { tetris: Tetris::<W, H>::new(), bot: Bot::new(Difficulty::Hard) };
+    demo.tetris.start();
+    let engine_rc = Rc::new(RefCell::new(Engine::spawn(demo, Config::load().tick_rate)));
+    let mut hud = Hud::new();
+
+    let engine_tick = engine_rc.clone();
+    view.set_callback(move |view| {
+        if engine_tick.borrow_mut().receive_frames(|_| {}) {
+            view.set_changed();
+        }
+    });
+
+    view.draw(move |view| {
+        clear_view(view);
+        draw_game(&engine_rc.borrow().game().tetris, COMPACT_LAYOUT.load(Ordering::Relaxed), &mut hud);
+
+        draw::set_font(Font::Courier, 30);
+        draw::set_draw_color(Color::Red);
+        draw::draw_text2(tr("demo.banner"), 0, 0, window_w(), CANVAS_Y, Align::Center);
+    });
+
+    wind.handle(move |_, ev| {
+        match ev {
+            Event::KeyDown | Event::Push => {
+                sender.send(Page::Menu);
+                true
+            },
+            _ => false,
+        }
+    });
+    view
+}
+
+// Online results for one board, as far as they have come in
+enum OnlineTop {
+    Loading(Receiver<Result<Vec<Entry>, String>>),
+    Loaded(Vec<Entry>),
+    Failed,
+}
+
+impl OnlineTop {
+    fn poll(&mut self) {
+        let OnlineTop::Loading(receiver) = self else {
+            return;
+        };
+        match receiver.try_recv() {
+            Ok(Ok(entries)) => *self = OnlineTop::Loaded(entries),
+            Ok(Err(err)) => {
+                eprintln!("can't load the online leaderboard: {err}");
+                *self = OnlineTop::Failed;
+            },
+            Err(_) => {},
+        }
+    }
+
+    fn lines(&self, text: fn(&Entry) -> String) -> Vec<String> {
+        match self {
+            OnlineTop::Loading(_) => vec![tr("scores.loading").to_string()],
+            OnlineTop::Loaded(entries) if entries.is_empty() => vec![tr("scores.no_entries").to_string()],
+            OnlineTop::Loaded(entries) => entries.iter().enumerate().map(|(idx, entry)| format!("{n}. {text}", n = idx + 1, text = text(entry))).collect(),
+            OnlineTop::Failed => vec![tr("scores.unreachable").to_string()],
+        }
+    }
+}
+
+struct OnlineBoard {
+    title: &'static str,
+    top: OnlineTop,
+    show: fn(&Entry) -> String,
+}
+
+fn setup_high_scores(leaderboard_url: Option<&str>, wind: &mut DoubleWindow, pack: &mut Pack, sender: Sender<Page>) {
+    pack.clear();
+    pack.begin();
+
+    Button::new(0, 0, 50, 40, tr("back")).emit(sender, Page::Menu);
+
+    pack.end();
+    pack.show();
+
+    let day = daily::today();
+    let races = [Mode::Sprint, Mode::DigRace];
+    let race_times: Vec<Leaderboard> = races.iter().filter_map(Mode::times_file).map(Leaderboard::load).collect();
+    let daily_best = DailyBest::load(day);
+    let mut online_boards = vec![];
+    if let Some(url) = leaderboard_url {
+        for mode in races {
+            let top = OnlineTop::Loading(online::fetch_top(url, mode.board_name().unwrap()));
+            online_boards.push(OnlineBoard { title: mode.name(), top, show: |entry| format_time(entry.time) });
+        }
+        let top = OnlineTop::Loading(online::fetch_top(url, &format!("daily-{day}")));
+        online_boards.push(OnlineBoard { title: tr("scores.daily_title"), top, show: |entry| entry.score.to_string() });
+    }
+    let online_boards = Rc::new(RefCell::new(online_boards));
+
+    // the page only shows what came in once it's drawn again, which nothing else does while it's left open.
+    // Stops once every board has come in, or the page is gone
+    let (boards, mut page) = (Rc::downgrade(&online_boards), pack.clone());
+    app::add_timeout3(SCORES_POLL_SECONDS, move |handle| {
+        let Some(boards) = boards.upgrade() else {
+            return;
+        };
+        let mut boards = boards.borrow_mut();
+        boards.iter_mut().for_each(|board| board.top.poll());
+        page.redraw();
+        if boards.iter().any(|board| matches!(board.top, OnlineTop::Loading(_))) {
+            app::repeat_timeout3(SCORES_POLL_SECONDS, handle);
+        }
+    });
+
+    pack.draw(move |_| {
+        let mut left = vec![];
+        for (mode, times) in races.iter().zip(race_times.iter()) {
+            left.push(mode.name().to_string());
+            left.extend(times.times().iter().take(SHOWN_TIMES).enumerate().map(|(idx, time)| format!("{n}. {time}", n = idx + 1, time = format_time(*time))));
+            left.push(String::new());
+        }
+        left.push(tr_with("scores.daily", &[("date", &daily::date(day))]));
+        left.push(daily_best.score.map_or(tr("scores.no_game").to_string(), |score| tr_with("scores.best", &[("score", &score)])));
+
+        let mut right = vec![];
+        for board in online_boards.borrow().iter() {
+            right.push(tr_with("scores.online", &[("title", &board.title)]));
+            right.extend(board.top.lines(board.show));
+            right.push(String::new());
+        }
+
+        draw::set_font(Font::Courier, 20);
+        draw::set_draw_color(Color::Red);
+        for (column, lines) in [left, right].iter().enumerate() {
+            for (line, text) in lines.iter().enumerate() {
+                draw::draw_text2(text, 20 + column as i32 * WINDOW_W / 2, CANVAS_Y + 20 + line as i32 * 30, WINDOW_W / 2 - 20, 30, Align::Left);
+            }
+        }
+    });
+
+    wind.handle(|_, _| false);
+}
+
+fn cell_at<const W: usize, const H: usize>(canvas: &Canvas, (x, y): (i32, i32)) -> Option<(usize, usize)> {
+    if x < canvas.x || y < canvas.y {
+        return None;
+    }
+    let (x, y) = (((x - canvas.x) / canvas.cell) as usize, ((y - canvas.y) / canvas.cell) as usize);
+    if x >= W || y >= H {
+        return None;
+    }
+    Some((x, y))
+}
+
+fn draw_editor<const W: usize, const H: usize>(setup: &BoardSetup<W, H>) {
+    draw::set_draw_color(Color::Black);
+    draw::draw_rectf(game_canvas::<W>().x, game_canvas::<W>().y, W as i32 * CELL_SIZE, H as i32 * CELL_SIZE);
+
+    for x in 0..W {
+        for y in 0..H {
+            if let Some(cell) = setup.field[y][x] {
+                draw_cell(&game_canvas::<W>(), x, y, piece_color(cell.color, cell.piece));
+            } else if !setup.can_paint(x, y) {
+                draw_outline(&game_canvas::<W>(), x, y, Color::from_rgb(40, 40, 40));
+            }
+        }
+    }
+
+    let queue: Vec<String> = setup.pieces.chunks(8)
+        .map(|pieces| pieces.iter().map(|name| format!("{name:?}")).collect::<Vec<_>>().join(" "))
+        .collect();
+    let help = [
+        tr("editor.paint"),
+        tr("editor.add_piece"),
+        tr("editor.remove_piece"),
+        "",
+        tr("editor.queue"),
+    ];
+
+    draw::set_font(Font::Courier, 20);
+    draw::set_draw_color(Color::Red);
+    for (line, text) in help.iter().copied().chain(queue.iter().map(String::as_str)).enumerate() {
+        draw::draw_text2(text, panel_x::<W>(), CANVAS_Y + 10 + line as i32 * 25, 7 * CELL_SIZE, 25, Align::Left);
+    }
+}
+
+fn setup_editor<const W: usize, const H: usize>(board_setup: Rc<RefCell<BoardSetup<W, H>>>, wind: &mut DoubleWindow, pack: &mut Pack, sender: Sender<Page>) {
+    pack.clear();
+    pack.begin();
+
+    let mut buttons = Pack::new(0, 0, WINDOW_W, 40, "");
+    buttons.set_type(PackType::Horizontal);
+    Button::new(0, 0, 50, 40, tr("back")).emit(sender, Page::Menu);
+    Button::new(0, 0, 80, 40, tr("editor.play")).emit(sender, Page::Game(Mode::Custom));
+    Button::new(0, 0, 100, 40, Mode::Sandbox.name()).emit(sender, Page::Game(Mode::Sandbox));
+    let setup = board_setup.clone();
+    Button::new(0, 0, 80, 40, tr("editor.clear")).set_callback(move |_| setup.borrow_mut().clear());
+    buttons.end();
+
+    pack.end();
+    pack.show();
+
+    let setup = board_setup.clone();
+    pack.draw(move |_| draw_editor(&setup.borrow()));
+
+    // dragging keeps doing whatever the first click did, so strokes either paint or erase
+    let mut painting = true;
+    wind.handle(move |_, ev| {
+        let mut setup = board_setup.borrow_mut();
+        match ev {
+            Event::Focus => true,
+            Event::Push | Event::Drag => {
+                let Some((x, y)) = cell_at::<W, H>(&game_canvas::<W>(), app::event_coords()) else {
+                    return false;
+                };
+                if ev == Event::Push {
+                    painting = setup.field[y][x].is_none();
+                }
+                setup.paint(x, y, painting);
+                true
+            },
+            Event::KeyDown => {
+                let key = app::event_key();
+                if key == Key::BackSpace {
+                    setup.pieces.pop();
+                } else if let Some(name) = key.to_char().and_then(|key| Tetromino::from_char(key.to_ascii_uppercase())) {
+                    setup.pieces.push(name);
+                }
+                true
+            },
+            _ => false,
+        }
+    });
 }
@@ -0,0 +1,144 @@
+use crate::daily;
+use crate::game::Tetris;
+use crate::locale::tr;
+use crate::rules::RuleSet;
+
+pub const SPRINT_LINES: usize = 40;
+// the garbage a dig race starts with
+pub const DIG_RACE_ROWS: usize = 10;
+// lines at which a sprint's time gets compared with the personal best
+pub const SPRINT_SPLITS: [usize; 3] = [10, 20, 30];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Mode {
+    Marathon,
+    Classic,
+    Master,
+    Guideline,
+    Pentomino,
+    Survival,
+    Sprint,
+    DigRace,
+    // everyone gets the same pieces on the same day
+    Daily,
+    // one of the PIECE_SETS
+    PieceSet(usize),
+    Practice,
+    // the usual rules played mirrored
+    Mirror,
+    Trainer(usize),
+    Custom,
+    // the board editor's setup without gravity
+    Sandbox,
+    Puzzle(usize),
+    // the usual rules, played by the votes of a Twitch chat
+    TwitchPlays,
+}
+
+impl Mode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Marathon => tr("mode.marathon"),
+            Mode::Classic => tr("mode.classic"),
+            Mode::Master => tr("mode.master"),
+            Mode::Guideline => tr("mode.guideline"),
+            Mode::Pentomino => tr("mode.pentomino"),
+            Mode::Survival => tr("mode.survival"),
+            Mode::Sprint => tr("mode.sprint"),
+            Mode::DigRace => tr("mode.dig_race"),
+            Mode::Daily => tr("mode.daily"),
+            Mode::PieceSet(_) => tr("mode.piece_set"),
+            Mode::Practice => tr("mode.practice"),
+            Mode::Mirror => tr("mode.mirror"),
+            Mode::Trainer(_) => tr("mode.trainer"),
+            Mode::Custom => tr("mode.custom"),
+            Mode::Sandbox => tr("mode.sandbox"),
+            Mode::Puzzle(_) => tr("mode.puzzle"),
+            Mode::TwitchPlays => tr("mode.twitch_plays"),
+        }
+    }
+
+    // The same in every language, for files other programs read
+    pub fn key(&self) -> String {
+        match self {
+            Mode::Marathon => "marathon".to_string(),
+            Mode::Classic => "classic".to_string(),
+            Mode::Master => "master".to_string(),
+            Mode::Guideline => "guideline".to_string(),
+            Mode::Pentomino => "pentomino".to_string(),
+            Mode::Survival => "survival".to_string(),
+            Mode::Sprint => "sprint".to_string(),
+            Mode::DigRace => "dig_race".to_string(),
+            Mode::Daily => "daily".to_string(),
+            Mode::PieceSet(idx) => format!("piece_set_{idx}"),
+            Mode::Practice => "practice".to_string(),
+            Mode::Mirror => "mirror".to_string(),
+            Mode::Trainer(idx) => format!("trainer_{idx}"),
+            Mode::Custom => "custom".to_string(),
+            Mode::Sandbox => "sandbox".to_string(),
+            Mode::Puzzle(idx) => format!("puzzle_{idx}"),
+            Mode::TwitchPlays => "twitch_plays".to_string(),
+        }
+    }
+
+    pub fn is_leaderboard_eligible(&self) -> bool {
+        matches!(self, Mode::Marathon | Mode::Sprint | Mode::DigRace | Mode::Daily)
+    }
+
+    // The start of a leaderboard game, the same every time for the same seed so a replay of it can
+    // start over from there. None for the other modes
+    pub fn ranked_game(&self, rules: &RuleSet, seed: u64, day: u64) -> Option<Tetris<10, 20>> {
+        match self {
+            Mode::Marathon => Some(Tetris::with_seed(rules.clone(), seed)),
+            Mode::Sprint => Some(Tetris::with_seed(RuleSet::default(), seed)),
+            Mode::DigRace => {
+                let mut tetris = Tetris::with_seed(RuleSet::default(), seed);
+                for _ in 0..DIG_RACE_ROWS {
+                    tetris.insert_garbage_row();
+                }
+                Some(tetris)
+            },
+            Mode::Daily => Some(daily::to_tetris(day, seed)),
+            _ => None,
+        }
+    }
+
+    pub fn allows_assist(&self) -> bool {
+        !self.is_leaderboard_eligible() && !matches!(self, Mode::Trainer(_) | Mode::Puzzle(_))
+    }
+
+    pub fn allows_undo(&self) -> bool {
+        matches!(self, Mode::Practice | Mode::Custom | Mode::Sandbox)
+    }
+
+    // Races are played against the clock, which stops as soon as this is done
+    pub fn is_race_finished<const W: usize, const H: usize>(&self, tetris: &Tetris<W, H>) -> bool {
+        match self {
+            Mode::Sprint => tetris.lines >= SPRINT_LINES,
+            Mode::DigRace => tetris.garbage_rows() == 0,
+            _ => false,
+        }
+    }
+
+    pub fn is_race(&self) -> bool {
+        self.times_file().is_some()
+    }
+
+    // Where the best times of a race are kept
+    pub fn times_file(&self) -> Option<&'static str> {
+        match self {
+            Mode::Sprint => Some("sprint_times.txt"),
+            Mode::DigRace => Some("dig_race_times.txt"),
+            _ => None,
+        }
+    }
+
+    // What the results are filed under on the online leaderboard
+    pub fn board_name(&self) -> Option<&'static str> {
+        match self {
+            Mode::Sprint => Some("sprint"),
+            Mode::DigRace => Some("dig-race"),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use serde::{Deserialize, Serialize};
+use crate::replay::Recorded;
+use super::transport::Connection;
+
+// Bumped whenever a message changes shape, so players on builds that can't understand each other
+// find out in the handshake rather than from garbled messages
+pub const PROTOCOL_VERSION: u16 = 4;
+// the oldest version this build still speaks. Games are played from every input and tick since 3,
+// which the inputs of earlier versions aren't enough for, and 4 can rotate counter-clockwise, which
+// a game on 3 couldn't play along with
+pub const MIN_PROTOCOL_VERSION: u16 = 4;
+
+// Everything a recorded game takes in but pausing, which an online game doesn't do
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Input {
+    LeftPress,
+    LeftRelease,
+    RightPress,
+    RightRelease,
+    SoftDropPress,
+    SoftDropRelease,
+    Rotate,
+    HardDrop,
+    SonicDrop,
+    Hold,
+    UseItem,
+    Tick,
+    RotateCcw,
+}
+
+impl Input {
+    pub fn of(recorded: Recorded) -> Option<Input> {
+        match recorded {
+            Recorded::Tick => Some(Input::Tick),
+            Recorded::LeftPress => Some(Input::LeftPress),
+            Recorded::LeftRelease => Some(Input::LeftRelease),
+            Recorded::RightPress => Some(Input::RightPress),
+            Recorded::RightRelease => Some(Input::RightRelease),
+            Recorded::DownPress => Some(Input::SoftDropPress),
+            Recorded::DownRelease => Some(Input::SoftDropRelease),
+            Recorded::Rotate => Some(Input::Rotate),
+            Recorded::HardDrop => Some(Input::HardDrop),
+            Recorded::SonicDrop => Some(Input::SonicDrop),
+            Recorded::Hold => Some(Input::Hold),
+            Recorded::UseItem => Some(Input::UseItem),
+            Recorded::Pause => None,
+            Recorded::RotateCcw => Some(Input::RotateCcw),
+        }
+    }
+
+    pub fn recorded(&self) -> Recorded {
+        match self {
+            Input::Tick => Recorded::Tick,
+            Input::LeftPress => Recorded::LeftPress,
+            Input::LeftRelease => Recorded::LeftRelease,
+            Input::RightPress => Recorded::RightPress,
+            Input::RightRelease => Recorded::RightRelease,
+            Input::SoftDropPress => Recorded::DownPress,
+            Input::SoftDropRelease => Recorded::DownRelease,
+            Input::Rotate => Recorded::Rotate,
+            Input::HardDrop => Recorded::HardDrop,
+            Input::SonicDrop => Recorded::SonicDrop,
+            Input::Hold => Recorded::Hold,
+            Input::UseItem => Recorded::UseItem,
+            Input::RotateCcw => Recorded::RotateCcw,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RoomControl {
+    Leave,
+    Ready,
+    // both sides play from the same seed, so only the inputs have to go over
+    Start { seed: u64 },
+    Kick { player: u8 },
+}
+
+// Everything netplay sends, encoded with postcard so an input takes a handful of bytes
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Message {
+    // the first thing both sides send, with the range of versions they speak
+    Hello { min_version: u16, max_version: u16, name: String },
+    // the version to go on with, None when the two sides share none
+    Welcome { version: Option<u16> },
+    // `at` is the time on the sender's game clock, in micros
+    Input { at: u64, input: Input },
+    // the sender's own field just took these lines, so the other side's copy of it takes them between
+    // the same two inputs
+    Garbage { lines: u8 },
+    // the sender's own game at a checkpoint piece, for the DesyncDetector on the other side
+    StateHash { piece: u32, hash: u64 },
+    Chat { text: String },
+    Room(RoomControl),
+    // Field and KnockedOut came with version 2, so they only go to rooms that agreed on it.
+    // A field is what net::snapshot compresses, for everyone else's board wall
+    Field { player: u8, cells: Vec<u8> },
+    // `by` is whoever sent the garbage that did it
+    KnockedOut { player: u8, by: Option<u8> },
+    // how the sender's keys move pieces, which the other side's copy of its game has to go by as well
+    Handling { das: u32, arr: u32, soft_drop_factor: u32, tap_only: bool },
+}
+
+impl Message {
+    pub fn hello(name: &str) -> Message {
+        Message::Hello { min_version: MIN_PROTOCOL_VERSION, max_version: PROTOCOL_VERSION, name: name.to_string() }
+    }
+}
+
+// The newest version both this build and the other side speak
+pub fn negotiate(min_version: u16, max_version: u16) -> Option<u16> {
+    let version = max_version.min(PROTOCOL_VERSION);
+    (version >= min_version.max(MIN_PROTOCOL_VERSION)).then_some(version)
+}
+
+pub fn encode(message: &Message) -> Vec<u8> {
+    postcard::to_allocvec(message).unwrap()
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Message, String> {
+    postcard::from_bytes(bytes).map_err(|err| err.to_string())
+}
+
+pub fn send(connection: &mut Connection, message: &Message) -> Result<(), String> {
+    connection.send(&encode(message))
+}
+
+pub fn receive(connection: &mut Connection) -> Result<Message, String> {
+    decode(&connection.receive()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_message_decodes_to_what_was_encoded() {
+        let messages = [
+            Message::hello("Player"),
+            Message::Welcome { version: Some(PROTOCOL_VERSION) },
+            Message::Welcome { version: None },
+            Message::Input { at: 1_234_567, input: Input::HardDrop },
+            Message::Garbage { lines: 4 },
+            Message::StateHash { piece: 30, hash: 0xdead_beef_cafe_f00d },
+            Message::Chat { text: "gg".to_string() },
+            Message::Room(RoomControl::Leave),
+            Message::Room(RoomControl::Ready),
+            Message::Room(RoomControl::Start { seed: u64::MAX }),
+            Message::Room(RoomControl::Kick { player: 1 }),
+            Message::Field { player: 2, cells: vec![0, 7, 200] },
+            Message::KnockedOut { player: 3, by: Some(0) },
+            Message::KnockedOut { player: 3, by: None },
+            Message::Handling { das: 167, arr: 33, soft_drop_factor: 20, tap_only: true },
+        ];
+        for message in messages {
+            assert_eq!(decode(&encode(&message)), Ok(message));
+        }
+    }
+
+    #[test]
+    fn every_input_goes_over_as_what_it_was_recorded_as() {
+        for recorded in Recorded::ALL {
+            match Input::of(recorded) {
+                Some(input) => assert_eq!(input.recorded(), recorded),
+                None => assert_eq!(recorded, Recorded::Pause),
+            }
+        }
+    }
+
+    #[test]
+    fn negotiate_takes_the_newest_shared_version() {
+        assert_eq!(negotiate(1, PROTOCOL_VERSION + 2), Some(PROTOCOL_VERSION));
+        assert_eq!(negotiate(MIN_PROTOCOL_VERSION, MIN_PROTOCOL_VERSION), Some(MIN_PROTOCOL_VERSION));
+    }
+
+    #[test]
+    fn the_handshake_agrees_on_a_version_over_a_connection() {
+        use std::net::TcpListener;
+        use std::thread;
+        use super::super::transport::Transport;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let host = thread::spawn(move || {
+            let mut connection = Connection::accept(listener.accept().unwrap().0).unwrap();
+            let Ok(Message::Hello { min_version, max_version, name }) = receive(&mut connection) else {
+                panic!("the first message wasn't a hello");
+            };
+            send(&mut connection, &Message::Welcome { version: negotiate(min_version, max_version) }).unwrap();
+            name
+        });
+
+        let mut connection = Connection::connect(&address, Transport::Tcp).unwrap();
+        send(&mut connection, &Message::hello("Player")).unwrap();
+        assert_eq!(receive(&mut connection), Ok(Message::Welcome { version: Some(PROTOCOL_VERSION) }));
+        assert_eq!(host.join().unwrap(), "Player");
+    }
+
+    #[test]
+    fn negotiate_rejects_versions_it_doesnt_speak() {
+        assert_eq!(negotiate(PROTOCOL_VERSION + 1, PROTOCOL_VERSION + 2), None);
+        assert_eq!(negotiate(1, MIN_PROTOCOL_VERSION - 1), None);
+    }
+
+    #[test]
+    fn garbage_doesnt_decode() {
+        assert!(decode(&[0xff, 0xff, 0xff]).is_err());
+    }
+}
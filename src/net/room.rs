@@ -0,0 +1,70 @@
+use std::time::{Duration, Instant};
+use super::protocol::Message;
+use super::snapshot::{self, Block};
+
+// how long a knockout stays announced
+const KO_NOTICE: Duration = Duration::from_secs(3);
+
+pub struct RoomPlayer {
+    pub name: String,
+    // the field as of the latest snapshot, row by row from the top
+    pub blocks: Vec<Block>,
+    pub kos: usize,
+    // where they finished, once they're knocked out
+    pub place: Option<usize>,
+}
+
+// What a room shows of everyone in it, kept up to date from the Field and KnockedOut messages alone
+// so it works the same whoever's field is simulated where. For now the only room is a big enough
+// versus match against the CPU, which sends them itself: netplay has the messages but no way to get them
+// from a room of players yet, and the wall doesn't need one
+pub struct BoardWall {
+    pub width: usize,
+    pub height: usize,
+    pub players: Vec<RoomPlayer>,
+    // (player, by) of the latest knockout
+    latest_ko: Option<(usize, Option<usize>, Instant)>,
+}
+
+impl BoardWall {
+    pub fn new(names: Vec<String>, width: usize, height: usize) -> Self {
+        let players = names.into_iter()
+            .map(|name| RoomPlayer { name, blocks: vec![Block::Empty; width * height], kos: 0, place: None })
+            .collect();
+        Self { width, height, players, latest_ko: None }
+    }
+
+    // Messages other than Field and KnockedOut, and ones about players who aren't in the room, are left alone
+    pub fn receive(&mut self, message: &Message) {
+        match message {
+            Message::Field { player, cells } => {
+                let blocks = snapshot::decompress(cells, self.width * self.height);
+                if let (Some(player), Some(blocks)) = (self.players.get_mut(*player as usize), blocks) {
+                    player.blocks = blocks;
+                }
+            },
+            Message::KnockedOut { player, by } => {
+                let (player, by) = (*player as usize, by.map(usize::from).filter(|by| *by < self.players.len()));
+                let place = self.players_left();
+                let Some(knocked_out) = self.players.get_mut(player).filter(|knocked_out| knocked_out.place.is_none()) else {
+                    return;
+                };
+                knocked_out.place = Some(place);
+                if let Some(by) = by {
+                    self.players[by].kos += 1;
+                }
+                self.latest_ko = Some((player, by, Instant::now()));
+            },
+            _ => {},
+        }
+    }
+
+    pub fn players_left(&self) -> usize {
+        self.players.iter().filter(|player| player.place.is_none()).count()
+    }
+
+    // (player, by) of a knockout recent enough to still be announced
+    pub fn latest_knockout(&self) -> Option<(usize, Option<usize>)> {
+        self.latest_ko.filter(|(_, _, at)| at.elapsed() < KO_NOTICE).map(|(player, by, _)| (player, by))
+    }
+}
@@ -0,0 +1,53 @@
+use crate::game::Tetris;
+
+// the most of the same block one byte can count
+const MAX_RUN: usize = 0x3f;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Block {
+    Empty,
+    Stack,
+    Garbage,
+}
+
+impl Block {
+    const ALL: [Block; 3] = [Block::Empty, Block::Stack, Block::Garbage];
+}
+
+// The field row by row from the top, as runs of the same block: a byte each, with the block in the
+// top two bits and how many of it in the rest. A field that's mostly empty takes a few dozen bytes
+pub fn compress<const W: usize, const H: usize>(tetris: &Tetris<W, H>) -> Vec<u8> {
+    let blocks = tetris.field.iter().flatten().map(|cell| match cell {
+        None => Block::Empty,
+        Some(cell) if cell.garbage => Block::Garbage,
+        Some(_) => Block::Stack,
+    });
+
+    let mut bytes = vec![];
+    let mut run: Option<(Block, usize)> = None;
+    for block in blocks {
+        run = match run {
+            Some((same, len)) if same == block && len < MAX_RUN => Some((same, len + 1)),
+            _ => {
+                bytes.extend(run.map(encode));
+                Some((block, 1))
+            },
+        };
+    }
+    bytes.extend(run.map(encode));
+    bytes
+}
+
+fn encode((block, len): (Block, usize)) -> u8 {
+    (Block::ALL.iter().position(|other| *other == block).unwrap() as u8) << 6 | len as u8
+}
+
+// None when the bytes don't add up to exactly `cells` blocks
+pub fn decompress(bytes: &[u8], cells: usize) -> Option<Vec<Block>> {
+    let mut blocks = Vec::with_capacity(cells);
+    for byte in bytes {
+        let block = *Block::ALL.get((byte >> 6) as usize)?;
+        blocks.extend(std::iter::repeat_n(block, (byte & 0x3f) as usize));
+    }
+    (blocks.len() == cells).then_some(blocks)
+}
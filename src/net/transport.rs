@@ -0,0 +1,320 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+// a WebSocket handshake answers the client's key with it, hashed together with this
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+// bigger messages than this aren't netplay, whoever sent them
+const MAX_MESSAGE: usize = 1 << 20;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Transport {
+    // every message goes with its length in front, as 4 big-endian bytes
+    Tcp,
+    // every message is a binary frame, so players in a browser can join the ones running the game
+    WebSocket,
+}
+
+impl Transport {
+    pub const ALL: [Transport; 2] = [Transport::Tcp, Transport::WebSocket];
+}
+
+// One side of a netplay connection, carrying whole messages either way whatever the transport
+pub struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    pub transport: Transport,
+    // clients have to mask what they send over WebSocket, servers must not
+    is_client: bool,
+}
+
+impl Connection {
+    // `host:port`, which WebSocket also takes as `ws://host:port/path` for servers that want a path
+    pub fn connect(address: &str, transport: Transport) -> Result<Connection, String> {
+        let address = address.strip_prefix("ws://").unwrap_or(address);
+        let (host, path) = match address.find('/') {
+            Some(idx) => (&address[..idx], &address[idx..]),
+            None => (address, "/"),
+        };
+        let socket = host.to_socket_addrs().map_err(|err| err.to_string())?
+            .next()
+            .ok_or("the host has no address")?;
+        let stream = TcpStream::connect_timeout(&socket, TIMEOUT).map_err(|err| err.to_string())?;
+        let mut connection = Connection::new(stream, transport, true)?;
+        connection.set_handshake_timeout(Some(TIMEOUT))?;
+
+        if transport == Transport::WebSocket {
+            let key = base64(&rand::random::<[u8; 16]>());
+            let request = format!(
+                "GET {path} HTTP/1.1\r\nHost: {host}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {key}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            );
+            connection.writer.write_all(request.as_bytes()).map_err(|err| err.to_string())?;
+            let (status, headers) = connection.read_head()?;
+            if status.split_whitespace().nth(1) != Some("101") {
+                return Err(format!("the server answered `{status}`"));
+            }
+            if header(&headers, "sec-websocket-accept") != Some(accept_key(&key).as_str()) {
+                return Err("the server answered the handshake wrong".to_string());
+            }
+        }
+        connection.set_handshake_timeout(None)?;
+        Ok(connection)
+    }
+
+    // Takes either transport on the same port: a WebSocket client opens with its HTTP handshake,
+    // anything else is raw TCP. Either way it waits for the client to send something first, for as
+    // long as a handshake gets
+    pub fn accept(stream: TcpStream) -> Result<Connection, String> {
+        let mut connection = Connection::new(stream, Transport::Tcp, false)?;
+        connection.set_handshake_timeout(Some(TIMEOUT))?;
+        connection.answer_handshake()?;
+        connection.set_handshake_timeout(None)?;
+        Ok(connection)
+    }
+
+    fn answer_handshake(&mut self) -> Result<(), String> {
+        let opening = self.reader.fill_buf().map_err(|err| err.to_string())?;
+        if !opening.starts_with(b"GET ") {
+            return Ok(());
+        }
+
+        let (_, headers) = self.read_head()?;
+        let key = header(&headers, "sec-websocket-key").ok_or("the client sent no WebSocket key")?;
+        let response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {accept}\r\n\r\n",
+            accept = accept_key(key),
+        );
+        self.writer.write_all(response.as_bytes()).map_err(|err| err.to_string())?;
+        self.transport = Transport::WebSocket;
+        Ok(())
+    }
+
+    // Once the handshake is done, waiting for the other side's messages is up to whoever has the connection
+    fn set_handshake_timeout(&self, timeout: Option<Duration>) -> Result<(), String> {
+        self.writer.set_read_timeout(timeout).map_err(|err| err.to_string())
+    }
+
+    fn new(stream: TcpStream, transport: Transport, is_client: bool) -> Result<Connection, String> {
+        stream.set_nodelay(true).map_err(|err| err.to_string())?;
+        let writer = stream.try_clone().map_err(|err| err.to_string())?;
+        Ok(Connection { reader: BufReader::new(stream), writer, transport, is_client })
+    }
+
+    // The status line and the headers, lowercased, of an HTTP handshake
+    fn read_head(&mut self) -> Result<(String, Vec<(String, String)>), String> {
+        let mut lines = vec![];
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line).map_err(|err| err.to_string())? == 0 {
+                return Err("the connection closed during the handshake".to_string());
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            lines.push(line.to_string());
+        }
+        let status = if lines.is_empty() {String::new()} else {lines.remove(0)};
+        let headers = lines.iter()
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+            .collect();
+        Ok((status, headers))
+    }
+
+    pub fn send(&mut self, message: &[u8]) -> Result<(), String> {
+        let mut data = vec![];
+        match self.transport {
+            Transport::Tcp => data.extend((message.len() as u32).to_be_bytes()),
+            Transport::WebSocket => data.extend(self.frame_head(0x2, message.len())),
+        }
+        if self.transport == Transport::WebSocket && self.is_client {
+            let mask: [u8; 4] = rand::random();
+            data.extend(mask);
+            data.extend(message.iter().enumerate().map(|(idx, byte)| byte ^ mask[idx % 4]));
+        } else {
+            data.extend(message);
+        }
+        self.writer.write_all(&data).map_err(|err| err.to_string())
+    }
+
+    fn frame_head(&self, opcode: u8, len: usize) -> Vec<u8> {
+        let mask_bit = if self.is_client {0x80} else {0};
+        let mut head = vec![0x80 | opcode];
+        match len {
+            0..=125 => head.push(mask_bit | len as u8),
+            126..=0xffff => {
+                head.push(mask_bit | 126);
+                head.extend((len as u16).to_be_bytes());
+            },
+            _ => {
+                head.push(mask_bit | 127);
+                head.extend((len as u64).to_be_bytes());
+            },
+        }
+        head
+    }
+
+    // Waits for the next whole message. Pings get answered on the way
+    pub fn receive(&mut self) -> Result<Vec<u8>, String> {
+        if self.transport == Transport::Tcp {
+            let len = u32::from_be_bytes(self.read_bytes(4)?.try_into().unwrap()) as usize;
+            if len > MAX_MESSAGE {
+                return Err(format!("a message of {len} bytes is too big"));
+            }
+            return self.read_bytes(len);
+        }
+
+        // a message can come split over several frames, all but the last without the FIN bit
+        let mut message = vec![];
+        loop {
+            let head = self.read_bytes(2)?;
+            let (fin, opcode, masked) = (head[0] & 0x80 != 0, head[0] & 0x0f, head[1] & 0x80 != 0);
+            let len = match head[1] & 0x7f {
+                126 => u16::from_be_bytes(self.read_bytes(2)?.try_into().unwrap()) as usize,
+                127 => u64::from_be_bytes(self.read_bytes(8)?.try_into().unwrap()) as usize,
+                len => len as usize,
+            };
+            if len > MAX_MESSAGE - message.len() {
+                return Err(format!("a message of {len} bytes is too big"));
+            }
+            let mask = if masked {self.read_bytes(4)?} else {vec![0; 4]};
+            let payload: Vec<u8> = self.read_bytes(len)?.iter().enumerate().map(|(idx, byte)| byte ^ mask[idx % 4]).collect();
+
+            match opcode {
+                0x8 => return Err("the other side closed the connection".to_string()),
+                0x9 => {
+                    let mut pong = self.frame_head(0xa, payload.len());
+                    if self.is_client {
+                        pong.extend([0; 4]);
+                    }
+                    pong.extend(&payload);
+                    self.writer.write_all(&pong).map_err(|err| err.to_string())?;
+                },
+                0xa => {},
+                _ => {
+                    message.extend(payload);
+                    if fin {
+                        return Ok(message);
+                    }
+                },
+            }
+        }
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>, String> {
+        let mut bytes = vec![0; len];
+        self.reader.read_exact(&mut bytes).map_err(|err| err.to_string())?;
+        Ok(bytes)
+    }
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers.iter().find(|(other, _)| other == name).map(|(_, value)| value.as_str())
+}
+
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{key}{WEBSOCKET_GUID}").as_bytes()))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::new();
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (idx, byte)| bits | (*byte as u32) << (16 - 8 * idx));
+        for idx in 0..4 {
+            text.push(if idx <= chunk.len() {ALPHABET[(bits >> (18 - 6 * idx) & 0x3f) as usize] as char} else {'='});
+        }
+    }
+    text
+}
+
+// Only ever hashes handshake keys, so it's spelled out here rather than pulled in
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    let mut padded = data.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend((data.len() as u64 * 8).to_be_bytes());
+
+    for block in padded.chunks(64) {
+        let mut words = [0u32; 80];
+        for idx in 0..80 {
+            words[idx] = if idx < 16 {
+                u32::from_be_bytes(block[idx * 4..idx * 4 + 4].try_into().unwrap())
+            } else {
+                (words[idx - 3] ^ words[idx - 8] ^ words[idx - 14] ^ words[idx - 16]).rotate_left(1)
+            };
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (idx, word) in words.iter().enumerate() {
+            let (f, k) = match idx {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+            (e, d, c, b, a) = (d, c, b.rotate_left(30), a, temp);
+        }
+        for (part, value) in state.iter_mut().zip([a, b, c, d, e]) {
+            *part = part.wrapping_add(value);
+        }
+    }
+
+    let mut hash = [0; 20];
+    for (idx, part) in state.iter().enumerate() {
+        hash[idx * 4..idx * 4 + 4].copy_from_slice(&part.to_be_bytes());
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::thread;
+    use super::*;
+
+    #[test]
+    fn accept_key_matches_the_rfc() {
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn messages_come_back_whole_over_either_transport() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // one that fits a short WebSocket frame, and ones that need the 16 and 64 bit lengths
+        let messages: Vec<Vec<u8>> = [3, 300, 70_000].iter().map(|len| (0..*len).map(|idx| idx as u8).collect()).collect();
+        let echoes = messages.len();
+        let server = thread::spawn(move || {
+            for transport in Transport::ALL {
+                let mut connection = Connection::accept(listener.accept().unwrap().0).unwrap();
+                assert_eq!(connection.transport, transport);
+                for _ in 0..echoes {
+                    let message = connection.receive().unwrap();
+                    connection.send(&message).unwrap();
+                }
+            }
+        });
+
+        for (transport, address) in Transport::ALL.into_iter().zip([format!("127.0.0.1:{port}"), format!("ws://127.0.0.1:{port}/play")]) {
+            let mut connection = Connection::connect(&address, transport).unwrap();
+            for message in messages.iter() {
+                connection.send(message).unwrap();
+                assert_eq!(&connection.receive().unwrap(), message);
+            }
+        }
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn a_client_that_says_nothing_gives_up_the_handshake() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let _silent = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        assert!(Connection::accept(listener.accept().unwrap().0).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+const TOP_ENTRIES: usize = 5;
+
+// Talks to the leaderboard server in `leaderboard_url`, plain http only.
+// POST <url> with `board=<board>&score=<score>&time=<millis>&hash=<state hash>` submits a result,
+// GET <url>?board=<board>&top=<n> answers with one `score millis` line per entry, best first.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub score: usize,
+    pub time: Duration,
+}
+
+// Sends the result off in the background, a server that can't be reached only gets reported
+pub fn submit(url: &str, board: &str, entry: &Entry, hash: u64) {
+    let url = url.to_string();
+    let body = format!("board={board}&score={score}&time={time}&hash={hash:016x}", score = entry.score, time = entry.time.as_millis());
+    thread::spawn(move || {
+        if let Err(err) = request(&url, "POST", &body) {
+            eprintln!("can't submit to the leaderboard at {url}: {err}");
+        }
+    });
+}
+
+// The answer shows up on the receiver once the server has sent it
+pub fn fetch_top(url: &str, board: &str) -> Receiver<Result<Vec<Entry>, String>> {
+    let (sender, receiver) = mpsc::channel();
+    let url = format!("{url}?board={board}&top={TOP_ENTRIES}");
+    thread::spawn(move || {
+        let entries = request(&url, "GET", "").map(|body| body.lines().filter_map(parse_entry).collect());
+        let _ = sender.send(entries);
+    });
+    receiver
+}
+
+fn parse_entry(line: &str) -> Option<Entry> {
+    let (score, time) = line.trim().split_once(' ')?;
+    Some(Entry { score: score.parse().ok()?, time: Duration::from_millis(time.trim().parse().ok()?) })
+}
+
+fn request(url: &str, method: &str, body: &str) -> Result<String, String> {
+    let rest = url.strip_prefix("http://").ok_or("only http:// addresses are supported")?;
+    let (host, path) = match rest.find(['/', '?']) {
+        Some(idx) if rest[idx..].starts_with('/') => (&rest[..idx], rest[idx..].to_string()),
+        Some(idx) => (&rest[..idx], format!("/{query}", query = &rest[idx..])),
+        None => (rest, "/".to_string()),
+    };
+    let address = if host.contains(':') {host.to_string()} else {format!("{host}:80")};
+    let address = address.to_socket_addrs().map_err(|err| err.to_string())?
+        .next()
+        .ok_or("the host has no address")?;
+
+    let mut stream = TcpStream::connect_timeout(&address, TIMEOUT).map_err(|err| err.to_string())?;
+    stream.set_read_timeout(Some(TIMEOUT)).map_err(|err| err.to_string())?;
+    stream.set_write_timeout(Some(TIMEOUT)).map_err(|err| err.to_string())?;
+
+    // HTTP/1.0 keeps servers from answering in chunks
+    let request = format!(
+        "{method} {path} HTTP/1.0\r\nHost: {host}\r\nContent-Type: application/x-www-form-urlencoded\r\nContent-Length: {length}\r\n\r\n{body}",
+        length = body.len(),
+    );
+    stream.write_all(request.as_bytes()).map_err(|err| err.to_string())?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).map_err(|err| err.to_string())?;
+    let (head, body) = response.split_once("\r\n\r\n").ok_or("the server sent a broken response")?;
+    let status = head.lines().next().unwrap_or_default();
+    if status.split_whitespace().nth(1) != Some("200") {
+        return Err(format!("the server answered `{status}`"));
+    }
+    Ok(body.to_string())
+}
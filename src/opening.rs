@@ -0,0 +1,93 @@
+use crate::game::{Tetris, Tetromino};
+
+pub static OPENINGS: std::sync::LazyLock<Vec<Opening>> = std::sync::LazyLock::new(|| {
+    parse(include_str!("../assets/openings.txt"))
+});
+
+pub struct Opening {
+    pub name: String,
+    pub pieces: Vec<Tetromino>,
+    // (x, rows from the bottom of the field)
+    cells: Vec<(usize, usize)>,
+}
+
+pub struct Progress {
+    pub missing: Vec<(usize, usize)>,
+    pub misplaced: Vec<(usize, usize)>,
+    pub is_complete: bool,
+}
+
+impl Opening {
+    pub fn queue(&self) -> Vec<Tetromino> {
+        self.pieces.clone()
+    }
+
+    pub fn setup_pieces(&self) -> usize {
+        self.cells.len() / 4
+    }
+
+    pub fn progress<const W: usize, const H: usize>(&self, tetris: &Tetris<W, H>) -> Progress {
+        let target: Vec<(usize, usize)> = self.cells.iter()
+            .filter(|(x, row)| *x < W && *row < H)
+            .map(|(x, row)| (*x, H - 1 - row))
+            .collect();
+
+        let missing = target.iter()
+            .filter(|(x, y)| tetris.field[*y][*x].is_none())
+            .copied()
+            .collect();
+
+        let mut misplaced = vec![];
+        for y in 0..H {
+            for x in 0..W {
+                if tetris.field[y][x].is_some() && !target.contains(&(x, y)) {
+                    misplaced.push((x, y));
+                }
+            }
+        }
+
+        Progress {
+            missing,
+            misplaced,
+            is_complete: tetris.pieces >= self.setup_pieces(),
+        }
+    }
+}
+
+fn parse(data: &str) -> Vec<Opening> {
+    let mut openings = vec![];
+    let mut rows: Vec<&str> = vec![];
+
+    let finish = |openings: &mut Vec<Opening>, rows: &mut Vec<&str>| {
+        if let Some(opening) = openings.last_mut() {
+            for (row, line) in rows.iter().rev().enumerate() {
+                for (x, cell) in line.chars().enumerate() {
+                    if cell != '.' {
+                        opening.cells.push((x, row));
+                    }
+                }
+            }
+        }
+        rows.clear();
+    };
+
+    for line in data.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            finish(&mut openings, &mut rows);
+            openings.push(Opening { name: name.to_string(), pieces: vec![], cells: vec![] });
+        } else if let Some(pieces) = line.strip_prefix("pieces") {
+            if let Some(opening) = openings.last_mut() {
+                opening.pieces = pieces.trim_start_matches([' ', '=']).chars().filter_map(Tetromino::from_char).collect();
+            }
+        } else {
+            rows.push(line);
+        }
+    }
+    finish(&mut openings, &mut rows);
+
+    openings
+}
@@ -0,0 +1,141 @@
+use std::collections::HashSet;
+use std::fs;
+use fltk::enums::Color;
+
+const PIECE_SETS_FILE: &str = "piece_sets.txt";
+pub const MAX_CELLS: usize = 8;
+// how far a cell can be from the centre, so pieces always fit where they spawn
+const MAX_OFFSET: i32 = 2;
+
+// The built-in sets followed by the ones from piece_sets.txt next to the game
+pub static PIECE_SETS: std::sync::LazyLock<Vec<CustomPieceSet>> = std::sync::LazyLock::new(|| {
+    let mut sets = parse("assets/piece_sets.txt", include_str!("../assets/piece_sets.txt"));
+    if let Ok(data) = fs::read_to_string(PIECE_SETS_FILE) {
+        sets.extend(parse(PIECE_SETS_FILE, &data));
+    }
+    sets
+});
+
+pub struct CustomPiece {
+    pub cells: Vec<(i32, i32)>,
+    pub color: Color,
+    // clockwise quarter turns it spawns with
+    pub turns: usize,
+}
+
+pub struct CustomPieceSet {
+    pub name: String,
+    pub pieces: Vec<CustomPiece>,
+}
+
+impl CustomPiece {
+    fn problem(&self) -> Option<String> {
+        if self.cells.is_empty() || self.cells.len() > MAX_CELLS {
+            return Some(format!("a piece needs 1 to {MAX_CELLS} cells"));
+        }
+        if self.cells.iter().any(|(dx, dy)| dx.abs() > MAX_OFFSET || dy.abs() > MAX_OFFSET) {
+            return Some(format!("cells can't be more than {MAX_OFFSET} away from the centre"));
+        }
+
+        let cells: HashSet<(i32, i32)> = self.cells.iter().copied().collect();
+        if cells.len() != self.cells.len() {
+            return Some("the same cell is listed twice".to_string());
+        }
+
+        // every cell has to be reachable from the first one through its neighbours
+        let mut reached = HashSet::from([self.cells[0]]);
+        let mut queue = vec![self.cells[0]];
+        while let Some((x, y)) = queue.pop() {
+            for next in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                if cells.contains(&next) && reached.insert(next) {
+                    queue.push(next);
+                }
+            }
+        }
+        if reached.len() != cells.len() {
+            return Some("cells have to touch each other".to_string());
+        }
+        None
+    }
+}
+
+// Pieces and sets that don't pass validation are reported and left out
+fn parse(file: &str, data: &str) -> Vec<CustomPieceSet> {
+    let mut sets: Vec<CustomPieceSet> = vec![];
+    // where every piece of the set being read starts, for reporting it
+    let mut lines_of_pieces: Vec<usize> = vec![];
+
+    let finish = |sets: &mut Vec<CustomPieceSet>, lines_of_pieces: &mut Vec<usize>| {
+        if let Some(set) = sets.last_mut() {
+            let mut idx = 0;
+            set.pieces.retain(|piece| {
+                let problem = piece.problem();
+                if let Some(problem) = problem.as_ref() {
+                    eprintln!("{file}:{line}: {problem}", line = lines_of_pieces[idx]);
+                }
+                idx += 1;
+                problem.is_none()
+            });
+            if set.pieces.is_empty() {
+                eprintln!("{file}: piece set `{name}` has no usable pieces", name = set.name);
+                sets.pop();
+            }
+        }
+        lines_of_pieces.clear();
+    };
+
+    for (number, line) in data.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            finish(&mut sets, &mut lines_of_pieces);
+            sets.push(CustomPieceSet { name: name.to_string(), pieces: vec![] });
+            continue;
+        }
+
+        let (Some(set), Some((key, value))) = (sets.last_mut(), line.split_once('=')) else {
+            eprintln!("{file}:{line}: expected `[set name]` or `key = value`", line = number + 1);
+            continue;
+        };
+        let value = value.trim();
+
+        let parsed = if key.trim() == "piece" {
+            parse_cells(value).map(|cells| {
+                set.pieces.push(CustomPiece { cells, color: Color::White, turns: 0 });
+                lines_of_pieces.push(number + 1);
+            })
+        } else {
+            match (key.trim(), set.pieces.last_mut()) {
+                ("color", Some(piece)) => parse_color(value).map(|color| piece.color = color),
+                ("turns", Some(piece)) => value.parse().ok().filter(|turns| *turns < 4).map(|turns| piece.turns = turns),
+                _ => None,
+            }
+        };
+        if parsed.is_none() {
+            eprintln!("{file}:{line}: can't use `{line_text}`", line = number + 1, line_text = line);
+        }
+    }
+    finish(&mut sets, &mut lines_of_pieces);
+
+    sets
+}
+
+fn parse_cells(value: &str) -> Option<Vec<(i32, i32)>> {
+    value.split_whitespace()
+        .map(|cell| {
+            let (dx, dy) = cell.split_once(',')?;
+            Some((dx.trim().parse().ok()?, dy.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+fn parse_color(value: &str) -> Option<Color> {
+    let parts: Vec<u8> = value.split(',').map(|part| part.trim().parse().ok()).collect::<Option<_>>()?;
+    let [r, g, b] = parts.as_slice() else {
+        return None;
+    };
+    Some(Color::from_rgb(*r, *g, *b))
+}
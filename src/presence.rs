@@ -0,0 +1,160 @@
+use std::sync::OnceLock;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+// Discord takes 5 updates every 20 seconds, anything faster gets dropped
+pub const UPDATE_INTERVAL: Duration = Duration::from_secs(5);
+
+// What Discord shows on the player's profile while they play. Only the backend reads it, and there
+// is no backend without the `discord` feature
+#[cfg_attr(not(feature = "discord"), allow(dead_code))]
+pub struct Activity {
+    pub details: String,
+    pub state: String,
+    // time played so far, Discord counts on from it. None while the clock is stopped
+    pub elapsed: Option<Duration>,
+}
+
+static ACTIVITY: OnceLock<Sender<Option<Activity>>> = OnceLock::new();
+
+// Starts talking to the Discord app on this computer as the application `app_id`. Without the
+// `discord` feature it does nothing, and neither does `show`
+pub fn start(app_id: &str) {
+    if let Some(sender) = backend::start(app_id.to_string()) {
+        let _ = ACTIVITY.set(sender);
+    }
+}
+
+// None takes the activity off the profile
+pub fn show(activity: Option<Activity>) {
+    if let Some(sender) = ACTIVITY.get() {
+        let _ = sender.send(activity);
+    }
+}
+
+#[cfg(feature = "discord")]
+mod backend {
+    use std::io::{self, Read, Write};
+    use std::sync::mpsc::{self, Sender};
+    use std::thread;
+    use std::time::SystemTime;
+    use super::Activity;
+
+    const OP_HANDSHAKE: u32 = 0;
+    const OP_FRAME: u32 = 1;
+
+    trait Pipe: Read + Write + Send {}
+
+    impl<T: Read + Write + Send> Pipe for T {}
+
+    pub fn start(app_id: String) -> Option<Sender<Option<Activity>>> {
+        let (sender, receiver) = mpsc::channel::<Option<Activity>>();
+
+        thread::spawn(move || {
+            let mut pipe: Option<Box<dyn Pipe>> = None;
+            let mut nonce = 0;
+            while let Ok(activity) = receiver.recv() {
+                // only the newest one matters if a few piled up
+                let activity = receiver.try_iter().last().unwrap_or(activity);
+                // Discord might have been started, or restarted, since the last try
+                if pipe.is_none() {
+                    pipe = connect(&app_id);
+                }
+                let Some(connection) = pipe.as_mut() else {
+                    continue;
+                };
+                nonce += 1;
+                let command = format!(
+                    "{{\"cmd\":\"SET_ACTIVITY\",\"args\":{{\"pid\":{pid},\"activity\":{activity}}},\"nonce\":\"{nonce}\"}}",
+                    pid = std::process::id(),
+                    activity = activity.as_ref().map_or("null".to_string(), to_json),
+                );
+                // Discord answers every command, which has to be read so the pipe doesn't fill up
+                if write_frame(connection, OP_FRAME, &command).and_then(|_| read_frame(connection)).is_err() {
+                    pipe = None;
+                }
+            }
+        });
+
+        Some(sender)
+    }
+
+    fn connect(app_id: &str) -> Option<Box<dyn Pipe>> {
+        let mut pipe = open()?;
+        write_frame(&mut pipe, OP_HANDSHAKE, &format!("{{\"v\":1,\"client_id\":\"{}\"}}", escape(app_id))).ok()?;
+        // READY comes back as a frame, a rejected handshake as a close
+        let (op, _) = read_frame(&mut pipe).ok()?;
+        (op == OP_FRAME).then_some(pipe)
+    }
+
+    // Discord listens on the first free one of discord-ipc-0 to discord-ipc-9
+    #[cfg(unix)]
+    fn open() -> Option<Box<dyn Pipe>> {
+        let dirs = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"].iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .chain(["/tmp".to_string()]);
+        for dir in dirs {
+            for idx in 0..10 {
+                if let Ok(stream) = std::os::unix::net::UnixStream::connect(format!("{dir}/discord-ipc-{idx}")) {
+                    return Some(Box::new(stream));
+                }
+            }
+        }
+        None
+    }
+
+    #[cfg(windows)]
+    fn open() -> Option<Box<dyn Pipe>> {
+        (0..10)
+            .find_map(|idx| std::fs::OpenOptions::new().read(true).write(true).open(format!(r"\\.\pipe\discord-ipc-{idx}")).ok())
+            .map(|pipe| Box::new(pipe) as Box<dyn Pipe>)
+    }
+
+    // every frame is its opcode and length, both as 4 little-endian bytes, then that much JSON
+    fn write_frame(pipe: &mut Box<dyn Pipe>, op: u32, json: &str) -> io::Result<()> {
+        let mut frame = vec![];
+        frame.extend(op.to_le_bytes());
+        frame.extend((json.len() as u32).to_le_bytes());
+        frame.extend(json.as_bytes());
+        pipe.write_all(&frame)
+    }
+
+    fn read_frame(pipe: &mut Box<dyn Pipe>) -> io::Result<(u32, Vec<u8>)> {
+        let mut head = [0; 8];
+        pipe.read_exact(&mut head)?;
+        let op = u32::from_le_bytes(head[..4].try_into().unwrap());
+        let mut json = vec![0; u32::from_le_bytes(head[4..].try_into().unwrap()) as usize];
+        pipe.read_exact(&mut json)?;
+        Ok((op, json))
+    }
+
+    fn to_json(activity: &Activity) -> String {
+        // Discord wants the moment the clock started, rather than how long it has been going
+        let timestamps = activity.elapsed.and_then(|elapsed| SystemTime::now().checked_sub(elapsed))
+            .and_then(|start| start.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map_or(String::new(), |start| format!(",\"timestamps\":{{\"start\":{}}}", start.as_secs()));
+        format!("{{\"details\":\"{}\",\"state\":\"{}\"{timestamps}}}", escape(&activity.details), escape(&activity.state))
+    }
+
+    fn escape(text: &str) -> String {
+        text.chars().fold(String::new(), |mut escaped, char| {
+            match char {
+                '"' => escaped.push_str("\\\""),
+                '\\' => escaped.push_str("\\\\"),
+                char if char.is_control() => escaped.push_str(&format!("\\u{:04x}", char as u32)),
+                char => escaped.push(char),
+            }
+            escaped
+        })
+    }
+}
+
+#[cfg(not(feature = "discord"))]
+mod backend {
+    use std::sync::mpsc::Sender;
+    use super::Activity;
+
+    pub fn start(_app_id: String) -> Option<Sender<Option<Activity>>> {
+        None
+    }
+}
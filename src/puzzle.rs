@@ -0,0 +1,162 @@
+use std::collections::HashSet;
+use std::fs;
+use fltk::enums::Color;
+use crate::game::{Cell, GameEvent, GameState, Tetris, Tetromino};
+use crate::locale::{tr, tr_with};
+use crate::rules::{ClearGravity, RuleSet};
+use crate::save::save_or_report;
+
+pub static PUZZLES: std::sync::LazyLock<Vec<Puzzle>> = std::sync::LazyLock::new(|| {
+    parse(include_str!("../assets/puzzles.txt"))
+});
+
+const BOARD_COLOR: Color = Color::from_rgb(110, 110, 110);
+const PROGRESS_FILE: &str = "puzzle_progress.txt";
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Goal {
+    ClearBoard,
+    TSpin(usize),
+}
+
+impl Goal {
+    pub fn description(&self) -> String {
+        match self {
+            Goal::ClearBoard => tr("goal.clear_board").to_string(),
+            Goal::TSpin(0) => tr("goal.t_spin").to_string(),
+            Goal::TSpin(1) => tr("goal.t_spin_single").to_string(),
+            Goal::TSpin(2) => tr("goal.t_spin_double").to_string(),
+            Goal::TSpin(lines) => tr_with("goal.t_spin_lines", &[("lines", lines)]),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Playing,
+    Solved,
+    Failed,
+}
+
+pub struct Puzzle {
+    pub name: String,
+    pub goal: Goal,
+    pub pieces: Vec<Tetromino>,
+    pub gravity: ClearGravity,
+    // (x, rows from the bottom of the field)
+    cells: Vec<(usize, usize)>,
+}
+
+impl Puzzle {
+    pub fn to_tetris<const W: usize, const H: usize>(&self) -> Tetris<W, H> {
+        let mut field = [[None; W]; H];
+        for (x, row) in self.cells.iter().filter(|(x, row)| *x < W && *row < H) {
+            field[H - 1 - row][*x] = Some(Cell::block(BOARD_COLOR));
+        }
+        Tetris::with_setup(field, self.pieces.clone(), RuleSet { clear_gravity: self.gravity, ..RuleSet::default() })
+    }
+
+    pub fn pieces_left<const W: usize, const H: usize>(&self, tetris: &Tetris<W, H>) -> usize {
+        self.pieces.len().saturating_sub(tetris.pieces)
+    }
+
+    pub fn is_solved_by(&self, event: &GameEvent) -> bool {
+        match (self.goal, event) {
+            (Goal::ClearBoard, GameEvent::PerfectClear) => true,
+            (Goal::TSpin(goal), GameEvent::TSpin(lines)) => *lines >= goal,
+            _ => false,
+        }
+    }
+
+    pub fn is_failed<const W: usize, const H: usize>(&self, tetris: &Tetris<W, H>) -> bool {
+        matches!(tetris.state, GameState::LOST) || self.pieces_left(tetris) == 0
+    }
+}
+
+// Names of the solved puzzles, kept next to the game between runs
+pub struct PuzzleProgress {
+    solved: HashSet<String>,
+}
+
+impl PuzzleProgress {
+    pub fn load() -> Self {
+        let solved = fs::read_to_string(PROGRESS_FILE).unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        Self { solved }
+    }
+
+    pub fn is_solved(&self, puzzle: &Puzzle) -> bool {
+        self.solved.contains(&puzzle.name)
+    }
+
+    pub fn solved_count(&self) -> usize {
+        PUZZLES.iter().filter(|puzzle| self.is_solved(puzzle)).count()
+    }
+
+    pub fn mark_solved(&mut self, puzzle: &Puzzle) {
+        if !self.solved.insert(puzzle.name.clone()) {
+            return;
+        }
+
+        let mut names: Vec<&str> = self.solved.iter().map(String::as_str).collect();
+        names.sort();
+        save_or_report(PROGRESS_FILE, names.join("\n"));
+    }
+}
+
+fn parse(data: &str) -> Vec<Puzzle> {
+    let mut puzzles = vec![];
+    let mut rows: Vec<&str> = vec![];
+
+    let finish = |puzzles: &mut Vec<Puzzle>, rows: &mut Vec<&str>| {
+        if let Some(puzzle) = puzzles.last_mut() {
+            for (row, line) in rows.iter().rev().enumerate() {
+                for (x, cell) in line.chars().enumerate() {
+                    if cell != '.' {
+                        puzzle.cells.push((x, row));
+                    }
+                }
+            }
+        }
+        rows.clear();
+    };
+
+    for line in data.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            finish(&mut puzzles, &mut rows);
+            puzzles.push(Puzzle { name: name.to_string(), goal: Goal::ClearBoard, pieces: vec![], gravity: ClearGravity::Naive, cells: vec![] });
+        } else if let Some(pieces) = line.strip_prefix("pieces") {
+            if let Some(puzzle) = puzzles.last_mut() {
+                puzzle.pieces = pieces.trim_start_matches([' ', '=']).chars().filter_map(Tetromino::from_char).collect();
+            }
+        } else if let Some(gravity) = line.strip_prefix("gravity") {
+            if let Some(puzzle) = puzzles.last_mut() {
+                puzzle.gravity = match gravity.trim_start_matches([' ', '=']) {
+                    "sticky" => ClearGravity::Sticky,
+                    "cascade" => ClearGravity::Cascade,
+                    _ => ClearGravity::Naive,
+                };
+            }
+        } else if let Some(goal) = line.strip_prefix("goal") {
+            if let Some(puzzle) = puzzles.last_mut() {
+                let goal: Vec<&str> = goal.trim_start_matches([' ', '=']).split_whitespace().collect();
+                puzzle.goal = match goal.as_slice() {
+                    ["tspin"] => Goal::TSpin(0),
+                    ["tspin", lines] => Goal::TSpin(lines.parse().unwrap()),
+                    _ => Goal::ClearBoard,
+                };
+            }
+        } else {
+            rows.push(line);
+        }
+    }
+    finish(&mut puzzles, &mut rows);
+
+    puzzles
+}
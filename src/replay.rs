@@ -0,0 +1,266 @@
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use crate::game::{Handling, Tetris};
+use crate::mode::Mode;
+use crate::rules::RuleSet;
+use crate::save::save_or_report;
+
+const MAGIC: &[u8; 4] = b"RPLY";
+const VERSION: u8 = 2;
+
+// Everything that gets to a recorded game from the outside, ticks included, since how far apart
+// they came shapes the game as much as the keys do
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Recorded {
+    Tick,
+    LeftPress,
+    LeftRelease,
+    RightPress,
+    RightRelease,
+    DownPress,
+    DownRelease,
+    Rotate,
+    HardDrop,
+    SonicDrop,
+    Hold,
+    UseItem,
+    Pause,
+    RotateCcw,
+}
+
+impl Recorded {
+    // a replay has an input as its place in here, so new ones only ever go at the end
+    pub const ALL: [Recorded; 14] = [
+        Recorded::Tick, Recorded::LeftPress, Recorded::LeftRelease, Recorded::RightPress, Recorded::RightRelease, Recorded::DownPress, Recorded::DownRelease,
+        Recorded::Rotate, Recorded::HardDrop, Recorded::SonicDrop, Recorded::Hold, Recorded::UseItem, Recorded::Pause, Recorded::RotateCcw,
+    ];
+
+    pub fn apply<const W: usize, const H: usize>(&self, tetris: &mut Tetris<W, H>) {
+        match self {
+            Recorded::Tick => tetris.receive_tick(),
+            Recorded::LeftPress => tetris.receive_left_press(),
+            Recorded::LeftRelease => tetris.receive_left_release(),
+            Recorded::RightPress => tetris.receive_right_press(),
+            Recorded::RightRelease => tetris.receive_right_release(),
+            Recorded::DownPress => tetris.receive_down_press(),
+            Recorded::DownRelease => tetris.receive_down_release(),
+            Recorded::Rotate => tetris.receive_rotate(),
+            Recorded::HardDrop => tetris.receive_hard_drop(),
+            Recorded::SonicDrop => tetris.receive_sonic_drop(),
+            Recorded::Hold => tetris.receive_hold(),
+            Recorded::UseItem => tetris.receive_use_item(),
+            Recorded::Pause => tetris.receive_pause(),
+            Recorded::RotateCcw => tetris.receive_rotate_ccw(),
+        }
+    }
+}
+
+// The inputs of a game and when on its clock each one came in. Every copy of the game shares the
+// list, and only goes as far into it as the copy had gotten when it was made
+#[derive(Clone, Debug, Default)]
+pub struct Recording {
+    inputs: Arc<Mutex<Vec<(Duration, Recorded)>>>,
+    len: usize,
+}
+
+impl Recording {
+    pub fn push(&mut self, time: Duration, input: Recorded) {
+        let mut inputs = self.inputs.lock().unwrap();
+        inputs.truncate(self.len);
+        inputs.push((time, input));
+        self.len += 1;
+    }
+
+    pub fn inputs(&self) -> Vec<(Duration, Recorded)> {
+        self.inputs.lock().unwrap()[..self.len].to_vec()
+    }
+}
+
+// A recorded leaderboard game, with what it ended on to check a replay of it against
+pub struct Replay {
+    pub mode: Mode,
+    pub seed: u64,
+    // the daily challenge's pieces come from the day
+    pub day: u64,
+    pub speed: f64,
+    pub handling: Handling,
+    pub score: usize,
+    pub hash: u64,
+    // the fingerprint of the rules it was played by, slowed down or not
+    pub rules: u64,
+    inputs: Vec<(Duration, Recorded)>,
+}
+
+impl Replay {
+    // None for a game that wasn't recorded
+    pub fn of<const W: usize, const H: usize>(tetris: &Tetris<W, H>, mode: Mode, day: u64, speed: f64, handling: Handling) -> Option<Self> {
+        let inputs = tetris.recording()?.inputs();
+        Some(Self { mode, seed: tetris.seed, day, speed, handling, score: tetris.score, hash: tetris.state_hash(), rules: tetris.rules().fingerprint(), inputs })
+    }
+
+    // Little endian numbers after the header, then every input as its place in Recorded::ALL and the
+    // micros since the one before it, seven bits to a byte
+    pub fn save(&self, file: &str) {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        let key = self.mode.key();
+        bytes.push(key.len() as u8);
+        bytes.extend(key.as_bytes());
+        for number in [self.seed, self.day, self.speed.to_bits(), self.handling.das as u64, self.handling.arr as u64] {
+            bytes.extend(number.to_le_bytes());
+        }
+        bytes.extend(self.handling.soft_drop_factor.to_le_bytes());
+        bytes.push(self.handling.tap_only as u8);
+        bytes.extend((self.score as u64).to_le_bytes());
+        bytes.extend(self.hash.to_le_bytes());
+        bytes.extend(self.rules.to_le_bytes());
+        bytes.extend((self.inputs.len() as u32).to_le_bytes());
+
+        let mut last = Duration::ZERO;
+        for (time, input) in self.inputs.iter() {
+            bytes.push(Recorded::ALL.iter().position(|other| other == input).unwrap() as u8);
+            let mut micros = time.saturating_sub(last).as_micros() as u64;
+            last = *time;
+            while micros >= 0x80 {
+                bytes.push(micros as u8 | 0x80);
+                micros >>= 7;
+            }
+            bytes.push(micros as u8);
+        }
+        save_or_report(file, bytes);
+    }
+
+    pub fn load(file: &str) -> Result<Self, String> {
+        let bytes = fs::read(file).map_err(|err| err.to_string())?;
+        let mut reader = Reader { bytes: &bytes };
+        if reader.take::<4>()? != *MAGIC {
+            return Err("not a replay".to_string());
+        }
+        let [version] = reader.take()?;
+        if version != VERSION {
+            return Err(format!("replay version {version} isn't supported"));
+        }
+        let [len] = reader.take()?;
+        let key = String::from_utf8_lossy(reader.take_slice(len as usize)?).to_string();
+        let mode = [Mode::Marathon, Mode::Sprint, Mode::DigRace, Mode::Daily].into_iter()
+            .find(|mode| mode.key() == key)
+            .ok_or(format!("there are no replays of {key}"))?;
+        let seed = reader.u64()?;
+        let day = reader.u64()?;
+        let speed = f64::from_bits(reader.u64()?);
+        let das = reader.u64()? as u128;
+        let arr = reader.u64()? as u128;
+        let soft_drop_factor = u32::from_le_bytes(reader.take()?);
+        let [tap_only] = reader.take()?;
+        let handling = Handling { das, arr, soft_drop_factor, tap_only: tap_only != 0 };
+        let score = reader.u64()? as usize;
+        let hash = reader.u64()?;
+        let rules = reader.u64()?;
+
+        let count = u32::from_le_bytes(reader.take()?);
+        let mut inputs = Vec::with_capacity(count as usize);
+        let mut time = Duration::ZERO;
+        for _ in 0..count {
+            let [input] = reader.take()?;
+            let input = *Recorded::ALL.get(input as usize).ok_or(format!("unknown input {input}"))?;
+            let mut micros = 0u64;
+            for shift in (0..64).step_by(7) {
+                let [byte] = reader.take()?;
+                micros |= ((byte & 0x7f) as u64) << shift;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            time += Duration::from_micros(micros);
+            inputs.push((time, input));
+        }
+        Ok(Self { mode, seed, day, speed, handling, score, hash, rules, inputs })
+    }
+
+    // Plays the game again from the start with nothing but the inputs, as fast as it goes, by `rules` the way
+    // the game would have been. A replay that doesn't end where the game did was either tampered with or the
+    // game doesn't play the same anymore
+    pub fn verify(&self, rules: &RuleSet) -> Result<(), String> {
+        let mut tetris = self.mode.ranked_game(rules, self.seed, self.day).unwrap();
+        tetris.set_handling(self.handling);
+        if self.speed < 1.0 {
+            tetris.slow_down(self.speed);
+        }
+        if tetris.rules().fingerprint() != self.rules {
+            return Err("the replay was played by other rules".to_string());
+        }
+        tetris.start();
+        for (time, input) in self.inputs.iter() {
+            tetris.replay(*time, *input);
+            while tetris.poll_event().is_some() {}
+        }
+
+        if tetris.score != self.score {
+            return Err(format!("the replay scores {score} rather than {expected}", score = tetris.score, expected = self.score));
+        }
+        if tetris.state_hash() != self.hash {
+            return Err(format!("the replay ends on {hash:016x} rather than {expected:016x}", hash = tetris.state_hash(), expected = self.hash));
+        }
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take_slice(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < len {
+            return Err("the replay ends too early".to_string());
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take_slice(N)?.try_into().unwrap())
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // the benchmarks bring this file in without running its tests, so the imports go where they're used
+    #[test]
+    fn recorded_bot_game_verifies() {
+        use super::*;
+        use crate::bot::{self, Bot, Difficulty};
+        use crate::game::GameState;
+
+        let rules = RuleSet::default();
+        let mut tetris = Mode::Marathon.ranked_game(&rules, 7, 0).unwrap();
+        tetris.record();
+        tetris.start();
+        let bot = Bot::new(Difficulty::Insane);
+        for _ in 0..40 {
+            let Some(placement) = bot.best_placement(&tetris).filter(|_| matches!(tetris.state, GameState::RUNNING)) else {
+                break;
+            };
+            for tap in placement.inputs.into_iter().flat_map(bot::taps) {
+                tetris.receive(*tap);
+            }
+            tetris.receive(Recorded::HardDrop);
+            tetris.receive(Recorded::Tick);
+            while tetris.poll_event().is_some() {}
+        }
+        assert!(tetris.pieces > 1);
+
+        let file = std::env::temp_dir().join("rustris-test-replay.bin");
+        let file = file.to_str().unwrap();
+        Replay::of(&tetris, Mode::Marathon, 0, 1.0, Handling::default()).unwrap().save(file);
+        let replay = Replay::load(file).unwrap();
+        assert_eq!(replay.verify(&rules), Ok(()));
+        assert!(replay.verify(&RuleSet { scoring: [0, 0, 0, 0, 0], ..rules }).is_err());
+    }
+}
@@ -0,0 +1,469 @@
+use std::fs;
+use crate::pieces::PIECE_SETS;
+
+const RULES_FILE: &str = "rules.toml";
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Randomizer {
+    Random,
+    SevenBag,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Rotation {
+    // a rotation that doesn't fit is not made
+    Simple,
+    // a rotation that doesn't fit gets nudged a cell to the side or up, the I two cells to the side as well
+    Nudge,
+    // a rotation that doesn't fit tries the SRS wall kicks, counting the orientation pieces come in as the spawn state
+    Srs,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PieceSet {
+    Tetrominoes,
+    Pentominoes,
+    // one of the PIECE_SETS
+    Custom(usize),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Hold {
+    Disabled,
+    // once, then not again until the piece locks
+    Once,
+    Unlimited,
+}
+
+// What the rest of the field does once full rows are cleared
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ClearGravity {
+    // everything above comes down by the rows cleared below it, leaving holes where they were
+    Naive,
+    // blocks that touch fall together until they land, which can fill rows again for a chain
+    Sticky,
+    // like sticky, with only blocks of the same color holding on to each other
+    Cascade,
+}
+
+#[derive(Clone, Debug)]
+pub struct RuleSet {
+    // millis per row for each level, the last entry holds for every level after it.
+    // Pieces fall several rows in one tick when it's shorter than a tick, straight down at 0 and not at all at `inf`.
+    pub gravity: Vec<f64>,
+    pub lines_per_level: usize,
+    pub soft_drop_delay: u128,
+    // how long a piece can rest on the stack before it locks
+    pub lock_delay: u128,
+    // how many times moving or rotating a resting piece starts its lock delay over
+    pub lock_resets: usize,
+    // ARE, the pause between a piece locking and the next one coming in
+    pub entry_delay: u128,
+    // how long cleared lines stay on the field before the stack falls, on top of the entry delay
+    pub line_clear_delay: u128,
+    pub clear_gravity: ClearGravity,
+    // score for clearing 0, 1, 2, 3 and 4 lines at once
+    pub scoring: [usize; 5],
+    // score for T-spins clearing 0, 1, 2 and 3 lines, None scores them like any other clear
+    pub t_spin_scoring: Option<[usize; 4]>,
+    // tetrises and T-spin clears right after another one score half again as much
+    pub back_to_back_bonus: bool,
+    // added for every clear in a row before this one
+    pub combo_bonus: usize,
+    pub pieces: PieceSet,
+    pub randomizer: Randomizer,
+    pub rotation: Rotation,
+    // times a piece may get kicked a cell up when a rotation doesn't fit, TGM style
+    pub floor_kicks: usize,
+    pub hard_drop: bool,
+    // drops the piece all the way down without locking it
+    pub sonic_drop: bool,
+    pub hold: Hold,
+    pub ghost: bool,
+    // pieces shown ahead, the next one included
+    pub previews: usize,
+    // None spawns pieces in the middle column
+    pub spawn_column: Option<usize>,
+    // row the centre of a new piece comes in on, whatever sticks out above it starts over the top of the field
+    pub spawn_row: usize,
+    // pieces come in flat side down instead of the way their shapes are defined
+    pub flat_spawn: bool,
+    // plays the mirror image of the game, pieces mirrored and spawning on the other side of the middle
+    pub mirror: bool,
+    // percent chance for the hole to move with every garbage row after the first one of an attack,
+    // 0 digs a clean column, 100 a random hole on every row
+    pub garbage_messiness: usize,
+    // millis between garbage rows pushing up from the bottom on their own, 0 for never
+    pub garbage_interval: u128,
+    pub t_spins: bool,
+    // percent chance for a line clear to hand out an item while none is held, 0 plays without items
+    pub item_chance: usize,
+    // the score for a clear gets multiplied by the level plus one
+    pub level_multiplier: bool,
+    // pieces change colors with every level, stack included
+    pub level_colors: bool,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self {
+            gravity: vec![1000.0],
+            lines_per_level: 10,
+            soft_drop_delay: 100,
+            lock_delay: 0,
+            lock_resets: 0,
+            entry_delay: 0,
+            line_clear_delay: 0,
+            clear_gravity: ClearGravity::Naive,
+            scoring: [0, 1, 2, 3, 4],
+            t_spin_scoring: None,
+            back_to_back_bonus: false,
+            combo_bonus: 0,
+            pieces: PieceSet::Tetrominoes,
+            randomizer: Randomizer::Random,
+            rotation: Rotation::Nudge,
+            floor_kicks: 2,
+            hard_drop: true,
+            sonic_drop: true,
+            hold: Hold::Disabled,
+            ghost: false,
+            previews: 1,
+            spawn_column: None,
+            spawn_row: 0,
+            flat_spawn: false,
+            mirror: false,
+            garbage_messiness: 0,
+            garbage_interval: 0,
+            t_spins: true,
+            item_chance: 0,
+            level_multiplier: false,
+            level_colors: false,
+        }
+    }
+}
+
+impl RuleSet {
+    // The NES game: its gravity table, no hard drop, pieces lock as soon as they land
+    pub fn classic() -> Self {
+        Self {
+            gravity: gravity_curve("nes").unwrap(),
+            lines_per_level: 10,
+            soft_drop_delay: 33,
+            lock_delay: 0,
+            lock_resets: 0,
+            // 10 frames
+            entry_delay: 167,
+            // 20 frames
+            line_clear_delay: 333,
+            clear_gravity: ClearGravity::Naive,
+            scoring: [0, 40, 100, 300, 1200],
+            t_spin_scoring: None,
+            back_to_back_bonus: false,
+            combo_bonus: 0,
+            pieces: PieceSet::Tetrominoes,
+            randomizer: Randomizer::Random,
+            rotation: Rotation::Simple,
+            floor_kicks: 0,
+            hard_drop: false,
+            sonic_drop: false,
+            hold: Hold::Disabled,
+            ghost: false,
+            previews: 1,
+            spawn_column: None,
+            spawn_row: 0,
+            flat_spawn: false,
+            mirror: false,
+            garbage_messiness: 0,
+            garbage_interval: 0,
+            t_spins: false,
+            item_chance: 0,
+            level_multiplier: true,
+            level_colors: true,
+        }
+    }
+
+    // Defaults with whatever rules.toml next to the game overrides
+    pub fn load() -> Self {
+        match fs::read_to_string(RULES_FILE) {
+            Ok(config) => Self::parse(&config),
+            Err(_) => Self::default(),
+        }
+    }
+
+    // Understands the flat `key = value` part of TOML, which is all the rules need.
+    // Lines that don't make sense are reported and skipped.
+    pub fn parse(config: &str) -> Self {
+        let mut rules = Self::default();
+
+        for (number, line) in config.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                eprintln!("{RULES_FILE}:{line}: expected `key = value`", line = number + 1);
+                continue;
+            };
+
+            if rules.set(key.trim(), value.trim()).is_none() {
+                eprintln!("{RULES_FILE}:{line}: can't use `{key}` = `{value}`", line = number + 1, key = key.trim(), value = value.trim());
+            }
+        }
+        rules
+    }
+
+    // Stretches every delay, so the game plays at `speed` times the pace it was made for. Falling
+    // straight down stays that way
+    pub fn slow_down(&mut self, speed: f64) {
+        for delay in self.gravity.iter_mut() {
+            *delay /= speed;
+        }
+        let stretch = |delay: u128| (delay as f64 / speed) as u128;
+        self.soft_drop_delay = stretch(self.soft_drop_delay);
+        self.lock_delay = stretch(self.lock_delay);
+        self.entry_delay = stretch(self.entry_delay);
+        self.line_clear_delay = stretch(self.line_clear_delay);
+        self.garbage_interval = stretch(self.garbage_interval);
+    }
+
+    // Stands for every rule at once and comes out the same on every machine, to tell whether two
+    // games were played by the same rules
+    pub fn fingerprint(&self) -> u64 {
+        format!("{self:?}").bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+    }
+
+    pub fn gravity_delay(&self, level: usize) -> f64 {
+        self.gravity.get(level).or(self.gravity.last()).copied().unwrap_or(1000.0)
+    }
+
+    // What current official games play like
+    pub fn guideline() -> Self {
+        Self {
+            gravity: gravity_curve("guideline").unwrap(),
+            lines_per_level: 10,
+            soft_drop_delay: 50,
+            lock_delay: 500,
+            lock_resets: 15,
+            entry_delay: 0,
+            line_clear_delay: 0,
+            clear_gravity: ClearGravity::Naive,
+            scoring: [0, 100, 300, 500, 800],
+            t_spin_scoring: Some([400, 800, 1200, 1600]),
+            back_to_back_bonus: true,
+            combo_bonus: 50,
+            pieces: PieceSet::Tetrominoes,
+            randomizer: Randomizer::SevenBag,
+            rotation: Rotation::Srs,
+            floor_kicks: 0,
+            hard_drop: true,
+            sonic_drop: false,
+            hold: Hold::Once,
+            ghost: true,
+            previews: 5,
+            spawn_column: None,
+            spawn_row: 0,
+            flat_spawn: true,
+            mirror: false,
+            garbage_messiness: 0,
+            garbage_interval: 0,
+            t_spins: true,
+            item_chance: 0,
+            level_multiplier: true,
+            level_colors: false,
+        }
+    }
+
+    // Arcade style: gravity ramps up to pieces dropping straight down, with a lock delay to make up for it
+    pub fn master() -> Self {
+        Self {
+            gravity: vec![
+                1000.0, 800.0, 600.0, 450.0, 330.0, 240.0, 170.0, 120.0, 80.0, 50.0,
+                30.0, 20.0, 10.0, 5.0, 0.0,
+            ],
+            lines_per_level: 10,
+            soft_drop_delay: 0,
+            lock_delay: 500,
+            lock_resets: 0,
+            // 30 frames
+            entry_delay: 500,
+            // 41 frames
+            line_clear_delay: 683,
+            clear_gravity: ClearGravity::Naive,
+            scoring: [0, 10, 30, 60, 120],
+            t_spin_scoring: None,
+            back_to_back_bonus: false,
+            combo_bonus: 0,
+            pieces: PieceSet::Tetrominoes,
+            randomizer: Randomizer::SevenBag,
+            rotation: Rotation::Simple,
+            floor_kicks: 1,
+            hard_drop: true,
+            sonic_drop: true,
+            hold: Hold::Disabled,
+            ghost: true,
+            previews: 1,
+            spawn_column: None,
+            spawn_row: 0,
+            flat_spawn: false,
+            mirror: false,
+            garbage_messiness: 0,
+            garbage_interval: 0,
+            t_spins: false,
+            item_chance: 0,
+            level_multiplier: true,
+            level_colors: false,
+        }
+    }
+
+    // Five cells to a piece, on a wider field
+    pub fn pentomino() -> Self {
+        Self { pieces: PieceSet::Pentominoes, ..Self::default() }
+    }
+
+    // Garbage keeps rising from below, the game lasts for as long as the stack stays under the top
+    pub fn survival() -> Self {
+        Self { garbage_interval: 5000, ..Self::default() }
+    }
+
+    // Nothing falls on its own, pieces only go down when they are dropped
+    pub fn sandbox() -> Self {
+        Self { gravity: vec![f64::INFINITY], hold: Hold::Unlimited, ghost: true, ..Self::default() }
+    }
+
+    // Casual versus where line clears now and then hand out an item
+    pub fn party() -> Self {
+        Self { item_chance: 30, ..Self::default() }
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Option<()> {
+        match key {
+            "gravity" => {
+                let gravity = gravity_curve(value.trim_matches('"')).or_else(|| parse_gravity(value))?;
+                if gravity.is_empty() || gravity.iter().any(|delay| delay.is_nan() || *delay < 0.0) {
+                    return None;
+                }
+                self.gravity = gravity;
+            },
+            "lines_per_level" => self.lines_per_level = value.parse().ok().filter(|lines| *lines > 0)?,
+            "soft_drop_delay" => self.soft_drop_delay = value.parse().ok()?,
+            "lock_delay" => self.lock_delay = value.parse().ok()?,
+            "lock_resets" => self.lock_resets = value.parse().ok()?,
+            "entry_delay" => self.entry_delay = value.parse().ok()?,
+            "line_clear_delay" => self.line_clear_delay = value.parse().ok()?,
+            "scoring" => self.scoring = parse_list(value)?.try_into().ok()?,
+            "t_spin_scoring" => self.t_spin_scoring = Some(parse_list(value)?.try_into().ok()?),
+            "back_to_back_bonus" => self.back_to_back_bonus = value.parse().ok()?,
+            "combo_bonus" => self.combo_bonus = value.parse().ok()?,
+            "pieces" => {
+                self.pieces = match value.trim_matches('"') {
+                    "tetrominoes" => PieceSet::Tetrominoes,
+                    "pentominoes" => PieceSet::Pentominoes,
+                    name => PieceSet::Custom(PIECE_SETS.iter().position(|set| set.name == name)?),
+                };
+            },
+            "randomizer" => {
+                self.randomizer = match value.trim_matches('"') {
+                    "random" => Randomizer::Random,
+                    "7-bag" => Randomizer::SevenBag,
+                    _ => return None,
+                };
+            },
+            "clear_gravity" => {
+                self.clear_gravity = match value.trim_matches('"') {
+                    "naive" => ClearGravity::Naive,
+                    "sticky" => ClearGravity::Sticky,
+                    "cascade" => ClearGravity::Cascade,
+                    _ => return None,
+                };
+            },
+            "rotation" => {
+                self.rotation = match value.trim_matches('"') {
+                    "simple" => Rotation::Simple,
+                    "nudge" => Rotation::Nudge,
+                    "srs" => Rotation::Srs,
+                    _ => return None,
+                };
+            },
+            "floor_kicks" => self.floor_kicks = value.parse().ok()?,
+            "hard_drop" => self.hard_drop = value.parse().ok()?,
+            "sonic_drop" => self.sonic_drop = value.parse().ok()?,
+            "hold" => {
+                self.hold = match value.trim_matches('"') {
+                    "disabled" | "false" => Hold::Disabled,
+                    "once" | "true" => Hold::Once,
+                    "unlimited" => Hold::Unlimited,
+                    _ => return None,
+                };
+            },
+            "ghost" => self.ghost = value.parse().ok()?,
+            "spawn_column" => self.spawn_column = Some(value.parse().ok()?),
+            "spawn_row" => self.spawn_row = value.parse().ok()?,
+            "flat_spawn" => self.flat_spawn = value.parse().ok()?,
+            "mirror" => self.mirror = value.parse().ok()?,
+            "previews" => self.previews = value.parse().ok().filter(|previews| *previews > 0)?,
+            "garbage_messiness" => self.garbage_messiness = value.parse().ok().filter(|percent| *percent <= 100)?,
+            "garbage_interval" => self.garbage_interval = value.parse().ok()?,
+            "t_spins" => self.t_spins = value.parse().ok()?,
+            "item_chance" => self.item_chance = value.parse().ok().filter(|percent| *percent <= 100)?,
+            "level_multiplier" => self.level_multiplier = value.parse().ok()?,
+            "level_colors" => self.level_colors = value.parse().ok()?,
+            _ => return None,
+        }
+        Some(())
+    }
+}
+
+// The speed curves of other games, by name
+fn gravity_curve(name: &str) -> Option<Vec<f64>> {
+    let frames = |frames: &[u32]| frames.iter().map(|frames| *frames as f64 * 1000.0 / 60.0).collect();
+    match name {
+        // frames per row at 60 fps
+        "nes" => Some(frames(&[
+            48, 43, 38, 33, 28, 23, 18, 13, 8, 6,
+            5, 5, 5, 4, 4, 4, 3, 3, 3,
+            2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+            1,
+        ])),
+        "none" => Some(vec![f64::INFINITY]),
+        // (0.8 - (level - 1) * 0.007) ^ (level - 1) seconds per row, starting from level 1
+        "guideline" => Some((0..20).map(|level| (0.8 - level as f64 * 0.007).powi(level) * 1000.0).collect()),
+        _ => None,
+    }
+}
+
+// Either a plain list with one entry per level, or `level: millis` entries that hold until the next one,
+// like `[0: 800, 10: 83, 13: 67]`
+fn parse_gravity(value: &str) -> Option<Vec<f64>> {
+    if !value.contains(':') {
+        return parse_list(value);
+    }
+
+    let items = value.strip_prefix('[')?.strip_suffix(']')?;
+    let mut steps: Vec<(usize, f64)> = items.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            let (level, delay) = item.split_once(':')?;
+            Some((level.trim().parse().ok()?, delay.trim().parse().ok()?))
+        })
+        .collect::<Option<_>>()?;
+    steps.sort_by_key(|(level, _)| *level);
+
+    let mut gravity = vec![];
+    for (idx, (level, delay)) in steps.iter().enumerate() {
+        // levels before the first entry play at its speed
+        let until = steps.get(idx + 1).map_or(level + 1, |(next, _)| *next);
+        gravity.resize(until.max(gravity.len()), *delay);
+    }
+    Some(gravity)
+}
+
+fn parse_list<T: std::str::FromStr>(value: &str) -> Option<Vec<T>> {
+    let items = value.strip_prefix('[')?.strip_suffix(']')?;
+    items.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| item.parse().ok())
+        .collect()
+}
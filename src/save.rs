@@ -0,0 +1,9 @@
+use std::fs;
+
+// Writes one of the files the game keeps next to it. Nothing in them is worth ending the game over,
+// so a file that can't be written only gets a line on stderr
+pub fn save_or_report(file: &str, contents: impl AsRef<[u8]>) {
+    if let Err(err) = fs::write(file, contents) {
+        eprintln!("can't save {file}: {err}");
+    }
+}
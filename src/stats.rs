@@ -0,0 +1,31 @@
+use crate::game::Tetris;
+
+// How fast and how cleanly a game is being played, worked out from the game as it stands
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Performance {
+    pub pieces_per_second: f64,
+    // None while nothing was sent, which outside of matches is always
+    pub attack_per_minute: Option<f64>,
+    pub keys_per_piece: f64,
+}
+
+impl Performance {
+    pub fn of<const W: usize, const H: usize>(tetris: &Tetris<W, H>) -> Self {
+        let seconds = tetris.elapsed().as_secs_f64();
+        let per_second = |count: usize| if seconds > 0.0 {count as f64 / seconds} else {0.0};
+        Performance {
+            pieces_per_second: per_second(tetris.pieces),
+            attack_per_minute: Some(per_second(tetris.attack) * 60.0).filter(|_| tetris.attack > 0),
+            keys_per_piece: if tetris.pieces > 0 {tetris.keys as f64 / tetris.pieces as f64} else {0.0},
+        }
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("PPS {:.2}", self.pieces_per_second)];
+        if let Some(apm) = self.attack_per_minute {
+            lines.push(format!("APM {apm:.1}"));
+        }
+        lines.push(format!("KPP {:.2}", self.keys_per_piece));
+        lines
+    }
+}
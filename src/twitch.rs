@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use crate::game::Tetris;
+
+// plain IRC, since chat is only ever read
+const SERVER: &str = "irc.chat.twitch.tv:6667";
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Command {
+    Left,
+    Right,
+    Rotate,
+    Drop,
+}
+
+impl Command {
+    pub const ALL: [Command; 4] = [Command::Left, Command::Right, Command::Rotate, Command::Drop];
+
+    // What chat types to vote for it, the same in every language
+    pub fn word(&self) -> &'static str {
+        match self {
+            Command::Left => "left",
+            Command::Right => "right",
+            Command::Rotate => "rotate",
+            Command::Drop => "drop",
+        }
+    }
+
+    pub fn apply<const W: usize, const H: usize>(&self, tetris: &mut Tetris<W, H>) {
+        match self {
+            Command::Left => tetris.receive_left(),
+            Command::Right => tetris.receive_right(),
+            Command::Rotate => tetris.receive_rotate(),
+            Command::Drop => tetris.receive_hard_drop(),
+        }
+    }
+}
+
+// The votes of a Twitch channel's chat for the round going on, where only the latest vote of each
+// chatter counts
+pub struct ChatVotes {
+    votes: Arc<Mutex<HashMap<String, Command>>>,
+}
+
+impl ChatVotes {
+    // Joins the chat anonymously, so it needs no account. A chat that can't be reached just never votes
+    pub fn connect(channel: &str) -> ChatVotes {
+        let votes = Arc::new(Mutex::new(HashMap::new()));
+        let channel = channel.trim_start_matches('#').to_lowercase();
+
+        let voted = votes.clone();
+        thread::spawn(move || {
+            if let Err(err) = read_chat(&channel, &voted) {
+                eprintln!("can't read the chat of {channel}: {err}");
+            }
+        });
+        ChatVotes { votes }
+    }
+
+    // Votes for each of Command::ALL
+    pub fn tally(&self) -> [usize; 4] {
+        let mut tally = [0; 4];
+        for command in self.votes.lock().unwrap().values() {
+            tally[Command::ALL.iter().position(|other| other == command).unwrap()] += 1;
+        }
+        tally
+    }
+
+    // Ends the round with the command that got the most votes, ties going to the one listed first.
+    // None when nobody voted
+    pub fn take_winner(&self) -> Option<Command> {
+        let tally = self.tally();
+        self.votes.lock().unwrap().clear();
+        let most = *tally.iter().max().unwrap();
+        (most > 0).then(|| Command::ALL[tally.iter().position(|votes| *votes == most).unwrap()])
+    }
+}
+
+fn read_chat(channel: &str, votes: &Mutex<HashMap<String, Command>>) -> Result<(), String> {
+    let mut writer = TcpStream::connect(SERVER).map_err(|err| err.to_string())?;
+    let reader = BufReader::new(writer.try_clone().map_err(|err| err.to_string())?);
+    // any justinfan nick reads chat without logging in
+    let login = format!("NICK justinfan{number}\r\nJOIN #{channel}\r\n", number = rand::random::<u32>() % 100_000);
+    writer.write_all(login.as_bytes()).map_err(|err| err.to_string())?;
+
+    for line in reader.lines() {
+        let line = line.map_err(|err| err.to_string())?;
+        // every line ends in \r\n
+        let line = line.trim_end();
+        // the server drops whoever doesn't answer its pings
+        if let Some(server) = line.strip_prefix("PING ") {
+            writer.write_all(format!("PONG {server}\r\n").as_bytes()).map_err(|err| err.to_string())?;
+            continue;
+        }
+        // :nick!nick@nick.tmi.twitch.tv PRIVMSG #channel :message
+        let Some((source, message)) = line.strip_prefix(':').and_then(|line| line.split_once(&format!(" PRIVMSG #{channel} :"))) else {
+            continue;
+        };
+        let chatter = source.split('!').next().unwrap_or(source);
+        let word = message.split_whitespace().next().unwrap_or_default().to_lowercase();
+        if let Some(command) = Command::ALL.into_iter().find(|command| command.word() == word) {
+            votes.lock().unwrap().insert(chatter.to_string(), command);
+        }
+    }
+    Err("the chat closed the connection".to_string())
+}
@@ -0,0 +1,37 @@
+use std::collections::VecDeque;
+use crate::game::{Snapshot, Tetris};
+
+// Keeps the state the game was in when each of the last few pieces spawned
+pub struct UndoHistory<const W: usize, const H: usize> {
+    snapshots: VecDeque<Snapshot<W, H>>,
+    depth: usize,
+}
+
+impl<const W: usize, const H: usize> UndoHistory<W, H> {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            snapshots: VecDeque::with_capacity(depth + 1),
+            depth: depth + 1,
+        }
+    }
+
+    // Has to run before the game is touched, so the snapshot has the new piece still at its spawn
+    pub fn record(&mut self, tetris: &Tetris<W, H>) {
+        if self.snapshots.back().is_some_and(|snapshot| snapshot.pieces() == tetris.pieces) {
+            return;
+        }
+        if self.snapshots.len() == self.depth {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(tetris.snapshot());
+    }
+
+    // The spawn of the piece placed last, for the game to go back to
+    pub fn undo(&mut self) -> Option<Snapshot<W, H>> {
+        if self.snapshots.len() < 2 {
+            return None;
+        }
+        self.snapshots.pop_back();
+        self.snapshots.back().cloned()
+    }
+}
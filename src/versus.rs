@@ -0,0 +1,271 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::bot::Bot;
+use crate::game::{GameEvent, GameState, Tetris};
+use crate::locale::{tr, tr_with};
+use crate::replay::Recorded;
+use crate::rules::RuleSet;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Targeting {
+    Random,
+    Attacker,
+    MostKos,
+    EvenSplit,
+}
+
+impl Targeting {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Targeting::Random => tr("targeting.random"),
+            Targeting::Attacker => tr("targeting.attacker"),
+            Targeting::MostKos => tr("targeting.most_kos"),
+            Targeting::EvenSplit => tr("targeting.even_split"),
+        }
+    }
+
+    pub fn next(&self) -> Targeting {
+        match self {
+            Targeting::Random => Targeting::Attacker,
+            Targeting::Attacker => Targeting::MostKos,
+            Targeting::MostKos => Targeting::EvenSplit,
+            Targeting::EvenSplit => Targeting::Random,
+        }
+    }
+}
+
+// Evens out a match between players of different strength
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Handicap {
+    None,
+    GarbageRows(usize),
+    // percent of the usual attack
+    Attack(usize),
+    // percent of the usual falling speed
+    Gravity(usize),
+}
+
+impl Handicap {
+    pub fn name(&self) -> String {
+        match self {
+            Handicap::None => tr("handicap.none").to_string(),
+            Handicap::GarbageRows(rows) => tr_with("handicap.garbage_rows", &[("rows", rows)]),
+            Handicap::Attack(percent) => tr_with("handicap.attack", &[("percent", percent)]),
+            Handicap::Gravity(percent) => tr_with("handicap.gravity", &[("percent", percent)]),
+        }
+    }
+
+    pub fn next(&self) -> Handicap {
+        match self {
+            Handicap::None => Handicap::GarbageRows(4),
+            Handicap::GarbageRows(4) => Handicap::GarbageRows(8),
+            Handicap::GarbageRows(_) => Handicap::Attack(50),
+            Handicap::Attack(50) => Handicap::Attack(150),
+            Handicap::Attack(_) => Handicap::Gravity(50),
+            Handicap::Gravity(_) => Handicap::None,
+        }
+    }
+
+    fn rules(&self, rules: RuleSet) -> RuleSet {
+        let Handicap::Gravity(percent) = *self else {
+            return rules;
+        };
+        let gravity = rules.gravity.iter().map(|delay| delay * 100.0 / percent as f64).collect();
+        RuleSet { gravity, ..rules }
+    }
+
+    fn attack(&self, attack: usize) -> usize {
+        match self {
+            Handicap::Attack(percent) => (attack * percent + 50) / 100,
+            _ => attack,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Player<const W: usize, const H: usize> {
+    pub tetris: Tetris<W, H>,
+    pub bot: Option<Bot>,
+    pub kos: usize,
+    handicap: Handicap,
+    last_attacker: Option<usize>,
+    knocked_out: bool,
+}
+
+impl<const W: usize, const H: usize> Player<W, H> {
+    fn new(rules: RuleSet, bot: Option<Bot>, handicap: Handicap) -> Self {
+        let mut tetris = Tetris::with_rules(handicap.rules(rules));
+        if let Handicap::GarbageRows(rows) = handicap {
+            for _ in 0..rows {
+                tetris.insert_garbage_row();
+            }
+        }
+
+        Self {
+            tetris,
+            bot,
+            kos: 0,
+            handicap,
+            last_attacker: None,
+            knocked_out: false,
+        }
+    }
+
+    pub fn human(rules: RuleSet, handicap: Handicap) -> Self {
+        Self::new(rules, None, handicap)
+    }
+
+    pub fn cpu(rules: RuleSet, bot: Bot, handicap: Handicap) -> Self {
+        Self::new(rules, Some(bot), handicap)
+    }
+
+    pub fn is_alive(&self) -> bool {
+        !matches!(self.tetris.state, GameState::LOST)
+    }
+
+    // Whoever sent the garbage that topped them out, once they have
+    pub fn knocked_out_by(&self) -> Option<usize> {
+        self.last_attacker.filter(|_| !self.is_alive())
+    }
+}
+
+#[derive(Clone)]
+pub struct Match<const W: usize, const H: usize> {
+    pub players: Vec<Player<W, H>>,
+    pub targeting: Targeting,
+    split_offset: usize,
+    // picks the targets that are left to chance, from the players' seeds so a match plays out the same
+    // from the same ones
+    rng: StdRng,
+}
+
+impl<const W: usize, const H: usize> Match<W, H> {
+    pub fn new(players: Vec<Player<W, H>>, targeting: Targeting) -> Self {
+        let seed = players.iter().fold(0, |seed: u64, player| seed.rotate_left(17) ^ player.tetris.seed);
+        Self { players, targeting, split_offset: 0, rng: StdRng::seed_from_u64(seed) }
+    }
+
+    pub fn start(&mut self) {
+        for player in self.players.iter_mut() {
+            player.tetris.start();
+        }
+    }
+
+    pub fn receive_tick(&mut self) {
+        if self.is_over() {
+            return;
+        }
+
+        for player in self.players.iter_mut() {
+            if let Some(bot) = player.bot.as_mut() {
+                bot.drive(&mut player.tetris);
+            }
+            player.tetris.receive_tick();
+        }
+
+        self.exchange_garbage();
+    }
+
+    // Stops or restarts everyone still in the match at once
+    pub fn set_paused(&mut self, paused: bool) {
+        for player in self.players.iter_mut() {
+            if matches!(player.tetris.state, GameState::RUNNING) == paused && player.is_alive() {
+                player.tetris.receive(Recorded::Pause);
+            }
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.players.iter().filter(|player| player.is_alive()).count() <= 1
+    }
+
+    pub fn winner(&self) -> Option<usize> {
+        if !self.is_over() {
+            return None;
+        }
+        self.players.iter().position(|player| player.is_alive())
+    }
+
+    fn exchange_garbage(&mut self) {
+        for idx in 0..self.players.len() {
+            while let Some(event) = self.players[idx].tetris.poll_event() {
+                let GameEvent::LinesCleared(lines) = event else {
+                    continue;
+                };
+                let attack = self.players[idx].handicap.attack(attack_for(lines));
+                let attack = self.players[idx].tetris.counter_garbage(attack);
+                if attack == 0 {
+                    continue;
+                }
+
+                for (target, lines) in self.targets(idx, attack) {
+                    self.players[target].tetris.receive_garbage(lines);
+                    self.players[target].last_attacker = Some(idx);
+                }
+            }
+        }
+
+        self.credit_knockouts();
+    }
+
+    fn targets(&mut self, attacker: usize, attack: usize) -> Vec<(usize, usize)> {
+        let opponents: Vec<usize> = (0..self.players.len())
+            .filter(|idx| *idx != attacker && self.players[*idx].is_alive())
+            .collect();
+
+        if opponents.is_empty() {
+            return vec![];
+        }
+
+        let random = opponents[self.rng.gen_range(0..opponents.len())];
+
+        match self.targeting {
+            Targeting::Random => vec![(random, attack)],
+            Targeting::Attacker => {
+                let target = self.players[attacker].last_attacker
+                    .filter(|idx| opponents.contains(idx))
+                    .unwrap_or(random);
+                vec![(target, attack)]
+            },
+            Targeting::MostKos => {
+                let most = opponents.iter().map(|idx| self.players[*idx].kos).max().unwrap_or(0);
+                let leaders: Vec<usize> = opponents.into_iter()
+                    .filter(|idx| self.players[*idx].kos == most)
+                    .collect();
+                vec![(leaders[self.rng.gen_range(0..leaders.len())], attack)]
+            },
+            Targeting::EvenSplit => {
+                // remainders rotate between opponents so nobody is always the one getting the extra line
+                let (share, remainder) = (attack / opponents.len(), attack % opponents.len());
+                let targets = (0..opponents.len())
+                    .map(|k| (opponents[(self.split_offset + k) % opponents.len()], share + usize::from(k < remainder)))
+                    .filter(|(_, lines)| *lines > 0)
+                    .collect();
+                self.split_offset += remainder;
+                targets
+            },
+        }
+    }
+
+    fn credit_knockouts(&mut self) {
+        for idx in 0..self.players.len() {
+            if self.players[idx].is_alive() || self.players[idx].knocked_out {
+                continue;
+            }
+            self.players[idx].knocked_out = true;
+
+            if let Some(attacker) = self.players[idx].last_attacker {
+                self.players[attacker].kos += 1;
+            }
+        }
+    }
+}
+
+fn attack_for(lines: usize) -> usize {
+    match lines {
+        0 | 1 => 0,
+        2 => 1,
+        3 => 2,
+        _ => 4,
+    }
+}